@@ -0,0 +1,1338 @@
+use super::{
+    escape_pointer, for_each_subschema, is_map_key_rule, subschemas, InstanceType, Schema, SchemaObject,
+    SchemaRewriteError, SingleOrVec,
+};
+use serde_json::Value;
+use std::fmt;
+
+/// A problem found in a schema by [`check_structural`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaIssue {
+    /// JSON pointer to the offending subschema
+    pub pointer: String,
+    /// What is wrong with the subschema
+    pub kind: SchemaIssueKind,
+}
+
+/// The kinds of problems reported as a [`SchemaIssue`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SchemaIssueKind {
+    /// The node defines both `enum` and `properties`, which contradict each other
+    EnumWithProperties,
+    /// The node requires a property that it doesn't define in `properties`
+    RequiredWithoutProperty {
+        /// Name of the missing property
+        name: String,
+    },
+    /// The node's `pattern` isn't a valid RE2 regular expression
+    InvalidPattern {
+        /// The offending pattern
+        pattern: String,
+        /// Why the pattern was rejected
+        error: String,
+    },
+    /// The node is nullable both through `nullable` and through `null` in its `type`
+    ConflictingNullability,
+    /// The name of the property doesn't match the pattern passed to [`invalid_property_names`]
+    InvalidPropertyName {
+        /// Name of the property
+        name: String,
+    },
+    /// The node's `default` isn't valid according to the node itself
+    InvalidDefault {
+        /// Why the default was rejected
+        reason: String,
+    },
+    /// The node is a list with `x-kubernetes-list-type: map`, but one of its `x-kubernetes-list-map-keys`
+    /// can't be used as a key (or the keys are missing entirely, in which case `key` is `None`)
+    InvalidListMapKey {
+        /// The offending key
+        key: Option<String>,
+        /// Why the key can't be used
+        reason: String,
+    },
+    /// The property has a `default`, but is also listed in the `required` properties of its object
+    RequiredWithDefault,
+    /// A value passed to [`validate_instance`](super::validate_instance) isn't valid according to the node
+    InvalidValue {
+        /// Why the value was rejected, including its field path
+        reason: String,
+    },
+}
+
+impl fmt::Display for SchemaIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "/"
+        } else {
+            &self.pointer
+        };
+        match &self.kind {
+            SchemaIssueKind::EnumWithProperties => {
+                write!(f, "{pointer}: node defines both `enum` and `properties`")
+            }
+            SchemaIssueKind::RequiredWithoutProperty { name } => {
+                write!(
+                    f,
+                    "{pointer}: `required` lists `{name}`, which is missing from `properties`"
+                )
+            }
+            SchemaIssueKind::InvalidPattern { pattern, error } => {
+                write!(f, "{pointer}: `{pattern}` is not a valid RE2 pattern: {error}")
+            }
+            SchemaIssueKind::ConflictingNullability => {
+                write!(f, "{pointer}: node has both `nullable` and a `null` type")
+            }
+            SchemaIssueKind::InvalidPropertyName { name } => {
+                write!(f, "{pointer}: `{name}` is not a valid property name")
+            }
+            SchemaIssueKind::InvalidDefault { reason } => {
+                write!(f, "{pointer}: `default` doesn't match the schema: {reason}")
+            }
+            SchemaIssueKind::InvalidListMapKey {
+                key: Some(key),
+                reason,
+            } => {
+                write!(f, "{pointer}: list map key `{key}` {reason}")
+            }
+            SchemaIssueKind::InvalidListMapKey { key: None, reason } => {
+                write!(f, "{pointer}: list map {reason}")
+            }
+            SchemaIssueKind::RequiredWithDefault => {
+                write!(f, "{pointer}: property is `required`, but has a `default`")
+            }
+            SchemaIssueKind::InvalidValue { reason } => write!(f, "{pointer}: {reason}"),
+        }
+    }
+}
+
+/// Check a schema for violations of Kubernetes' structural schema rules
+///
+/// Schemas generated with [`StructuralSchemaRewriter`](super::StructuralSchemaRewriter) should never
+/// produce any issues, so this is mostly useful for validating hand-written or post-processed schemas.
+///
+/// ```
+/// use kube_core::schema::{check_structural, SchemaIssueKind};
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "enum": [{}],
+///     "properties": { "a": { "type": "string" } },
+/// });
+/// let issues = check_structural(&schema);
+/// assert_eq!(issues.len(), 1);
+/// assert_eq!(issues[0].pointer, "");
+/// assert_eq!(issues[0].kind, SchemaIssueKind::EnumWithProperties);
+/// ```
+pub fn check_structural(schema: &Value) -> Vec<SchemaIssue> {
+    let mut issues = Vec::new();
+    for_each_subschema(schema, "", &mut |pointer, subschema| {
+        let Ok(Schema::Object(node)) = serde_json::from_value::<Schema>(subschema.clone()) else {
+            return;
+        };
+        for kind in check_node(&node) {
+            issues.push(SchemaIssue {
+                pointer: pointer.to_string(),
+                kind,
+            });
+        }
+    });
+    issues
+}
+
+#[cfg(feature = "schema-regex")]
+#[cfg_attr(docsrs, doc(cfg(feature = "schema-regex")))]
+/// Check that every `pattern` in a schema is a valid RE2 regular expression
+///
+/// The apiserver evaluates `pattern` with Go's RE2-based `regexp` package, which doesn't support
+/// PCRE features like lookaround or backreferences, and rejects CRDs with patterns that it can't compile.
+/// Patterns are checked with the [`regex`](https://docs.rs/regex) crate, whose syntax closely follows RE2.
+///
+/// ```
+/// use kube_core::schema::{invalid_patterns, SchemaIssueKind};
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "properties": {
+///         "name": { "type": "string", "pattern": "^[a-z]+$" },
+///         "password": { "type": "string", "pattern": "^(?=.*[0-9]).*$" },
+///     },
+/// });
+/// let issues = invalid_patterns(&schema);
+/// assert_eq!(issues.len(), 1);
+/// assert_eq!(issues[0].pointer, "/properties/password");
+/// assert!(matches!(issues[0].kind, SchemaIssueKind::InvalidPattern { .. }));
+/// ```
+pub fn invalid_patterns(schema: &Value) -> Vec<SchemaIssue> {
+    let mut issues = Vec::new();
+    for_each_subschema(schema, "", &mut |pointer, subschema| {
+        let Some(Value::String(pattern)) = subschema.get("pattern") else {
+            return;
+        };
+        if let Err(error) = regex::Regex::new(pattern) {
+            issues.push(SchemaIssue {
+                pointer: pointer.to_string(),
+                kind: SchemaIssueKind::InvalidPattern {
+                    pattern: pattern.clone(),
+                    error: error.to_string(),
+                },
+            });
+        }
+    });
+    issues
+}
+
+/// Check that every `default` in a schema is valid according to the schema that it is defined in
+///
+/// The apiserver rejects CRDs with defaults that don't match their own schema, such as a string default of
+/// an integer field. Defaults are checked against the `type`, `nullable`, `enum` and range keywords
+/// (`minimum`, `maxLength`, `maxItems` and so on), as well as the `properties`, `required` and `items` of
+/// object and array defaults.
+///
+/// ```
+/// use kube_core::schema::{invalid_defaults, SchemaIssueKind};
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "properties": {
+///         "replicas": { "type": "integer", "default": "one" },
+///         "image": { "type": "string", "default": "nginx" },
+///     },
+/// });
+/// let issues = invalid_defaults(&schema);
+/// assert_eq!(issues.len(), 1);
+/// assert_eq!(issues[0].pointer, "/properties/replicas");
+/// assert!(matches!(issues[0].kind, SchemaIssueKind::InvalidDefault { .. }));
+/// ```
+pub fn invalid_defaults(schema: &Value) -> Vec<SchemaIssue> {
+    let mut issues = Vec::new();
+    for_each_subschema(schema, "", &mut |pointer, subschema| {
+        let Some(default) = subschema.get("default") else {
+            return;
+        };
+        if let Err(SchemaIssue {
+            kind: SchemaIssueKind::InvalidValue { reason },
+            ..
+        }) = check_value(subschema, default, pointer, "")
+        {
+            issues.push(SchemaIssue {
+                pointer: pointer.to_string(),
+                kind: SchemaIssueKind::InvalidDefault { reason },
+            });
+        }
+    });
+    issues
+}
+
+/// Find properties that are `required`, but also have a `default`
+///
+/// The apiserver applies defaults before validating an object, so a property with a `default` is never
+/// missing, and listing it in `required` contradicts the `default`. Only the `required` properties of the
+/// objects themselves are considered, not those of their enum variants. Such properties can be made optional
+/// with [`make_defaulted_optional`](super::make_defaulted_optional).
+///
+/// ```
+/// use kube_core::schema::{required_with_defaults, SchemaIssueKind};
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "properties": {
+///         "replicas": { "type": "integer", "default": 1 },
+///         "image": { "type": "string" },
+///     },
+///     "required": ["replicas", "image"],
+/// });
+/// let issues = required_with_defaults(&schema);
+/// assert_eq!(issues.len(), 1);
+/// assert_eq!(issues[0].pointer, "/properties/replicas");
+/// assert_eq!(issues[0].kind, SchemaIssueKind::RequiredWithDefault);
+/// ```
+pub fn required_with_defaults(schema: &Value) -> Vec<SchemaIssue> {
+    let mut issues = Vec::new();
+    for_each_subschema(schema, "", &mut |pointer, subschema| {
+        for name in defaulted_required(subschema) {
+            issues.push(SchemaIssue {
+                pointer: format!("{pointer}/properties/{}", escape_pointer(name)),
+                kind: SchemaIssueKind::RequiredWithDefault,
+            });
+        }
+    });
+    issues
+}
+
+/// The names of the `required` properties of `schema` that have a `default`
+pub(super) fn defaulted_required(schema: &Value) -> Vec<&str> {
+    let Some(Value::Array(required)) = schema.get("required") else {
+        return Vec::new();
+    };
+    required
+        .iter()
+        .filter_map(Value::as_str)
+        .filter(|name| {
+            schema
+                .get("properties")
+                .and_then(|properties| properties.get(name))
+                .is_some_and(|property| property.get("default").is_some())
+        })
+        .collect()
+}
+
+/// Check that `value` (located at the field path `path`) is valid according to `schema` (located at the JSON
+/// pointer `pointer`)
+///
+/// Fields that the apiserver would prune (because the schema doesn't define them) are rejected as well. The
+/// returned issue points to the subschema that rejected the value.
+pub(super) fn check_value(
+    schema: &Value,
+    value: &Value,
+    pointer: &str,
+    path: &str,
+) -> Result<(), SchemaIssue> {
+    let at = if path.is_empty() {
+        String::new()
+    } else {
+        format!(" at `{path}`")
+    };
+    let keyword = |keyword: &str| schema.get(keyword);
+    let number = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+    let count = |keyword: &str| schema.get(keyword).and_then(Value::as_u64);
+    let invalid = |reason: String| SchemaIssue {
+        pointer: pointer.to_string(),
+        kind: SchemaIssueKind::InvalidValue { reason },
+    };
+
+    if value.is_null() {
+        let nullable = keyword("nullable") == Some(&Value::Bool(true))
+            || keyword("x-kubernetes-preserve-unknown-fields") == Some(&Value::Bool(true));
+        if !nullable {
+            return Err(invalid(format!(
+                "null{at} isn't allowed, since the field isn't nullable"
+            )));
+        }
+        return Ok(());
+    }
+    if let Some(Value::Array(values)) = keyword("enum") {
+        if !values.contains(value) {
+            return Err(invalid(format!("{value}{at} isn't one of the `enum` values")));
+        }
+    }
+
+    if let Some(Value::Array(variants)) = keyword("allOf") {
+        for (index, variant) in variants.iter().enumerate() {
+            check_value(variant, value, &format!("{pointer}/allOf/{index}"), path)?;
+        }
+    }
+    if let Some(Value::Array(variants)) = keyword("anyOf") {
+        if !variants
+            .iter()
+            .any(|variant| check_value(variant, value, pointer, path).is_ok())
+        {
+            return Err(invalid(format!(
+                "the value{at} doesn't match any of the `anyOf` variants"
+            )));
+        }
+    }
+    if let Some(Value::Array(variants)) = keyword("oneOf") {
+        let matches = variants
+            .iter()
+            .filter(|variant| check_value(variant, value, pointer, path).is_ok())
+            .count();
+        if matches != 1 {
+            return Err(invalid(format!(
+                "the value{at} matches {matches} of the `oneOf` variants, rather than exactly one"
+            )));
+        }
+    }
+
+    let matches_type = match keyword("type").and_then(Value::as_str) {
+        Some("string") => value.is_string(),
+        Some("integer") => value.is_i64() || value.is_u64(),
+        Some("number") => value.is_number(),
+        Some("boolean") => value.is_boolean(),
+        Some("array") => value.is_array(),
+        Some("object") => value.is_object(),
+        _ if keyword("x-kubernetes-int-or-string") == Some(&Value::Bool(true)) => {
+            value.is_string() || value.is_i64() || value.is_u64()
+        }
+        _ => true,
+    };
+    if !matches_type {
+        let tpe = keyword("type").and_then(Value::as_str).unwrap_or("int-or-string");
+        return Err(invalid(format!("{value}{at} isn't of type {tpe}")));
+    }
+
+    match value {
+        Value::Number(number_value) => {
+            let value = number_value.as_f64().unwrap_or_default();
+            let exclusive = |keyword: &str| schema.get(keyword) == Some(&Value::Bool(true));
+            if let Some(minimum) = number("minimum") {
+                if value < minimum || (exclusive("exclusiveMinimum") && value == minimum) {
+                    return Err(invalid(format!(
+                        "{number_value}{at} is below the `minimum` of {minimum}"
+                    )));
+                }
+            }
+            if let Some(maximum) = number("maximum") {
+                if value > maximum || (exclusive("exclusiveMaximum") && value == maximum) {
+                    return Err(invalid(format!(
+                        "{number_value}{at} is above the `maximum` of {maximum}"
+                    )));
+                }
+            }
+        }
+        Value::String(string) => {
+            let length = string.chars().count() as u64;
+            if count("minLength").is_some_and(|min| length < min) {
+                return Err(invalid(format!("{value}{at} is shorter than `minLength`")));
+            }
+            if count("maxLength").is_some_and(|max| length > max) {
+                return Err(invalid(format!("{value}{at} is longer than `maxLength`")));
+            }
+        }
+        Value::Array(items) => {
+            let length = items.len() as u64;
+            if count("minItems").is_some_and(|min| length < min) {
+                return Err(invalid(format!("the array{at} has fewer items than `minItems`")));
+            }
+            if count("maxItems").is_some_and(|max| length > max) {
+                return Err(invalid(format!("the array{at} has more items than `maxItems`")));
+            }
+            if let Some(items_schema @ Value::Object(_)) = keyword("items") {
+                let items_pointer = format!("{pointer}/items");
+                for (index, item) in items.iter().enumerate() {
+                    check_value(items_schema, item, &items_pointer, &format!("{path}[{index}]"))?;
+                }
+            }
+        }
+        Value::Object(fields) => {
+            let length = fields.len() as u64;
+            if count("minProperties").is_some_and(|min| length < min) {
+                return Err(invalid(format!(
+                    "the object{at} has fewer fields than `minProperties`"
+                )));
+            }
+            if count("maxProperties").is_some_and(|max| length > max) {
+                return Err(invalid(format!(
+                    "the object{at} has more fields than `maxProperties`"
+                )));
+            }
+            let required = keyword("required")
+                .and_then(Value::as_array)
+                .into_iter()
+                .flatten();
+            for name in required.filter_map(Value::as_str) {
+                if !fields.contains_key(name) {
+                    return Err(invalid(format!(
+                        "the object{at} is missing the required field `{name}`"
+                    )));
+                }
+            }
+            let properties = keyword("properties").and_then(Value::as_object);
+            let prunes_unknown_fields = (properties.is_some() || keyword("type") == Some(&"object".into()))
+                && keyword("x-kubernetes-preserve-unknown-fields") != Some(&Value::Bool(true))
+                && keyword("additionalProperties") != Some(&Value::Bool(true));
+            for (name, field) in fields {
+                let field_schema = match properties.and_then(|properties| properties.get(name)) {
+                    Some(property) => {
+                        Some((property, format!("{pointer}/properties/{}", escape_pointer(name))))
+                    }
+                    None => keyword("additionalProperties")
+                        .filter(|values| values.is_object())
+                        .map(|values| (values, format!("{pointer}/additionalProperties"))),
+                };
+                match field_schema {
+                    Some((field_schema, field_pointer)) => {
+                        check_value(field_schema, field, &field_pointer, &format!("{path}.{name}"))?
+                    }
+                    None if prunes_unknown_fields => {
+                        return Err(invalid(format!("the unknown field `{name}`{at} would be pruned")));
+                    }
+                    None => {}
+                }
+            }
+        }
+        Value::Null | Value::Bool(_) => {}
+    }
+    Ok(())
+}
+
+/// Check that the `x-kubernetes-list-map-keys` of every list with `x-kubernetes-list-type: map` are valid
+///
+/// The apiserver identifies the items of such lists by the values of their keys, so every key must be a
+/// scalar (string, integer, number or boolean) property of the items that is either required or has a
+/// `default`. CRDs violating this are rejected when they are applied.
+///
+/// ```
+/// use kube_core::schema::invalid_list_map_keys;
+///
+/// let schema = serde_json::json!({
+///     "type": "array",
+///     "x-kubernetes-list-type": "map",
+///     "x-kubernetes-list-map-keys": ["name"],
+///     "items": {
+///         "type": "object",
+///         "properties": { "port": { "type": "integer" } },
+///         "required": ["port"],
+///     },
+/// });
+/// let issues = invalid_list_map_keys(&schema);
+/// assert_eq!(issues.len(), 1);
+/// assert_eq!(
+///     issues[0].to_string(),
+///     "/: list map key `name` is not a property of the items"
+/// );
+/// ```
+pub fn invalid_list_map_keys(schema: &Value) -> Vec<SchemaIssue> {
+    let mut issues = Vec::new();
+    for_each_subschema(schema, "", &mut |pointer, subschema| {
+        if subschema.get("x-kubernetes-list-type").and_then(Value::as_str) != Some("map") {
+            return;
+        }
+        let mut report = |key: Option<&str>, reason: &str| {
+            issues.push(SchemaIssue {
+                pointer: pointer.to_string(),
+                kind: SchemaIssueKind::InvalidListMapKey {
+                    key: key.map(Into::into),
+                    reason: reason.into(),
+                },
+            })
+        };
+        let keys = match subschema.get("x-kubernetes-list-map-keys") {
+            Some(Value::Array(keys)) if !keys.is_empty() => keys,
+            _ => return report(None, "has no `x-kubernetes-list-map-keys`"),
+        };
+        let is_object = |items: &&Value| items.get("type").and_then(Value::as_str) == Some("object");
+        let Some(items) = subschema.get("items").filter(is_object) else {
+            return report(None, "items are not objects");
+        };
+        let required = items
+            .get("required")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        for key in keys.iter().filter_map(Value::as_str) {
+            let Some(property) = items.get("properties").and_then(|properties| properties.get(key)) else {
+                report(Some(key), "is not a property of the items");
+                continue;
+            };
+            let is_scalar = matches!(
+                property.get("type").and_then(Value::as_str),
+                Some("string" | "integer" | "number" | "boolean")
+            );
+            if !is_scalar {
+                report(Some(key), "is not a scalar property");
+            } else if !required.contains(&Value::from(key)) && property.get("default").is_none() {
+                report(Some(key), "is neither required nor has a default");
+            }
+        }
+    });
+    issues
+}
+
+#[cfg(feature = "schema-regex")]
+#[cfg_attr(docsrs, doc(cfg(feature = "schema-regex")))]
+/// A conservative pattern for property names, see [`invalid_property_names`]
+///
+/// Names matching it can be used in CEL rules, field paths and JSON pointers without escaping.
+pub const PROPERTY_NAME_PATTERN: &str = "^[A-Za-z_][A-Za-z0-9_-]*$";
+
+#[cfg(feature = "schema-regex")]
+#[cfg_attr(docsrs, doc(cfg(feature = "schema-regex")))]
+/// Check that the names of all properties in a schema match `pattern`
+///
+/// Serde's `rename` can produce property names that are awkward (or impossible) to refer to, for example
+/// names with slashes or dots in field paths, or names that aren't identifiers in CEL rules. Returns the
+/// JSON pointers of all properties whose names don't match, or an error if `pattern` isn't a valid regular
+/// expression. Use [`PROPERTY_NAME_PATTERN`] unless the CRD needs to allow other names.
+///
+/// ```
+/// use kube_core::schema::{invalid_property_names, PROPERTY_NAME_PATTERN};
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "properties": {
+///         "replicas": { "type": "integer" },
+///         "app.kubernetes.io/name": { "type": "string" },
+///     },
+/// });
+/// let issues = invalid_property_names(&schema, PROPERTY_NAME_PATTERN)?;
+/// assert_eq!(issues.len(), 1);
+/// assert_eq!(issues[0].pointer, "/properties/app.kubernetes.io~1name");
+/// # Ok::<(), kube_core::schema::SchemaRewriteError>(())
+/// ```
+pub fn invalid_property_names(schema: &Value, pattern: &str) -> Result<Vec<SchemaIssue>, SchemaRewriteError> {
+    let pattern = regex::Regex::new(pattern).map_err(|error| SchemaRewriteError::InvalidPattern {
+        pattern: pattern.to_string(),
+        reason: error.to_string(),
+    })?;
+    let mut issues = Vec::new();
+    for_each_subschema(schema, "", &mut |pointer, subschema| {
+        let Some(Value::Object(properties)) = subschema.get("properties") else {
+            return;
+        };
+        for name in properties.keys().filter(|name| !pattern.is_match(name)) {
+            issues.push(SchemaIssue {
+                pointer: format!("{pointer}/properties/{}", escape_pointer(name)),
+                kind: SchemaIssueKind::InvalidPropertyName { name: name.clone() },
+            });
+        }
+    });
+    Ok(issues)
+}
+
+/// List collections and strings without a maximum size that are covered by CEL validation rules
+///
+/// The apiserver estimates the cost of every `x-kubernetes-validations` rule based on the worst-case size of
+/// the data it applies to, and rejects CRDs whose rules are too expensive. Arrays without `maxItems`, maps
+/// without `maxProperties` and strings without `maxLength` (or an `enum`) are assumed to be very large, so
+/// bounding them is the main way to bring the estimated cost down.
+///
+/// Returns the JSON pointers of all such unbounded nodes that are located at or below a node that has
+/// `x-kubernetes-validations`.
+///
+/// ```
+/// use kube_core::schema::missing_cost_bounds;
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "x-kubernetes-validations": [{ "rule": "self.items.all(i, i != '')" }],
+///     "properties": {
+///         "items": { "type": "array", "items": { "type": "string", "maxLength": 63 } },
+///     },
+/// });
+/// assert_eq!(missing_cost_bounds(&schema), ["/properties/items"]);
+/// ```
+pub fn missing_cost_bounds(schema: &Value) -> Vec<String> {
+    let mut validated = Vec::<String>::new();
+    let mut unbounded = Vec::new();
+    for_each_subschema(schema, "", &mut |pointer, subschema| {
+        if subschema.get("x-kubernetes-validations").is_some() {
+            validated.push(pointer.to_string());
+        }
+        let is_validated = validated
+            .iter()
+            .any(|root| pointer == root || pointer.starts_with(&format!("{root}/")));
+        if is_validated && is_unbounded(subschema) {
+            unbounded.push(pointer.to_string());
+        }
+    });
+    unbounded
+}
+
+/// List maps whose keys don't have a maximum length
+///
+/// The cost of CEL validation rules that iterate over a map grows with the worst-case length of its keys.
+/// `propertyNames` can't bound them in a CRD, since `JSONSchemaProps` doesn't model it (so it is dropped from
+/// CRDs generated by `#[derive(CustomResource)]`), so maps only count as bounded if they have the validation
+/// rule added by [`map_key_max_length`](super::StructuralSchemaRewriterBuilder::map_key_max_length). Unlike
+/// [`missing_cost_bounds`], this reports all maps (objects with an `additionalProperties` schema), whether
+/// they are validated or not.
+///
+/// ```
+/// use kube_core::schema::unbounded_map_keys;
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "properties": {
+///         "labels": { "type": "object", "additionalProperties": { "type": "string" } },
+///         "ports": {
+///             "type": "object",
+///             "additionalProperties": { "type": "integer" },
+///             "propertyNames": { "maxLength": 15 },
+///         },
+///         "annotations": {
+///             "type": "object",
+///             "additionalProperties": { "type": "string" },
+///             "x-kubernetes-validations": [{ "rule": "self.all(k, size(k) <= 253)" }],
+///         },
+///     },
+/// });
+/// assert_eq!(unbounded_map_keys(&schema), ["/properties/labels", "/properties/ports"]);
+/// ```
+pub fn unbounded_map_keys(schema: &Value) -> Vec<String> {
+    let mut unbounded = Vec::new();
+    for_each_subschema(schema, "", &mut |pointer, subschema| {
+        if !matches!(subschema.get("additionalProperties"), Some(Value::Object(_))) {
+            return;
+        }
+        let is_bounded = matches!(
+            subschema.get("x-kubernetes-validations"),
+            Some(Value::Array(rules)) if rules.iter().any(|rule| is_map_key_rule(&rule["rule"]))
+        );
+        if !is_bounded {
+            unbounded.push(pointer.to_string());
+        }
+    });
+    unbounded
+}
+
+/// Metrics of a schema that drive the estimated cost of CEL validation rules, see [`complexity_report`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ComplexityReport {
+    /// The deepest nesting of properties, array items and map values (the root is at depth 0)
+    pub max_depth: usize,
+    /// The number of properties, summed over all objects
+    pub property_count: usize,
+    /// The number of arrays without `maxItems` and maps without `maxProperties`
+    pub unbounded_collections: usize,
+}
+
+/// Summarize the shape of a schema, to predict whether CEL validation rules will fit into the cost budget
+///
+/// The apiserver estimates the cost of rules from the worst-case size of the data they apply to, which
+/// grows with the nesting and the number of unbounded collections. This complements
+/// [`missing_cost_bounds`], which lists the individual collections that should be bounded.
+///
+/// ```
+/// use kube_core::schema::complexity_report;
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "properties": {
+///         "hosts": { "type": "array", "items": { "type": "string" } },
+///     },
+/// });
+/// let report = complexity_report(&schema);
+/// assert_eq!(report.max_depth, 2);
+/// assert_eq!(report.property_count, 1);
+/// assert_eq!(report.unbounded_collections, 1);
+/// ```
+pub fn complexity_report(schema: &Value) -> ComplexityReport {
+    let mut report = ComplexityReport {
+        max_depth: depth(schema),
+        property_count: field_count(schema),
+        ..ComplexityReport::default()
+    };
+    for_each_subschema(schema, "", &mut |_, subschema| {
+        let is_collection = matches!(
+            subschema.get("type").and_then(Value::as_str),
+            Some("array" | "object")
+        );
+        if is_collection && is_unbounded(subschema) {
+            report.unbounded_collections += 1;
+        }
+    });
+    report
+}
+
+/// The nesting depth of the data described by `schema`, where subschemas of junctors such as `oneOf`
+/// describe the same level
+fn depth(schema: &Value) -> usize {
+    subschemas(schema)
+        .into_iter()
+        .filter(|(path, _)| !path.starts_with("/$defs/") && !path.starts_with("/definitions/"))
+        .map(|(path, subschema)| {
+            let nested = [
+                "/properties/",
+                "/patternProperties/",
+                "/items",
+                "/additionalProperties",
+            ]
+            .iter()
+            .any(|prefix| path.starts_with(prefix));
+            depth(subschema) + usize::from(nested)
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// List the properties (and the root) of a schema that don't have a `description`
+///
+/// Returns the JSON pointers of all undocumented nodes, which can be used to enforce documentation
+/// coverage of a CRD, for example by failing CI when the list isn't empty.
+///
+/// ```
+/// use kube_core::schema::undocumented_properties;
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "description": "A Foo",
+///     "properties": {
+///         "replicas": { "type": "integer", "description": "Number of replicas" },
+///         "image": { "type": "string" },
+///     },
+/// });
+/// assert_eq!(undocumented_properties(&schema), ["/properties/image"]);
+/// ```
+pub fn undocumented_properties(schema: &Value) -> Vec<String> {
+    let mut undocumented = Vec::new();
+    for_each_subschema(schema, "", &mut |pointer, subschema| {
+        let Ok(node) = serde_json::from_value::<Schema>(subschema.clone()) else {
+            return;
+        };
+        if pointer.is_empty() && !has_description(&node) {
+            undocumented.push(String::new());
+        }
+        let Schema::Object(SchemaObject {
+            object: Some(object), ..
+        }) = node
+        else {
+            return;
+        };
+        for (name, property) in object.properties.iter().flatten() {
+            if !has_description(property) {
+                undocumented.push(format!("{pointer}/properties/{}", escape_pointer(name)));
+            }
+        }
+    });
+    undocumented
+}
+
+fn has_description(schema: &Schema) -> bool {
+    matches!(schema, Schema::Object(SchemaObject { metadata: Some(metadata), .. }) if metadata.description.is_some())
+}
+
+/// The maximum size of the `kubectl.kubernetes.io/last-applied-configuration` annotation
+///
+/// Applying a CRD with client-side `kubectl apply` fails if the CRD doesn't fit into this annotation.
+pub const LAST_APPLIED_ANNOTATION_LIMIT: usize = 262_144;
+
+/// Check that a schema doesn't exceed `limit` bytes when serialized
+///
+/// CRDs that are too large can't be applied (see [`LAST_APPLIED_ANNOTATION_LIMIT`]), which is otherwise
+/// only noticed when installing them. Checking the size when generating the CRD catches this early.
+/// Descriptions usually make up most of a schema, so
+/// [`strip_descriptions`](super::StructuralSchemaRewriterBuilder::strip_descriptions) is the usual remedy.
+///
+/// ```
+/// use kube_core::schema::{check_schema_size, SchemaRewriteError};
+///
+/// let schema = serde_json::json!({ "type": "string", "description": "A very long description" });
+/// assert!(check_schema_size(&schema, 1024).is_ok());
+/// assert!(matches!(
+///     check_schema_size(&schema, 16),
+///     Err(SchemaRewriteError::CrdTooLarge { limit: 16, .. })
+/// ));
+/// ```
+pub fn check_schema_size(schema: &Value, limit: usize) -> Result<(), SchemaRewriteError> {
+    let size = serde_json::to_vec(schema).map_or(0, |serialized| serialized.len());
+    if size > limit {
+        return Err(SchemaRewriteError::CrdTooLarge { size, limit });
+    }
+    Ok(())
+}
+
+/// Count the fields of a schema, which are the `properties` of all of its (nested) objects
+///
+/// Every field adds to the cost of validating, pruning and defaulting objects in the apiserver, so very
+/// large types can make a CRD impractical to serve even if it fits within the size limits.
+///
+/// ```
+/// use kube_core::schema::field_count;
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "properties": {
+///         "replicas": { "type": "integer" },
+///         "ports": {
+///             "type": "array",
+///             "items": { "type": "object", "properties": { "port": { "type": "integer" } } },
+///         },
+///     },
+/// });
+/// assert_eq!(field_count(&schema), 3);
+/// ```
+pub fn field_count(schema: &Value) -> usize {
+    let mut count = 0;
+    for_each_subschema(schema, "", &mut |_, subschema| {
+        if let Some(Value::Object(properties)) = subschema.get("properties") {
+            count += properties.len();
+        }
+    });
+    count
+}
+
+/// Check that a schema doesn't have more than `limit` fields, as counted by [`field_count`]
+///
+/// This reports oversized types when generating the CRD, rather than when it is installed or served.
+/// The usual remedies are splitting the type or replacing rarely validated parts with
+/// `x-kubernetes-preserve-unknown-fields` (see [`preserve_unknown_fields_at`](super::preserve_unknown_fields_at)).
+///
+/// ```
+/// use kube_core::schema::{check_field_count, SchemaRewriteError};
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "properties": { "a": { "type": "string" }, "b": { "type": "string" } },
+/// });
+/// assert!(check_field_count(&schema, 2).is_ok());
+/// assert!(matches!(
+///     check_field_count(&schema, 1),
+///     Err(SchemaRewriteError::TooManyFields { count: 2, limit: 1 })
+/// ));
+/// ```
+pub fn check_field_count(schema: &Value, limit: usize) -> Result<(), SchemaRewriteError> {
+    let count = field_count(schema);
+    if count > limit {
+        return Err(SchemaRewriteError::TooManyFields { count, limit });
+    }
+    Ok(())
+}
+
+fn is_unbounded(schema: &Value) -> bool {
+    let has = |keyword: &str| schema.get(keyword).is_some();
+    match schema.get("type").and_then(Value::as_str) {
+        Some("array") => !has("maxItems"),
+        Some("string") => !has("maxLength") && !has("enum"),
+        Some("object") => {
+            let is_map = matches!(schema.get("additionalProperties"), Some(Value::Object(_)));
+            is_map && !has("maxProperties")
+        }
+        _ => false,
+    }
+}
+
+fn check_node(node: &SchemaObject) -> Vec<SchemaIssueKind> {
+    let mut issues = Vec::new();
+    let has_properties = node.object.as_ref().is_some_and(|o| o.has_properties());
+    if node.enum_values.is_some() && has_properties {
+        issues.push(SchemaIssueKind::EnumWithProperties);
+    }
+    let has_null_type =
+        matches!(&node.instance_type, Some(SingleOrVec::Vec(types)) if types.contains(&InstanceType::Null));
+    if has_null_type && node.extensions.contains_key("nullable") {
+        issues.push(SchemaIssueKind::ConflictingNullability);
+    }
+    // Value validations (such as the `oneOf` branches of hoisted enums) have no type, and can require
+    // properties that are defined by the node they belong to
+    if let (Some(_), Some(object)) = (&node.instance_type, &node.object) {
+        for name in object.required.iter().flatten() {
+            if !object.properties.as_ref().is_some_and(|p| p.contains_key(name)) {
+                issues.push(SchemaIssueKind::RequiredWithoutProperty { name: name.clone() });
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_field_count, check_structural, complexity_report, field_count, invalid_defaults,
+        invalid_list_map_keys, missing_cost_bounds, unbounded_map_keys, undocumented_properties,
+        ComplexityReport, SchemaIssue, SchemaIssueKind,
+    };
+    #[cfg(feature = "schema-regex")]
+    use super::{invalid_patterns, invalid_property_names, SchemaRewriteError, PROPERTY_NAME_PATTERN};
+    use crate::schema::{
+        tests::{schema_for, schema_with_rewriter_for},
+        to_json_schema_props, StructuralSchemaRewriter,
+    };
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn reports_enum_with_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "valid": { "type": "string", "enum": ["a", "b"] },
+                "invalid": {
+                    "type": "object",
+                    "enum": [{ "a": "x" }],
+                    "properties": { "a": { "type": "string" } },
+                },
+            },
+        });
+        assert_eq!(check_structural(&schema), [SchemaIssue {
+            pointer: "/properties/invalid".into(),
+            kind: SchemaIssueKind::EnumWithProperties,
+        }]);
+    }
+
+    #[test]
+    fn reports_conflicting_nullability() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": ["string", "null"], "nullable": true },
+                "image": { "type": "string", "nullable": true },
+            },
+        });
+        assert_eq!(check_structural(&schema), [SchemaIssue {
+            pointer: "/properties/name".into(),
+            kind: SchemaIssueKind::ConflictingNullability,
+        }]);
+    }
+
+    #[test]
+    fn reports_required_without_property() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "a": { "type": "string" },
+            },
+            "required": ["a", "b"],
+            "oneOf": [{ "required": ["a"] }],
+        });
+        let issues = check_structural(&schema);
+        assert_eq!(issues, [SchemaIssue {
+            pointer: "".into(),
+            kind: SchemaIssueKind::RequiredWithoutProperty { name: "b".into() },
+        }]);
+        assert_eq!(
+            issues[0].to_string(),
+            "/: `required` lists `b`, which is missing from `properties`"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "schema-regex")]
+    fn accepts_valid_patterns() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string", "pattern": "^[a-z0-9]([-a-z0-9]*[a-z0-9])?$" },
+                "tags": {
+                    "type": "array",
+                    "items": { "type": "string", "pattern": r"^\p{L}+(\.\w+)*$" },
+                },
+            },
+        });
+        assert_eq!(invalid_patterns(&schema), []);
+    }
+
+    #[test]
+    #[cfg(feature = "schema-regex")]
+    fn reports_backreference_pattern() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "repeated": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string", "pattern": r"^(a+)\1$" },
+                },
+            },
+        });
+        let issues = invalid_patterns(&schema);
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].pointer, "/properties/repeated/additionalProperties");
+        let SchemaIssueKind::InvalidPattern { pattern, .. } = &issues[0].kind else {
+            panic!("expected an invalid pattern, got {:?}", issues[0].kind);
+        };
+        assert_eq!(pattern, r"^(a+)\1$");
+        assert!(issues[0].to_string().starts_with(
+            r"/properties/repeated/additionalProperties: `^(a+)\1$` is not a valid RE2 pattern"
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "schema-regex")]
+    fn reports_invalid_property_names() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            replicas: u32,
+            #[serde(rename = "app.kubernetes.io/name")]
+            app_name: String,
+            #[serde(rename = "max-surge%")]
+            max_surge: Option<u32>,
+        }
+
+        let schema = schema_for::<Spec>();
+        let issues = invalid_property_names(&schema, PROPERTY_NAME_PATTERN).unwrap();
+        assert_eq!(issues, [
+            SchemaIssue {
+                pointer: "/properties/app.kubernetes.io~1name".into(),
+                kind: SchemaIssueKind::InvalidPropertyName {
+                    name: "app.kubernetes.io/name".into()
+                },
+            },
+            SchemaIssue {
+                pointer: "/properties/max-surge%".into(),
+                kind: SchemaIssueKind::InvalidPropertyName {
+                    name: "max-surge%".into()
+                },
+            },
+        ]);
+        assert_eq!(
+            issues[1].to_string(),
+            "/properties/max-surge%: `max-surge%` is not a valid property name"
+        );
+
+        // The pattern is configurable
+        assert_eq!(invalid_property_names(&schema, ".*").unwrap(), []);
+        assert!(matches!(
+            invalid_property_names(&schema, "("),
+            Err(SchemaRewriteError::InvalidPattern { pattern, .. }) if pattern == "("
+        ));
+    }
+
+    #[test]
+    fn checks_defaults_against_their_schema() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "replicas": { "type": "integer", "minimum": 1, "default": 3 },
+                "protocol": { "type": "string", "enum": ["TCP", "UDP"], "default": "TCP" },
+                "port": { "x-kubernetes-int-or-string": true, "default": "http" },
+                "resources": {
+                    "type": "object",
+                    "properties": {
+                        "cpu": { "type": "string" },
+                        "memory": { "type": "string" },
+                    },
+                    "required": ["cpu"],
+                    "default": { "cpu": "100m" },
+                },
+                "hosts": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "default": [],
+                },
+                "tls": { "type": "object", "nullable": true, "default": null },
+            },
+        });
+        assert_eq!(invalid_defaults(&schema), []);
+
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "replicas": { "type": "integer", "default": "3" },
+                "timeout": { "type": "integer", "minimum": 1, "default": 0 },
+                "protocol": { "type": "string", "enum": ["TCP", "UDP"], "default": "SCTP" },
+                "ports": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": { "port": { "type": "integer" } },
+                    },
+                    "default": [{ "port": 80 }, { "port": "https" }],
+                },
+                "tls": { "type": "object", "default": null },
+            },
+        });
+        // Sorted, since the order of properties depends on the `preserve_order` feature of `serde_json`
+        let mut issues = invalid_defaults(&schema)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        issues.sort();
+        assert_eq!(issues, [
+            "/properties/ports: `default` doesn't match the schema: \"https\" at `[1].port` isn't of type integer",
+            "/properties/protocol: `default` doesn't match the schema: \"SCTP\" isn't one of the `enum` values",
+            "/properties/replicas: `default` doesn't match the schema: \"3\" isn't of type integer",
+            "/properties/timeout: `default` doesn't match the schema: 0 is below the `minimum` of 1",
+            "/properties/tls: `default` doesn't match the schema: null isn't allowed, since the field isn't nullable",
+        ]);
+    }
+
+    #[test]
+    fn checks_list_map_keys() {
+        let list_map = |keys: Value| {
+            json!({
+                "type": "object",
+                "properties": {
+                    "ports": {
+                        "type": "array",
+                        "x-kubernetes-list-type": "map",
+                        "x-kubernetes-list-map-keys": keys,
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "port": { "type": "integer" },
+                                "protocol": { "type": "string", "default": "TCP" },
+                                "name": { "type": "string" },
+                                "labels": { "type": "object", "additionalProperties": { "type": "string" } },
+                            },
+                            "required": ["port", "labels"],
+                        },
+                    },
+                },
+            })
+        };
+        assert_eq!(invalid_list_map_keys(&list_map(json!(["port", "protocol"]))), []);
+
+        let issues = invalid_list_map_keys(&list_map(json!(["port", "portName", "labels", "name"])));
+        assert_eq!(issues.iter().map(ToString::to_string).collect::<Vec<_>>(), [
+            "/properties/ports: list map key `portName` is not a property of the items",
+            "/properties/ports: list map key `labels` is not a scalar property",
+            "/properties/ports: list map key `name` is neither required nor has a default",
+        ]);
+        assert_eq!(issues[0].kind, SchemaIssueKind::InvalidListMapKey {
+            key: Some("portName".into()),
+            reason: "is not a property of the items".into(),
+        });
+
+        let issues = invalid_list_map_keys(&list_map(json!([])));
+        assert_eq!(issues.iter().map(ToString::to_string).collect::<Vec<_>>(), [
+            "/properties/ports: list map has no `x-kubernetes-list-map-keys`"
+        ]);
+    }
+
+    #[test]
+    fn reports_and_bounds_unbounded_map_keys() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            labels: BTreeMap<String, String>,
+            nested: Vec<BTreeMap<String, i32>>,
+            #[schemars(extend("propertyNames" = { "type": "string", "maxLength": 63 }))]
+            bounded: BTreeMap<String, String>,
+        }
+
+        let schema = schema_for::<Spec>();
+        let mut unbounded = unbounded_map_keys(&schema);
+        unbounded.sort();
+        assert_eq!(unbounded, [
+            "/properties/bounded",
+            "/properties/labels",
+            "/properties/nested/items",
+        ]);
+
+        let bounded = schema_with_rewriter_for::<Spec>(
+            StructuralSchemaRewriter::builder()
+                .map_key_max_length(Some(253))
+                .build(),
+        );
+        assert_eq!(unbounded_map_keys(&bounded), Vec::<String>::new());
+        assert_eq!(
+            bounded["properties"]["labels"]["x-kubernetes-validations"][0]["rule"],
+            "self.all(k, size(k) <= 253)"
+        );
+        assert_eq!(
+            bounded["properties"]["bounded"]["x-kubernetes-validations"][0]["rule"],
+            "self.all(k, size(k) <= 63)"
+        );
+
+        // The bounds survive the conversion into a CRD, unlike `propertyNames`
+        let props = to_json_schema_props(&bounded).unwrap();
+        let property = |name: &str| props.properties.as_ref().unwrap()[name].clone();
+        let rules = property("bounded").x_kubernetes_validations.unwrap();
+        assert_eq!(rules[0].rule, "self.all(k, size(k) <= 63)");
+        let rules = property("labels").x_kubernetes_validations.unwrap();
+        assert_eq!(rules[0].rule, "self.all(k, size(k) <= 253)");
+        assert_eq!(
+            rules[0].message.as_deref(),
+            Some("keys must be at most 253 characters long")
+        );
+    }
+
+    #[test]
+    fn reports_complexity() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "labels": { "type": "object", "additionalProperties": { "type": "string" } },
+                "ports": {
+                    "type": "array",
+                    "maxItems": 16,
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "port": { "type": "integer" },
+                            "hosts": { "type": "array", "items": { "type": "string" } },
+                        },
+                    },
+                },
+                "backend": {
+                    "type": "object",
+                    "properties": {
+                        "service": { "type": "string" },
+                        "url": { "type": "string" },
+                    },
+                    "oneOf": [{ "required": ["service"] }, { "required": ["url"] }],
+                },
+            },
+        });
+        assert_eq!(complexity_report(&schema), ComplexityReport {
+            // .ports[*].hosts[*]
+            max_depth: 4,
+            // name, labels, ports, backend, port, hosts, service, url
+            property_count: 8,
+            // labels and hosts
+            unbounded_collections: 2,
+        });
+        assert_eq!(
+            complexity_report(&json!({ "type": "string" })),
+            ComplexityReport::default()
+        );
+    }
+
+    #[test]
+    fn reports_schemas_with_too_many_fields() {
+        let entry = json!({
+            "type": "object",
+            "properties": (0..10)
+                .map(|i| (format!("field{i}"), json!({ "type": "string" })))
+                .collect::<serde_json::Map<_, _>>(),
+        });
+        let schema = json!({
+            "type": "object",
+            "properties": (0..50)
+                .map(|i| (format!("entry{i}"), entry.clone()))
+                .collect::<serde_json::Map<_, _>>(),
+        });
+        // 50 entries with 10 fields each
+        assert_eq!(field_count(&schema), 550);
+        assert!(check_field_count(&schema, 550).is_ok());
+        assert_eq!(
+            check_field_count(&schema, 500).unwrap_err().to_string(),
+            "schema has 550 fields, which exceeds the limit of 500 fields"
+        );
+        assert_eq!(field_count(&json!({ "type": "string" })), 0);
+    }
+
+    #[test]
+    fn lists_undocumented_properties() {
+        let schema = json!({
+            "type": "object",
+            "description": "Spec of a Foo",
+            "properties": {
+                "name": { "type": "string", "description": "Name of the foo" },
+                "image": { "type": "string" },
+                "ports": {
+                    "type": "array",
+                    "description": "Exposed ports",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "port": { "type": "integer", "description": "Port number" },
+                            "protocol": { "type": "string" },
+                        },
+                    },
+                },
+            },
+        });
+        assert_eq!(undocumented_properties(&schema), [
+            "/properties/image",
+            "/properties/ports/items/properties/protocol",
+        ]);
+        assert_eq!(undocumented_properties(&json!({ "type": "object" })), [""]);
+    }
+
+    #[test]
+    fn reports_unbounded_collections_under_validation_rules() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "unvalidated": { "type": "array", "items": { "type": "string" } },
+                "spec": {
+                    "type": "object",
+                    "x-kubernetes-validations": [{ "rule": "self.hosts.size() == self.ports.size()" }],
+                    "properties": {
+                        "hosts": { "type": "array", "items": { "type": "string", "maxLength": 253 } },
+                        "ports": {
+                            "type": "array",
+                            "maxItems": 16,
+                            "items": { "type": "integer" },
+                        },
+                        "labels": {
+                            "type": "object",
+                            "additionalProperties": { "type": "string", "enum": ["a", "b"] },
+                        },
+                    },
+                },
+            },
+        });
+        assert_eq!(missing_cost_bounds(&schema), [
+            "/properties/spec/properties/hosts",
+            "/properties/spec/properties/labels",
+        ]);
+    }
+}
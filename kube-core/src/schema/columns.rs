@@ -0,0 +1,327 @@
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceColumnDefinition;
+use serde_json::Value;
+use std::fmt;
+
+/// A misconfigured printer column found by [`check_printer_columns`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrinterColumnIssue {
+    /// Name of the offending column
+    pub column: String,
+    /// What is wrong with the column
+    pub kind: PrinterColumnIssueKind,
+}
+
+/// The kinds of problems reported as a [`PrinterColumnIssue`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PrinterColumnIssueKind {
+    /// The JSONPath doesn't resolve to a property of the schema
+    PathNotFound {
+        /// The JSONPath of the column
+        json_path: String,
+    },
+    /// The property that the JSONPath resolves to has a type that can't be displayed as the column's type
+    TypeMismatch {
+        /// The type of the column
+        expected: String,
+        /// The type of the property
+        found: String,
+    },
+}
+
+impl fmt::Display for PrinterColumnIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let column = &self.column;
+        match &self.kind {
+            PrinterColumnIssueKind::PathNotFound { json_path } => {
+                write!(
+                    f,
+                    "column {column}: `{json_path}` is not a property of the schema"
+                )
+            }
+            PrinterColumnIssueKind::TypeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "column {column}: expected a property of type {expected}, found {found}"
+                )
+            }
+        }
+    }
+}
+
+/// Check that the `additionalPrinterColumns` of a CRD refer to properties of its schema
+///
+/// Every column's `jsonPath` must resolve to a property of `schema` whose type is compatible with the
+/// column's `type`. Array indices and filters (such as `.status.conditions[?(@.type=="Ready")].status`)
+/// select the items of arrays, and properties below `metadata` or `x-kubernetes-preserve-unknown-fields`
+/// are not checked.
+///
+/// ```
+/// use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceColumnDefinition;
+/// use kube_core::schema::{check_printer_columns, PrinterColumnIssueKind};
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "properties": {
+///         "spec": {
+///             "type": "object",
+///             "properties": { "replicas": { "type": "integer" } },
+///         },
+///     },
+/// });
+/// let column = |name: &str, json_path: &str| CustomResourceColumnDefinition {
+///     name: name.into(),
+///     json_path: json_path.into(),
+///     type_: "integer".into(),
+///     ..Default::default()
+/// };
+/// let issues = check_printer_columns(&schema, &[column("Replicas", ".spec.replicas"), column("Ready", ".status.ready")]);
+/// assert_eq!(issues.len(), 1);
+/// assert_eq!(issues[0].column, "Ready");
+/// assert!(matches!(issues[0].kind, PrinterColumnIssueKind::PathNotFound { .. }));
+/// ```
+pub fn check_printer_columns(
+    schema: &Value,
+    columns: &[CustomResourceColumnDefinition],
+) -> Vec<PrinterColumnIssue> {
+    let mut issues = Vec::new();
+    for column in columns {
+        let kind = match resolve(schema, &column.json_path) {
+            Resolved::NotFound => Some(PrinterColumnIssueKind::PathNotFound {
+                json_path: column.json_path.clone(),
+            }),
+            Resolved::Unchecked => None,
+            Resolved::Property(property) => {
+                let found = property_type(property);
+                (!is_compatible(&column.type_, &found)).then(|| PrinterColumnIssueKind::TypeMismatch {
+                    expected: column.type_.clone(),
+                    found,
+                })
+            }
+        };
+        if let Some(kind) = kind {
+            issues.push(PrinterColumnIssue {
+                column: column.name.clone(),
+                kind,
+            });
+        }
+    }
+    issues
+}
+
+enum Resolved<'a> {
+    NotFound,
+    /// The path points into a part of the object that the schema doesn't describe
+    Unchecked,
+    Property(&'a Value),
+}
+
+fn resolve<'a>(schema: &'a Value, json_path: &str) -> Resolved<'a> {
+    let json_path = json_path.trim_start_matches('$');
+    let mut node = schema;
+    for (depth, segment) in segments(json_path).into_iter().enumerate() {
+        // Split `name[0][*]` into the property name and the number of index expressions
+        let (name, indices) = match segment.find('[') {
+            Some(start) => (&segment[..start], index_expressions(&segment[start..])),
+            None => (segment, 0),
+        };
+        if is_preserved(node) {
+            return Resolved::Unchecked;
+        }
+        if !name.is_empty() {
+            let property = node.get("properties").and_then(|properties| properties.get(name));
+            node = match property {
+                Some(property) => property,
+                // The apiserver fills in the metadata of all objects, even when the schema doesn't describe it
+                None if depth == 0 && ["metadata", "apiVersion", "kind"].contains(&name) => {
+                    return Resolved::Unchecked
+                }
+                None => match node.get("additionalProperties") {
+                    Some(values @ Value::Object(_)) => values,
+                    _ => return Resolved::NotFound,
+                },
+            };
+        }
+        for _ in 0..indices {
+            if is_preserved(node) {
+                return Resolved::Unchecked;
+            }
+            node = match node.get("items") {
+                Some(items @ Value::Object(_)) => items,
+                _ => return Resolved::NotFound,
+            };
+        }
+    }
+    Resolved::Property(node)
+}
+
+/// Split a JSONPath at the dots that aren't part of an index expression (such as a filter)
+fn segments(json_path: &str) -> Vec<&str> {
+    let mut segments = Vec::new();
+    let (mut start, mut nesting) = (0, 0);
+    for (i, c) in json_path.char_indices() {
+        match c {
+            '[' => nesting += 1,
+            ']' => nesting -= 1,
+            '.' if nesting == 0 => {
+                segments.push(&json_path[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    segments.push(&json_path[start..]);
+    segments.retain(|segment| !segment.is_empty());
+    segments
+}
+
+/// Count the (possibly nested) index expressions, such as `[0]` or `[?(@.a[0]=="b")]`
+fn index_expressions(indices: &str) -> usize {
+    let mut nesting = 0;
+    let mut count = 0;
+    for c in indices.chars() {
+        match c {
+            '[' => nesting += 1,
+            ']' => {
+                nesting -= 1;
+                if nesting == 0 {
+                    count += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+    count
+}
+
+fn is_preserved(schema: &Value) -> bool {
+    schema.get("x-kubernetes-preserve-unknown-fields") == Some(&Value::Bool(true))
+}
+
+fn property_type(property: &Value) -> String {
+    if property.get("x-kubernetes-int-or-string") == Some(&Value::Bool(true)) {
+        return "int-or-string".into();
+    }
+    match property.get("type").and_then(Value::as_str) {
+        Some(tpe) => tpe.into(),
+        None if is_preserved(property) => "any".into(),
+        None => "unknown".into(),
+    }
+}
+
+fn is_compatible(column_type: &str, property_type: &str) -> bool {
+    matches!(
+        (column_type, property_type),
+        (_, "any")
+            | ("integer", "integer" | "int-or-string")
+            | ("number", "number" | "integer")
+            | (
+                "string",
+                "string" | "integer" | "number" | "boolean" | "int-or-string"
+            )
+            | ("boolean", "boolean")
+            | ("date", "string")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_printer_columns, PrinterColumnIssue, PrinterColumnIssueKind};
+    use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceColumnDefinition;
+    use serde_json::{json, Value};
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "type": "object",
+                    "properties": {
+                        "replicas": { "type": "integer" },
+                        "image": { "type": "string" },
+                        "labels": { "type": "object", "additionalProperties": { "type": "string" } },
+                        "extra": { "type": "object", "x-kubernetes-preserve-unknown-fields": true },
+                    },
+                },
+                "status": {
+                    "type": "object",
+                    "properties": {
+                        "conditions": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "type": { "type": "string" },
+                                    "status": { "type": "string" },
+                                    "lastTransitionTime": { "type": "string", "format": "date-time" },
+                                },
+                            },
+                        },
+                    },
+                },
+            },
+        })
+    }
+
+    fn column(name: &str, json_path: &str, type_: &str) -> CustomResourceColumnDefinition {
+        CustomResourceColumnDefinition {
+            name: name.into(),
+            json_path: json_path.into(),
+            type_: type_.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn accepts_valid_columns() {
+        let columns = [
+            column("Replicas", ".spec.replicas", "integer"),
+            column("Image", ".spec.image", "string"),
+            column("App", ".spec.labels.app", "string"),
+            column("Extra", ".spec.extra.anything[0].goes", "integer"),
+            column(
+                "Ready",
+                r#".status.conditions[?(@.type=="Ready")].status"#,
+                "string",
+            ),
+            column("Since", ".status.conditions[0].lastTransitionTime", "date"),
+            column(
+                "Nested",
+                r#".status.conditions[?(@.type[0]=="R")].type"#,
+                "string",
+            ),
+            column("Age", ".metadata.creationTimestamp", "date"),
+        ];
+        assert_eq!(check_printer_columns(&schema(), &columns), []);
+    }
+
+    #[test]
+    fn reports_invalid_columns() {
+        let columns = [
+            column("Replicas", ".spec.replicaCount", "integer"),
+            column("Image", ".spec.image", "integer"),
+            column("Ready", ".status.conditions.reason", "string"),
+        ];
+        assert_eq!(check_printer_columns(&schema(), &columns), [
+            PrinterColumnIssue {
+                column: "Replicas".into(),
+                kind: PrinterColumnIssueKind::PathNotFound {
+                    json_path: ".spec.replicaCount".into()
+                },
+            },
+            PrinterColumnIssue {
+                column: "Image".into(),
+                kind: PrinterColumnIssueKind::TypeMismatch {
+                    expected: "integer".into(),
+                    found: "string".into(),
+                },
+            },
+            PrinterColumnIssue {
+                column: "Ready".into(),
+                kind: PrinterColumnIssueKind::PathNotFound {
+                    json_path: ".status.conditions.reason".into()
+                },
+            },
+        ]);
+    }
+}
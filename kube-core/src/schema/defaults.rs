@@ -0,0 +1,208 @@
+use super::{check::defaulted_required, escape_pointer, subschemas_mut};
+use serde_json::{Map, Value};
+
+/// Build an instance of a schema from the `default`s in it, such as for generating example manifests
+///
+/// Every property with a `default` is set to it (as-is, including arrays and maps), and objects without a
+/// `default` are built from the defaults of their own properties. Properties without any defaults are
+/// omitted.
+///
+/// ```
+/// use kube_core::schema::instantiate_defaults;
+/// use serde_json::json;
+///
+/// let schema = json!({
+///     "type": "object",
+///     "properties": {
+///         "replicas": { "type": "integer", "default": 1 },
+///         "image": { "type": "string" },
+///     },
+/// });
+/// assert_eq!(instantiate_defaults(&schema), json!({ "replicas": 1 }));
+/// ```
+pub fn instantiate_defaults(schema: &Value) -> Value {
+    defaults_of(schema).unwrap_or_else(|| Value::Object(Map::new()))
+}
+
+/// Remove properties that have a `default` from the `required` properties of their objects
+///
+/// The apiserver applies defaults before validating an object, so such properties are never missing anyway
+/// (see [`required_with_defaults`](super::required_with_defaults)). Returns the JSON pointers of the
+/// properties that were made optional.
+///
+/// ```
+/// use kube_core::schema::make_defaulted_optional;
+/// use serde_json::json;
+///
+/// let mut schema = json!({
+///     "type": "object",
+///     "properties": {
+///         "replicas": { "type": "integer", "default": 1 },
+///         "image": { "type": "string" },
+///     },
+///     "required": ["replicas", "image"],
+/// });
+/// assert_eq!(make_defaulted_optional(&mut schema), ["/properties/replicas"]);
+/// assert_eq!(schema["required"], json!(["image"]));
+/// ```
+pub fn make_defaulted_optional(schema: &mut Value) -> Vec<String> {
+    let mut optional = Vec::new();
+    make_defaulted_optional_at(schema, "", &mut optional);
+    optional
+}
+
+fn make_defaulted_optional_at(schema: &mut Value, pointer: &str, optional: &mut Vec<String>) {
+    let defaulted = defaulted_required(schema)
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+    if let (false, Some(object)) = (defaulted.is_empty(), schema.as_object_mut()) {
+        if let Some(Value::Array(required)) = object.get_mut("required") {
+            required.retain(|name| !defaulted.iter().any(|defaulted| name == defaulted));
+            if required.is_empty() {
+                object.remove("required");
+            }
+        }
+        for name in defaulted {
+            optional.push(format!("{pointer}/properties/{}", escape_pointer(&name)));
+        }
+    }
+    for (path, subschema) in subschemas_mut(schema) {
+        make_defaulted_optional_at(subschema, &format!("{pointer}{path}"), optional);
+    }
+}
+
+fn defaults_of(schema: &Value) -> Option<Value> {
+    if let Some(default) = schema.get("default") {
+        return Some(default.clone());
+    }
+    let properties = schema.get("properties")?.as_object()?;
+    let instance = properties
+        .iter()
+        .filter_map(|(name, property)| Some((name.clone(), defaults_of(property)?)))
+        .collect::<Map<_, _>>();
+    (!instance.is_empty()).then_some(Value::Object(instance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{instantiate_defaults, make_defaulted_optional};
+    use crate::schema::{required_with_defaults, SchemaIssue, SchemaIssueKind};
+    use serde_json::json;
+
+    #[test]
+    fn instantiates_nested_defaults() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "type": "object",
+                    "properties": {
+                        "replicas": { "type": "integer", "default": 3 },
+                        "image": { "type": "string" },
+                        "ports": {
+                            "type": "array",
+                            "items": { "type": "integer", "default": 80 },
+                            "default": [80, 443],
+                        },
+                        "labels": {
+                            "type": "object",
+                            "additionalProperties": { "type": "string" },
+                            "default": { "app": "web" },
+                        },
+                        "resources": {
+                            "type": "object",
+                            "properties": {
+                                "cpu": { "type": "string", "default": "100m" },
+                                "memory": { "type": "string" },
+                            },
+                        },
+                        "tls": {
+                            "type": "object",
+                            "properties": {
+                                "secretName": { "type": "string" },
+                            },
+                        },
+                        "strategy": {
+                            "type": "object",
+                            "properties": {
+                                "type": { "type": "string", "default": "Recreate" },
+                            },
+                            "default": { "type": "RollingUpdate" },
+                        },
+                    },
+                },
+                "status": {
+                    "type": "object",
+                    "properties": { "ready": { "type": "boolean" } },
+                },
+            },
+        });
+        assert_eq!(
+            instantiate_defaults(&schema),
+            json!({
+                "spec": {
+                    "replicas": 3,
+                    "ports": [80, 443],
+                    "labels": { "app": "web" },
+                    "resources": { "cpu": "100m" },
+                    "strategy": { "type": "RollingUpdate" },
+                },
+            })
+        );
+        assert_eq!(instantiate_defaults(&json!({ "type": "object" })), json!({}));
+    }
+
+    #[test]
+    fn reports_and_fixes_required_properties_with_defaults() {
+        let mut schema = json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "type": "object",
+                    "properties": {
+                        "replicas": { "type": "integer", "default": 1 },
+                        "image": { "type": "string" },
+                        "pull/policy": { "type": "string", "default": "IfNotPresent" },
+                    },
+                    "required": ["replicas", "image", "pull/policy"],
+                },
+                "mode": {
+                    "type": "object",
+                    "properties": { "fast": { "type": "boolean", "default": false } },
+                    "oneOf": [{ "required": ["fast"] }],
+                },
+            },
+            "required": ["spec"],
+        });
+
+        let issues = required_with_defaults(&schema);
+        assert_eq!(issues, [
+            SchemaIssue {
+                pointer: "/properties/spec/properties/replicas".into(),
+                kind: SchemaIssueKind::RequiredWithDefault,
+            },
+            SchemaIssue {
+                pointer: "/properties/spec/properties/pull~1policy".into(),
+                kind: SchemaIssueKind::RequiredWithDefault,
+            },
+        ]);
+        assert_eq!(
+            issues[0].to_string(),
+            "/properties/spec/properties/replicas: property is `required`, but has a `default`"
+        );
+
+        assert_eq!(make_defaulted_optional(&mut schema), [
+            "/properties/spec/properties/replicas",
+            "/properties/spec/properties/pull~1policy",
+        ]);
+        assert_eq!(schema["properties"]["spec"]["required"], json!(["image"]));
+        // The variants of enums are left alone
+        assert_eq!(
+            schema["properties"]["mode"]["oneOf"],
+            json!([{ "required": ["fast"] }])
+        );
+        assert_eq!(required_with_defaults(&schema), []);
+        assert_eq!(make_defaulted_optional(&mut schema), Vec::<String>::new());
+    }
+}
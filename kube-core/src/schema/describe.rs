@@ -0,0 +1,170 @@
+use super::{InstanceType, Schema, SchemaObject, SingleOrVec};
+use serde_json::Value;
+
+/// Render a `kubectl explain`-like summary of a schema
+///
+/// Every property is printed on its own line with its type and required-ness, followed by its
+/// description (if any). Nested objects, array items and map values are expanded recursively,
+/// with each level indented by two spaces.
+///
+/// ```
+/// use kube_core::schema::describe_schema;
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "description": "A Foo",
+///     "properties": {
+///         "replicas": {
+///             "type": "integer",
+///             "description": "Number of replicas",
+///         },
+///     },
+///     "required": ["replicas"],
+/// });
+/// assert_eq!(
+///     describe_schema(&schema),
+///     "<object>\n  A Foo\n  replicas <integer> -required-\n    Number of replicas\n"
+/// );
+/// ```
+pub fn describe_schema(schema: &Value) -> String {
+    let mut out = String::new();
+    match serde_json::from_value::<Schema>(schema.clone()) {
+        Ok(schema) => {
+            out.push_str(&format!("<{}>\n", type_name(&schema)));
+            describe_node(&schema, 1, &mut out);
+        }
+        Err(_) => out.push_str("<unknown>\n"),
+    }
+    out
+}
+
+fn describe_node(schema: &Schema, depth: usize, out: &mut String) {
+    let Schema::Object(schema) = schema else {
+        return;
+    };
+    if let Some(description) = schema.metadata.as_ref().and_then(|m| m.description.as_ref()) {
+        for line in description.lines() {
+            out.push_str(&format!("{}{line}\n", indent(depth)));
+        }
+    }
+    describe_children(schema, depth, out);
+}
+
+fn describe_children(schema: &SchemaObject, depth: usize, out: &mut String) {
+    if let Some(object) = &schema.object {
+        for (name, property) in object.properties.iter().flatten() {
+            let required = if object.is_required(name) {
+                " -required-"
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "{}{name} <{}>{required}\n",
+                indent(depth),
+                type_name(property)
+            ));
+            describe_node(property, depth + 1, out);
+        }
+        if !object.has_properties() {
+            if let Some(Schema::Object(values)) = object.additional_properties.as_deref() {
+                describe_children(values, depth, out);
+            }
+        }
+    }
+    if let Some(SingleOrVec::Single(items)) = schema.array.as_ref().and_then(|a| a.items.as_ref()) {
+        if let Schema::Object(items) = items.as_ref() {
+            describe_children(items, depth, out);
+        }
+    }
+}
+
+fn type_name(schema: &Schema) -> String {
+    let schema = match schema {
+        Schema::Bool(true) => return "any".into(),
+        Schema::Bool(false) => return "never".into(),
+        Schema::Object(schema) => schema,
+    };
+    match &schema.instance_type {
+        Some(SingleOrVec::Single(tpe)) => match **tpe {
+            InstanceType::Array => match schema.array.as_ref().and_then(|a| a.items.as_ref()) {
+                Some(SingleOrVec::Single(items)) => format!("[]{}", type_name(items)),
+                _ => "[]any".into(),
+            },
+            InstanceType::Object => match schema.object.as_deref() {
+                Some(object) if !object.has_properties() => match object.additional_properties.as_deref() {
+                    Some(values @ Schema::Object(_)) => format!("map[string]{}", type_name(values)),
+                    _ => "object".into(),
+                },
+                _ => "object".into(),
+            },
+            tpe => tpe.name().into(),
+        },
+        Some(SingleOrVec::Vec(types)) => types.iter().map(|tpe| tpe.name()).collect::<Vec<_>>().join("|"),
+        None if schema.extensions.get("x-kubernetes-int-or-string") == Some(&Value::Bool(true)) => {
+            "int-or-string".into()
+        }
+        None => "any".into(),
+    }
+}
+
+fn indent(depth: usize) -> String {
+    "  ".repeat(depth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::describe_schema;
+
+    #[test]
+    fn describes_nested_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "description": "Spec of a Foo",
+            "properties": {
+                "labels": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                },
+                "name": {
+                    "type": "string",
+                    "description": "Name of the foo\nMust be unique",
+                },
+                "nested": {
+                    "type": "object",
+                    "description": "Nested object",
+                    "properties": {
+                        "enabled": { "type": "boolean", "nullable": true },
+                    },
+                },
+                "ports": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "port": { "type": "integer", "format": "int32" },
+                        },
+                        "required": ["port"],
+                    },
+                },
+            },
+            "required": ["name"],
+        });
+
+        assert_eq!(
+            describe_schema(&schema),
+            concat!(
+                "<object>\n",
+                "  Spec of a Foo\n",
+                "  labels <map[string]string>\n",
+                "  name <string> -required-\n",
+                "    Name of the foo\n",
+                "    Must be unique\n",
+                "  nested <object>\n",
+                "    Nested object\n",
+                "    enabled <boolean>\n",
+                "  ports <[]object>\n",
+                "    port <integer> -required-\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,543 @@
+use super::{
+    escape_pointer, for_each_subschema, subschemas, SchemaRewriteError, Step, StructuralSchemaRewriter,
+};
+use serde_json::{Map, Value};
+use std::{collections::BTreeSet, fmt};
+
+/// A keyword that differs between two schemas, as found by [`diff_schemas`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaDifference {
+    /// JSON pointer to the keyword that differs
+    pub pointer: String,
+    /// The keyword's value in the left schema, if it is set
+    pub left: Option<Value>,
+    /// The keyword's value in the right schema, if it is set
+    pub right: Option<Value>,
+}
+
+impl fmt::Display for SchemaDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "/"
+        } else {
+            &self.pointer
+        };
+        let show = |value: &Option<Value>| match value {
+            Some(value) => value.to_string(),
+            None => "<missing>".to_string(),
+        };
+        write!(f, "{pointer}: {} != {}", show(&self.left), show(&self.right))
+    }
+}
+
+/// List the keywords that differ between two schemas
+///
+/// Subschemas (such as `properties` or `items`) are compared recursively, so that each difference points
+/// at the individual keyword that changed, rather than at the whole schema that contains it.
+///
+/// ```
+/// use kube_core::schema::diff_schemas;
+/// use serde_json::json;
+///
+/// let left = json!({ "type": "object", "properties": { "a": { "type": "string" } } });
+/// let right = json!({ "type": "object", "properties": { "a": { "type": "integer" } } });
+/// let differences = diff_schemas(&left, &right);
+/// assert_eq!(differences.len(), 1);
+/// assert_eq!(
+///     differences[0].to_string(),
+///     r#"/properties/a/type: "string" != "integer""#
+/// );
+/// ```
+pub fn diff_schemas(left: &Value, right: &Value) -> Vec<SchemaDifference> {
+    let mut differences = Vec::new();
+    diff_subschemas(left, right, "", &mut differences);
+    differences
+}
+
+fn diff_subschemas(left: &Value, right: &Value, pointer: &str, differences: &mut Vec<SchemaDifference>) {
+    let (Some(left_keywords), Some(right_keywords)) = (left.as_object(), right.as_object()) else {
+        if left != right {
+            differences.push(SchemaDifference {
+                pointer: pointer.to_string(),
+                left: Some(left.clone()),
+                right: Some(right.clone()),
+            });
+        }
+        return;
+    };
+
+    let subschema_paths = subschemas(left)
+        .into_iter()
+        .chain(subschemas(right))
+        .map(|(path, _)| path)
+        .collect::<BTreeSet<_>>();
+    let keywords = left_keywords
+        .keys()
+        .chain(right_keywords.keys())
+        .collect::<BTreeSet<_>>();
+    for keyword in keywords {
+        let (left_value, right_value) = (left_keywords.get(keyword), right_keywords.get(keyword));
+        if left_value == right_value {
+            continue;
+        }
+        let keyword_pointer = format!("/{}", escape_pointer(keyword));
+        let nested = subschema_paths
+            .iter()
+            .filter(|path| *path == &keyword_pointer || path.starts_with(&format!("{keyword_pointer}/")))
+            .collect::<Vec<_>>();
+        if left_value.is_some() && right_value.is_some() && !nested.is_empty() {
+            for path in nested {
+                match (left.pointer(path), right.pointer(path)) {
+                    (Some(left), Some(right)) => {
+                        diff_subschemas(left, right, &format!("{pointer}{path}"), differences)
+                    }
+                    (left, right) => differences.push(SchemaDifference {
+                        pointer: format!("{pointer}{path}"),
+                        left: left.cloned(),
+                        right: right.cloned(),
+                    }),
+                }
+            }
+        } else {
+            differences.push(SchemaDifference {
+                pointer: format!("{pointer}{keyword_pointer}"),
+                left: left_value.cloned(),
+                right: right_value.cloned(),
+            });
+        }
+    }
+}
+
+/// Render the differences between two schemas as a unified diff-like report
+///
+/// This is a human-readable rendering of [`diff_schemas`], meant for CLI output and CI logs. The
+/// differences are grouped by the path of the schema that contains them, and each one is labeled as
+/// `added`, `removed`, `type-changed`, `required-changed` or `changed`, followed by the old (`-`) and
+/// new (`+`) value of the keyword. Returns an empty string if the schemas are equal.
+///
+/// ```
+/// use kube_core::schema::diff_to_string;
+/// use serde_json::json;
+///
+/// let old = json!({ "type": "object", "properties": { "a": { "type": "string" } } });
+/// let new = json!({ "type": "object", "properties": { "a": { "type": "integer" } } });
+/// assert_eq!(
+///     diff_to_string(&old, &new),
+///     "/properties/a:\n  type-changed type:\n    - \"string\"\n    + \"integer\"\n"
+/// );
+/// ```
+pub fn diff_to_string(old: &Value, new: &Value) -> String {
+    let mut groups = Vec::<(&str, Vec<(&str, &SchemaDifference)>)>::new();
+    let differences = diff_schemas(old, new);
+    for difference in &differences {
+        let (path, keyword) = difference
+            .pointer
+            .rsplit_once('/')
+            .unwrap_or(("", &difference.pointer));
+        match groups.iter_mut().find(|(group, _)| *group == path) {
+            Some((_, group)) => group.push((keyword, difference)),
+            None => groups.push((path, vec![(keyword, difference)])),
+        }
+    }
+
+    let mut out = String::new();
+    for (path, group) in groups {
+        out.push_str(&format!("{}:\n", if path.is_empty() { "/" } else { path }));
+        for (keyword, difference) in group {
+            let label = match (&difference.left, &difference.right, keyword) {
+                (None, _, _) => "added",
+                (_, None, _) => "removed",
+                (_, _, "type") => "type-changed",
+                (_, _, "required") => "required-changed",
+                _ => "changed",
+            };
+            let keyword = keyword.replace("~1", "/").replace("~0", "~");
+            out.push_str(&format!("  {label} {keyword}:\n"));
+            if let Some(left) = &difference.left {
+                out.push_str(&format!("    - {left}\n"));
+            }
+            if let Some(right) = &difference.right {
+                out.push_str(&format!("    + {right}\n"));
+            }
+        }
+    }
+    out
+}
+
+/// A change that [`StructuralSchemaRewriter`] would make to a schema, as listed by [`preview_changes`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedChange {
+    /// Name of the transform that makes the change, as in [`StructuralSchemaRewriter::transform_steps`]
+    pub transform: String,
+    /// JSON pointer to the subschema that is changed
+    pub pointer: String,
+    /// The changed keywords of the subschema before the change, omitting the ones that are added
+    pub before: Value,
+    /// The changed keywords of the subschema after the change, omitting the ones that are removed
+    pub after: Value,
+}
+
+impl fmt::Display for PlannedChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pointer = if self.pointer.is_empty() {
+            "/"
+        } else {
+            &self.pointer
+        };
+        write!(
+            f,
+            "{} at {pointer}: {} -> {}",
+            self.transform, self.before, self.after
+        )
+    }
+}
+
+/// List the changes that rewriting a schema with the default [`StructuralSchemaRewriter`] would make
+///
+/// The rewrite is applied to a copy of `schema` exactly as by
+/// [`rewrite_existing_schema`](super::rewrite_existing_schema), and the keywords changed by each transform
+/// are reported per subschema, in the order in which they are applied (subschemas before the schemas that
+/// contain them). This makes it possible to review what a rewrite does before adopting it. If `schema` can't
+/// be rewritten, the error of the rewrite is returned instead.
+///
+/// ```
+/// use kube_core::schema::preview_changes;
+/// use serde_json::json;
+///
+/// let schema = json!({ "type": ["integer", "string"] });
+/// let changes = preview_changes(&schema)?;
+/// assert_eq!(changes.len(), 1);
+/// assert_eq!(
+///     changes[0].to_string(),
+///     r#"int_or_string_types at /: {"type":["integer","string"]} -> {"x-kubernetes-int-or-string":true}"#
+/// );
+/// # Ok::<(), kube_core::schema::SchemaRewriteError>(())
+/// ```
+pub fn preview_changes(schema: &Value) -> Result<Vec<PlannedChange>, SchemaRewriteError> {
+    let mut schema = schemars::Schema::try_from(schema.clone())
+        .map_err(|source| SchemaRewriteError::InvalidSchema {
+            pointer: String::new(),
+            source,
+        })?
+        .to_value();
+    let rewriter = StructuralSchemaRewriter::default();
+    let mut changes = Vec::new();
+    let mut trace = |step: Step, pointer: &str, before: &Value, after: &Value| {
+        let mut planned = Vec::<PlannedChange>::new();
+        for difference in diff_schemas(before, after) {
+            let (path, keyword) = difference
+                .pointer
+                .rsplit_once('/')
+                .unwrap_or(("", &difference.pointer));
+            let path = format!("{pointer}{path}");
+            let keyword = keyword.replace("~1", "/").replace("~0", "~");
+            let index = match planned.iter().position(|change| change.pointer == path) {
+                Some(index) => index,
+                None => {
+                    planned.push(PlannedChange {
+                        transform: step.name().to_string(),
+                        pointer: path,
+                        before: Value::Object(Map::new()),
+                        after: Value::Object(Map::new()),
+                    });
+                    planned.len() - 1
+                }
+            };
+            let change = &mut planned[index];
+            if let (Some(value), Value::Object(before)) = (difference.left, &mut change.before) {
+                before.insert(keyword.clone(), value);
+            }
+            if let (Some(value), Value::Object(after)) = (difference.right, &mut change.after) {
+                after.insert(keyword, value);
+            }
+        }
+        changes.extend(planned);
+    };
+    rewriter.apply_steps(&rewriter.steps(), &mut schema, &mut Vec::new(), Some(&mut trace))?;
+    Ok(changes)
+}
+
+/// List the properties that are required by the `new` schema, but weren't required by the `old` schema
+///
+/// Making a property required is the most common breaking change between versions of a CRD, since objects
+/// that were valid before may be missing it. Returns the JSON pointers of these properties (in `new`).
+/// Properties of objects that don't exist in the `old` schema at all are only reported through their
+/// closest existing ancestor, since there can't be any old objects that contain them.
+///
+/// ```
+/// use kube_core::schema::newly_required;
+/// use serde_json::json;
+///
+/// let old = json!({ "type": "object", "properties": { "a": { "type": "string" } } });
+/// let new = json!({
+///     "type": "object",
+///     "properties": { "a": { "type": "string" }, "b": { "type": "string" } },
+///     "required": ["a", "b"],
+/// });
+/// assert_eq!(newly_required(&old, &new), ["/properties/a", "/properties/b"]);
+/// ```
+pub fn newly_required(old: &Value, new: &Value) -> Vec<String> {
+    let mut required = Vec::new();
+    for_each_subschema(new, "", &mut |pointer, subschema| {
+        let Some(old_subschema) = old.pointer(pointer) else {
+            return;
+        };
+        let Some(Value::Array(names)) = subschema.get("required") else {
+            return;
+        };
+        let was_required = |name: &str| matches!(old_subschema.get("required"), Some(Value::Array(old_names)) if old_names.iter().any(|old| old == name));
+        for name in names.iter().filter_map(Value::as_str) {
+            // `required` of value validations (such as `oneOf` branches) refer to the properties of their parent
+            if subschema
+                .pointer(&format!("/properties/{}", escape_pointer(name)))
+                .is_some()
+                && !was_required(name)
+            {
+                required.push(format!("{pointer}/properties/{}", escape_pointer(name)));
+            }
+        }
+    });
+    required
+}
+
+/// Assert that two schemas are equal, listing the keywords that differ if they aren't
+///
+/// This is like [`assert_eq!`], but the failure message is built with [`diff_schemas`](crate::schema::diff_schemas),
+/// which is easier to read than the full schemas for large schemas.
+///
+/// ```
+/// use serde_json::json;
+///
+/// let schema = json!({ "type": "object", "properties": { "a": { "type": "string" } } });
+/// kube_core::assert_schema_eq!(schema, json!({ "type": "object", "properties": { "a": { "type": "string" } } }));
+/// ```
+#[macro_export]
+macro_rules! assert_schema_eq {
+    ($left:expr, $right:expr $(,)?) => {{
+        let differences = $crate::schema::diff_schemas(&$left, &$right);
+        if !differences.is_empty() {
+            let differences = differences
+                .iter()
+                .map(|difference| format!("  {difference}\n"))
+                .collect::<String>();
+            panic!("schemas are not equal (left != right):\n{differences}");
+        }
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        diff_schemas, diff_to_string, newly_required, preview_changes, PlannedChange, SchemaDifference,
+    };
+    use crate::schema::SchemaRewriteError;
+    use serde_json::json;
+
+    #[test]
+    fn reports_changed_keywords_of_nested_subschemas() {
+        let left = json!({
+            "type": "object",
+            "description": "A Foo",
+            "properties": {
+                "port": { "type": "integer", "minimum": 0 },
+                "hosts": { "type": "array", "items": { "type": "string" } },
+                "removed": { "type": "string" },
+            },
+            "required": ["port"],
+        });
+        let right = json!({
+            "type": "object",
+            "description": "A Foo",
+            "properties": {
+                "port": { "type": "string", "minimum": 0 },
+                "hosts": { "type": "array", "items": { "type": "string", "maxLength": 253 } },
+            },
+            "required": ["hosts", "port"],
+        });
+        assert_eq!(diff_schemas(&left, &right), [
+            SchemaDifference {
+                pointer: "/properties/hosts/items/maxLength".into(),
+                left: None,
+                right: Some(json!(253)),
+            },
+            SchemaDifference {
+                pointer: "/properties/port/type".into(),
+                left: Some(json!("integer")),
+                right: Some(json!("string")),
+            },
+            SchemaDifference {
+                pointer: "/properties/removed".into(),
+                left: Some(json!({ "type": "string" })),
+                right: None,
+            },
+            SchemaDifference {
+                pointer: "/required".into(),
+                left: Some(json!(["port"])),
+                right: Some(json!(["hosts", "port"])),
+            },
+        ]);
+    }
+
+    #[test]
+    fn renders_grouped_and_labeled_differences() {
+        let old = json!({
+            "type": "object",
+            "properties": {
+                "port": { "type": "integer", "minimum": 0 },
+                "hosts": { "type": "array", "items": { "type": "string" } },
+                "removed": { "type": "string" },
+            },
+            "required": ["port"],
+        });
+        let new = json!({
+            "type": "object",
+            "properties": {
+                "port": { "type": "string", "minimum": 1 },
+                "hosts": { "type": "array", "items": { "type": "string", "maxLength": 253 } },
+                "a/b": { "type": "boolean" },
+            },
+            "required": ["hosts", "port"],
+        });
+        assert_eq!(
+            diff_to_string(&old, &new),
+            concat!(
+                "/properties:\n",
+                "  added a/b:\n",
+                "    + {\"type\":\"boolean\"}\n",
+                "  removed removed:\n",
+                "    - {\"type\":\"string\"}\n",
+                "/properties/hosts/items:\n",
+                "  added maxLength:\n",
+                "    + 253\n",
+                "/properties/port:\n",
+                "  changed minimum:\n",
+                "    - 0\n",
+                "    + 1\n",
+                "  type-changed type:\n",
+                "    - \"integer\"\n",
+                "    + \"string\"\n",
+                "/:\n",
+                "  required-changed required:\n",
+                "    - [\"port\"]\n",
+                "    + [\"hosts\",\"port\"]\n",
+            )
+        );
+        assert_eq!(diff_to_string(&new, &new), "");
+    }
+
+    #[test]
+    fn assert_schema_eq_lists_differences() {
+        let left = json!({ "type": "object", "properties": { "a": { "type": "string" } } });
+        let right = json!({ "type": "object", "properties": { "a": { "type": "integer" } } });
+        let panic = std::panic::catch_unwind(|| crate::assert_schema_eq!(left, right)).unwrap_err();
+        assert_eq!(
+            panic.downcast_ref::<String>().map(String::as_str),
+            Some("schemas are not equal (left != right):\n  /properties/a/type: \"string\" != \"integer\"\n")
+        );
+    }
+
+    #[test]
+    fn reports_newly_required_nested_properties() {
+        let old = json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "type": "object",
+                    "properties": {
+                        "replicas": { "type": "integer" },
+                        "ports": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": { "port": { "type": "integer" } },
+                                "required": ["port"],
+                            },
+                        },
+                    },
+                    "required": ["replicas"],
+                },
+            },
+            "required": ["spec"],
+        });
+        let new = json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "type": "object",
+                    "properties": {
+                        "replicas": { "type": "integer" },
+                        "ports": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "port": { "type": "integer" },
+                                    "protocol": { "type": "string" },
+                                },
+                                "required": ["port", "protocol"],
+                            },
+                        },
+                        "image": {
+                            "type": "object",
+                            "properties": { "name": { "type": "string" } },
+                            "required": ["name"],
+                        },
+                    },
+                    "required": ["replicas"],
+                },
+            },
+            "required": ["spec"],
+        });
+        assert_eq!(newly_required(&old, &new), [
+            "/properties/spec/properties/ports/items/properties/protocol"
+        ]);
+        assert_eq!(newly_required(&new, &new), Vec::<String>::new());
+    }
+
+    #[test]
+    fn previews_hoisting_of_one_of_enums() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "mode": {
+                    "oneOf": [
+                        { "type": "string", "enum": ["Fast"] },
+                        { "type": "string", "enum": ["Slow"] },
+                    ],
+                },
+            },
+        });
+        let changes = preview_changes(&schema).unwrap();
+        assert!(changes.contains(&PlannedChange {
+            transform: "hoist_one_of".to_string(),
+            pointer: "/properties/mode".to_string(),
+            before: json!({
+                "oneOf": [
+                    { "type": "string", "enum": ["Fast"] },
+                    { "type": "string", "enum": ["Slow"] },
+                ],
+            }),
+            after: json!({ "type": "string", "enum": ["Fast", "Slow"] }),
+        }));
+        // The schema that was passed in is left as it is
+        assert!(schema["properties"]["mode"].get("oneOf").is_some());
+    }
+
+    #[test]
+    fn previews_report_rewrite_errors() {
+        assert!(matches!(
+            preview_changes(&json!(1)),
+            Err(SchemaRewriteError::InvalidSchema { .. })
+        ));
+        let mismatched = json!({
+            "oneOf": [
+                { "type": "object", "properties": { "a": { "type": "string" } } },
+                { "type": "object", "properties": { "a": { "type": "integer" } } },
+            ],
+        });
+        assert!(matches!(
+            preview_changes(&mismatched),
+            Err(SchemaRewriteError::Hoist { path, .. }) if path.is_empty()
+        ));
+    }
+}
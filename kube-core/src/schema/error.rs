@@ -0,0 +1,196 @@
+use serde_json::Value;
+use thiserror::Error;
+
+/// Possible errors when rewriting a schema into a structural schema
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum SchemaRewriteError {
+    /// The schema (or one of its subschemas) is not a valid JSON Schema
+    #[error("invalid schema at {pointer:?}: {source}")]
+    InvalidSchema {
+        /// JSON pointer to the offending subschema
+        pointer: String,
+        /// The underlying deserialization error
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// A property's (or an array's `items`) schema is `false`, or a typed `enum` that only lists `null`
+    /// without being `nullable`, meaning that no value is valid for it
+    #[error("field at {path:?} can never be valid, which can't be expressed in a structural schema")]
+    UninhabitedField {
+        /// JSON pointer to the offending property or `items`
+        path: String,
+    },
+
+    /// A schema allows multiple types, which can't be expressed in a structural schema
+    ///
+    /// The only exception is the combination of integers and strings, which is rewritten to
+    /// `x-kubernetes-int-or-string`.
+    #[error("field at {path:?} may be any of {types:?}, which can't be expressed in a structural schema")]
+    MultipleTypes {
+        /// JSON pointer to the offending schema
+        path: String,
+        /// The allowed types (other than `null`)
+        types: Vec<String>,
+    },
+
+    /// A schema uses a keyword that Kubernetes doesn't support, and that can't be rewritten
+    #[error("the keyword {keyword:?} at {path:?} is not supported by Kubernetes")]
+    UnsupportedKeyword {
+        /// JSON pointer to the offending schema
+        path: String,
+        /// The unsupported keyword
+        keyword: String,
+    },
+
+    /// A variant of an internally tagged enum has a field with the same name as the enum's tag
+    #[error("the tag {property:?} of the enum at {path:?} collides with a field of one of its variants")]
+    DiscriminatorCollision {
+        /// JSON pointer to the enum
+        path: String,
+        /// Name of the tag property
+        property: String,
+    },
+
+    /// The members of an `allOf` define the same property with different schemas
+    #[error("the property {property:?} has conflicting schemas in the `allOf` at {path:?}")]
+    ConflictingProperty {
+        /// JSON pointer to the schema with the `allOf`
+        path: String,
+        /// Name of the conflicting property
+        property: String,
+    },
+
+    /// The variants of an enum can't be merged into a single schema
+    #[error("the variants of the enum at {path:?} can't be merged: {source}")]
+    Hoist {
+        /// JSON pointer to the enum
+        path: String,
+        /// Why the variants can't be merged
+        #[source]
+        source: Box<HoistError>,
+    },
+
+    /// A property can't be renamed, because the new name is already used by another property
+    #[error("a property already exists at {path:?}")]
+    PropertyExists {
+        /// JSON pointer to the existing property
+        path: String,
+    },
+
+    /// A subschema can't be unwrapped by
+    /// [`StructuralSchemaRewriterBuilder::unwrap_single_property`](super::StructuralSchemaRewriterBuilder::unwrap_single_property),
+    /// because it isn't an object with exactly one property
+    #[error("the schema at {path:?} is not an object with a single property")]
+    NotSingleProperty {
+        /// JSON pointer to the schema
+        path: String,
+    },
+
+    /// An `enum` value is longer than the limit set by
+    /// [`StructuralSchemaRewriterBuilder::enum_value_max_length`](super::StructuralSchemaRewriterBuilder::enum_value_max_length)
+    #[error("the enum value {value:?} at {path:?} is longer than {max_length} characters")]
+    EnumValueTooLong {
+        /// JSON pointer to the enum
+        path: String,
+        /// The offending value
+        value: String,
+        /// The configured limit
+        max_length: u64,
+    },
+
+    /// A field path doesn't refer to any field of the schema
+    #[error("no field found at {path:?}")]
+    FieldNotFound {
+        /// The dot-separated path of the field
+        path: String,
+    },
+
+    /// A JSON pointer doesn't refer to any subschema
+    #[error("no subschema found at {pointer:?}")]
+    PointerNotFound {
+        /// The JSON pointer that couldn't be resolved
+        pointer: String,
+    },
+
+    /// A subschema can't be marked with `x-kubernetes-preserve-unknown-fields`, because it isn't an object
+    #[error("the schema at {path:?} may be any of {types:?}, so it can't preserve unknown fields")]
+    NotAnObject {
+        /// JSON pointer to the schema
+        path: String,
+        /// The types allowed by the schema
+        types: Vec<String>,
+    },
+
+    /// A pattern passed to [`invalid_property_names`](super::invalid_property_names) is not a valid regular
+    /// expression
+    #[error("the pattern {pattern:?} is not a valid regular expression: {reason}")]
+    InvalidPattern {
+        /// The offending pattern
+        pattern: String,
+        /// Why the pattern can't be compiled
+        reason: String,
+    },
+
+    /// A transform would lose information, which isn't allowed by
+    /// [`StructuralSchemaRewriterBuilder::lossless`](super::StructuralSchemaRewriterBuilder::lossless)
+    #[error("rewriting the schema at {path:?} would lose information: {detail}")]
+    LossyTransform {
+        /// JSON pointer to the affected schema
+        path: String,
+        /// What would be lost
+        detail: String,
+    },
+
+    /// The serialized schema exceeds the size limit
+    #[error(
+        "schema is {size} bytes, which exceeds the limit of {limit} bytes (consider stripping descriptions)"
+    )]
+    CrdTooLarge {
+        /// Size of the serialized schema, in bytes
+        size: usize,
+        /// The maximum allowed size, in bytes
+        limit: usize,
+    },
+
+    /// The schema has more fields than allowed
+    #[error("schema has {count} fields, which exceeds the limit of {limit} fields")]
+    TooManyFields {
+        /// Number of fields in the schema
+        count: usize,
+        /// The maximum allowed number of fields
+        limit: usize,
+    },
+}
+
+/// Why the variants of an enum can't be merged into a single schema, see [`SchemaRewriteError::Hoist`]
+///
+/// Schemas are rendered as compact JSON.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum HoistError {
+    /// A variant has a different `type` than the enum (or the variants before it)
+    #[error("the variant {variant} has the type {found}, but the enum has the type {expected}")]
+    MismatchedTypes {
+        /// The `type` of the enum
+        expected: Value,
+        /// The `type` of the variant
+        found: Value,
+        /// The offending variant
+        variant: Value,
+    },
+
+    /// Two variants define the same property with different schemas
+    #[error("the property {property:?} is {found} in the variant {variant}, but {expected} in another")]
+    MismatchedProperty {
+        /// Name of the property
+        property: String,
+        /// The schema of the property in the variants before
+        expected: Value,
+        /// The schema of the property in the offending variant
+        found: Value,
+        /// The offending variant
+        variant: Value,
+    },
+}
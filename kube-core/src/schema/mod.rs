@@ -0,0 +1,8421 @@
+//! Utilities for managing [`CustomResourceDefinition`] schemas
+//!
+//! Properties are sorted by name, unless the `preserve-order` feature is enabled, in which case they keep
+//! the order in which they were generated (usually the declaration order of the fields).
+//!
+//! The keywords of each schema are sorted by name as well, unless the `preserve-order` feature is enabled, in
+//! which case they are emitted in a fixed order: annotations (such as `description`) first, followed by
+//! `type`, `format`, `enum`, the junctors, the array and object keywords, and then all other keywords
+//! (including extensions) sorted by name. See
+//! [`StructuralSchemaRewriterBuilder::canonical_keyword_order`] for an order that is closer to how Kubernetes
+//! documents schemas.
+//!
+//! Schemas are passed around as [`serde_json::Value`]s, whose objects are sorted by key unless the
+//! `preserve_order` feature of `serde_json` is enabled. The `preserve-order` feature doesn't enable it, since
+//! that would change the order of every `serde_json` map in the application, so the order is only kept if the
+//! application enables `serde_json/preserve_order` as well.
+//!
+//! [`CustomResourceDefinition`]: `k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition`
+
+#[cfg(feature = "schema-regex")]
+pub use self::check::{invalid_patterns, invalid_property_names, PROPERTY_NAME_PATTERN};
+#[cfg(feature = "schema-testing")]
+pub use self::testing::{assert_schema_accepted, validate_instance};
+pub use self::{
+    check::{
+        check_field_count, check_schema_size, check_structural, complexity_report, field_count,
+        invalid_defaults, invalid_list_map_keys, missing_cost_bounds, required_with_defaults,
+        unbounded_map_keys, undocumented_properties, ComplexityReport, SchemaIssue, SchemaIssueKind,
+        LAST_APPLIED_ANNOTATION_LIMIT,
+    },
+    columns::{check_printer_columns, PrinterColumnIssue, PrinterColumnIssueKind},
+    defaults::{instantiate_defaults, make_defaulted_optional},
+    describe::describe_schema,
+    diff::{diff_schemas, diff_to_string, newly_required, preview_changes, PlannedChange, SchemaDifference},
+    error::{HoistError, SchemaRewriteError},
+    validations::{attach_field_validations, FieldValidations},
+    view::StructuralSchemaView,
+};
+
+/// Checks for schemas that Kubernetes would reject.
+mod check;
+/// Checks for the `additionalPrinterColumns` of CRDs.
+mod columns;
+/// Instances built from the defaults of schemas, and fixes for defaults that contradict `required`.
+mod defaults;
+/// Human-readable summaries of schemas.
+mod describe;
+/// Keyword-level differences between schemas.
+mod diff;
+/// Errors raised while rewriting schemas.
+mod error;
+/// Helpers for testing schemas without a cluster.
+#[cfg(feature = "schema-testing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "schema-testing")))]
+mod testing;
+/// CEL validation rules for individual fields.
+mod validations;
+/// Read-only access to schemas.
+mod view;
+
+use schemars::generate::SchemaSettings;
+
+use crate::Rule;
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::JSONSchemaProps;
+use schemars::{transform::Transform, JsonSchema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The map of `properties`, which keeps the order of the generated schema with the `preserve-order` feature
+#[cfg(feature = "preserve-order")]
+type PropertyMap<V> = indexmap::IndexMap<String, V>;
+#[cfg(feature = "preserve-order")] use indexmap::map::Entry;
+/// The map of `properties`, which is sorted by name unless the `preserve-order` feature is enabled
+#[cfg(not(feature = "preserve-order"))]
+type PropertyMap<V> = BTreeMap<String, V>;
+#[cfg(not(feature = "preserve-order"))] use std::collections::btree_map::Entry;
+
+/// schemars [`Visitor`] that rewrites a [`Schema`] to conform to Kubernetes' "structural schema" rules
+///
+/// The following transformations are applied
+///  * Inline references to definitions (`$ref`), keeping the description and default of the referencing field
+///  * Rewrite `const` to an `enum` with a single value
+///  * Rewrite `type: [integer, string]` to `x-kubernetes-int-or-string` (other type unions are rejected)
+///  * Rewrite fields that can only be `null` (such as `()`) to nullable `x-kubernetes-preserve-unknown-fields`
+///  * Rewrite tuples (such as tuple structs) with items of the same type to arrays with a fixed length, and
+///    tuples with items of different types to `x-kubernetes-preserve-unknown-fields`
+///  * Merge an `allOf` of several objects into a single object, failing if they define a property differently
+///  * Rewrite `items: true` to items with `x-kubernetes-preserve-unknown-fields`
+///  * Mark fields that accept any value (such as `serde_json::Value`) with `x-kubernetes-preserve-unknown-fields`
+///  * Rewrite enums from `oneOf` to `object`s with multiple variants ([schemars#84](https://github.com/GREsau/schemars/issues/84))
+///  * Rewrite untagged enums from `anyOf` to `object`s with multiple variants ([kube#1028](https://github.com/kube-rs/kube/pull/1028))
+///    (a field that is optional in some variants and required in others is hoisted as `nullable`)
+///  * Rewrite enums mixing objects, arrays and primitives (such as unit and struct variants) to `x-kubernetes-preserve-unknown-fields`
+///  * Replace `unevaluatedProperties` with its closest equivalent (`additionalProperties: false` or
+///    `x-kubernetes-preserve-unknown-fields`), once the properties of enum variants have been hoisted
+///  * Remove the `enum` constraint from enums (or the tags of internally tagged enums) marked with
+///    `x-kube-rs-open-enum: true`, such as enums with a `#[serde(other)]` catch-all variant
+///  * Mark enums of hard-coded objects with `x-kubernetes-preserve-unknown-fields` so that their values aren't pruned
+///  * Rewrite `additionalProperties` from `#[serde(flatten)]` to `x-kubernetes-preserve-unknown-fields` ([kube#844](https://github.com/kube-rs/kube/issues/844)),
+///    unless the struct has no other fields (in which case the schema of the map values is kept)
+///  * Remove the `contentEncoding` and `contentMediaType` keywords, which Kubernetes doesn't support
+///  * Replace `examples` with a single `example`, which is all that Kubernetes supports
+///  * Remove empty `properties`, `required` and `anyOf` that are left behind by the rewrites above
+///
+/// Subschemas marked with `x-kube-rs-skip-rewrite: true` (such as hand-written schemas from
+/// `#[schemars(schema_with)]`) are left untouched, apart from removing the marker itself.
+///
+/// This is used automatically by `kube::derive`'s `#[derive(CustomResource)]`,
+/// but it can also be used manually with [`SchemaSettings::with_transform`].
+///
+/// Additional, opt-in transformations can be enabled through [`StructuralSchemaRewriter::builder`], which
+/// can also disable some of the default ones.
+///
+/// # Panics
+///
+/// [`Transform::transform`] panics if the transform could not be applied, use
+/// [`StructuralSchemaRewriter::try_transform`] to handle these errors instead. For example, properties that
+/// are defined by multiple enum variants are merged if they are identical, but rejected if they conflict
+/// (see [`SchemaRewriteError::Hoist`]), and no field may have a schema that never validates (see
+/// [`SchemaRewriteError::UninhabitedField`]) or that allows multiple types other than integers and strings
+/// (see [`SchemaRewriteError::MultipleTypes`]). Dynamic references (`$dynamicRef` and `$recursiveRef`) and
+/// `contains` aren't supported either (see [`SchemaRewriteError::UnsupportedKeyword`]).
+#[derive(Debug, Clone)]
+pub struct StructuralSchemaRewriter {
+    normalize_byte_arrays: bool,
+    nullable_type_arrays: bool,
+    map_key_max_length: Option<u64>,
+    enum_value_max_length: Option<u64>,
+    strip_descriptions: bool,
+    compact: bool,
+    content_encoding_as_format: bool,
+    lossless: bool,
+    opaque_types: Vec<String>,
+    preserve_examples: bool,
+    source_types: bool,
+    nullable_policy: Option<NullablePolicy>,
+    root_description: Option<String>,
+    preserve_unknown_root: bool,
+    unwrapped_properties: Vec<String>,
+    open_enum_tag_validations: bool,
+    canonical_keyword_order: bool,
+    copy_variant_descriptions: bool,
+    tagged_enum_validations: bool,
+    // The default transforms are enabled unless these are set, so that all options default to `false`
+    skip_hoist_enums: bool,
+    skip_strip_unique_items: bool,
+    skip_preserve_unknown_flattened_maps: bool,
+    skip_remove_null_enum_variant: bool,
+    skip_inline_references: bool,
+    break_cycles: bool,
+    warnings: Vec<String>,
+}
+
+/// The [`StructuralSchemaRewriter`] with the default transforms
+///
+/// This keeps the rewriter usable like the unit struct it used to be:
+///
+/// ```
+/// use kube_core::schema::StructuralSchemaRewriter;
+/// use schemars::generate::SchemaSettings;
+///
+/// let generator = SchemaSettings::openapi3()
+///     .with_transform(StructuralSchemaRewriter)
+///     .into_generator();
+/// ```
+#[allow(non_upper_case_globals)]
+pub const StructuralSchemaRewriter: StructuralSchemaRewriter = StructuralSchemaRewriter {
+    normalize_byte_arrays: false,
+    nullable_type_arrays: false,
+    map_key_max_length: None,
+    enum_value_max_length: None,
+    strip_descriptions: false,
+    compact: false,
+    content_encoding_as_format: false,
+    lossless: false,
+    opaque_types: Vec::new(),
+    preserve_examples: false,
+    source_types: false,
+    nullable_policy: None,
+    root_description: None,
+    preserve_unknown_root: false,
+    unwrapped_properties: Vec::new(),
+    open_enum_tag_validations: false,
+    canonical_keyword_order: false,
+    copy_variant_descriptions: false,
+    tagged_enum_validations: false,
+    skip_hoist_enums: false,
+    skip_strip_unique_items: false,
+    skip_preserve_unknown_flattened_maps: false,
+    skip_remove_null_enum_variant: false,
+    skip_inline_references: false,
+    break_cycles: false,
+    warnings: Vec::new(),
+};
+
+impl Default for StructuralSchemaRewriter {
+    fn default() -> Self {
+        StructuralSchemaRewriter
+    }
+}
+
+/// How the nullability of properties should relate to whether they are required,
+/// see [`StructuralSchemaRewriterBuilder::nullable_policy`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NullablePolicy {
+    /// Mark all optional properties without a non-null `default` as `nullable`
+    OptionalImpliesNullable,
+    /// Mark all optional properties without a non-null `default` as `nullable`, and no required properties
+    NullableIffOptional,
+}
+
+/// Builder for a [`StructuralSchemaRewriter`] with non-default options
+///
+/// ```
+/// use kube_core::schema::StructuralSchemaRewriter;
+///
+/// let rewriter = StructuralSchemaRewriter::builder()
+///     .normalize_byte_arrays(true)
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StructuralSchemaRewriterBuilder {
+    rewriter: StructuralSchemaRewriter,
+}
+
+impl StructuralSchemaRewriterBuilder {
+    /// Rewrite byte arrays to base64-encoded strings (`type: string, format: byte`)
+    ///
+    /// By default, `serde` serializes a `Vec<u8>` as an array of integers, which `schemars` renders as
+    /// `type: array` with `items` of `type: integer, format: uint8`. Types like
+    /// [`ByteString`](k8s_openapi::ByteString), or fields using a base64 adapter such as
+    /// `#[serde(with = "...")]`, are serialized as strings instead, and are rendered either as
+    /// `format: byte` or `format: binary` depending on their `JsonSchema` implementation.
+    ///
+    /// Since `#[serde(with)]` adapters are invisible to `schemars`, enabling this option rewrites all of
+    /// these representations to `type: string, format: byte`, which is what Kubernetes expects for binary
+    /// data. Only enable this if all byte arrays in the schema are actually serialized as base64 strings.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn normalize_byte_arrays(mut self, enabled: bool) -> Self {
+        self.rewriter.normalize_byte_arrays = enabled;
+        self
+    }
+
+    /// Express nullability as `type: [T, "null"]` rather than `nullable: true`
+    ///
+    /// Kubernetes only understands the OpenAPI v3 `nullable` keyword, and rejects schemas with multiple
+    /// types. Plain JSON Schema validators ignore `nullable` though, so this option is only meant for
+    /// schemas that are consumed outside of Kubernetes. `null` is also added to the `enum` of nullable enums.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn nullable_type_arrays(mut self, enabled: bool) -> Self {
+        self.rewriter.nullable_type_arrays = enabled;
+        self
+    }
+
+    /// Bound the length of the keys of all maps (`additionalProperties`) to `max_length`
+    ///
+    /// The cost of CEL validation rules that iterate over a map depends on the worst-case size of its keys.
+    /// CRDs can't bound them with `propertyNames`, which `JSONSchemaProps` doesn't model (so it is dropped
+    /// from CRDs generated by `#[derive(CustomResource)]`). Instead, this adds the validation rule
+    /// `self.all(k, size(k) <= max_length)` to all maps that don't already have one, which are listed by
+    /// [`unbounded_map_keys`]. Maps that set `propertyNames.maxLength` keep that length in their rule, and
+    /// `propertyNames` itself is left as it is.
+    ///
+    /// Defaults to `None`, leaving map keys unbounded.
+    #[must_use]
+    pub fn map_key_max_length(mut self, max_length: Option<u64>) -> Self {
+        self.rewriter.map_key_max_length = max_length;
+        self
+    }
+
+    /// Reject string `enum` values that are longer than `max_length` characters
+    ///
+    /// Long enum values (such as ones generated from long string constants) bloat every CRD that uses them,
+    /// and may exceed the limits of clients that store them. Values are checked after the variants of enums
+    /// have been merged, and the rewrite fails with [`SchemaRewriteError::EnumValueTooLong`] for the first
+    /// value that is too long.
+    ///
+    /// Defaults to `None`, allowing values of any length.
+    #[must_use]
+    pub fn enum_value_max_length(mut self, max_length: Option<u64>) -> Self {
+        self.rewriter.enum_value_max_length = max_length;
+        self
+    }
+
+    /// Make the nullability of properties consistent with whether they are required, according to `policy`
+    ///
+    /// Optional fields can be omitted, but Kubernetes only accepts an explicit `null` for them if they are
+    /// `nullable`, which surprises clients that send `null` for fields they don't set. `null` is also added
+    /// to the `enum` of properties that become nullable.
+    ///
+    /// Properties with a non-null `default` are never made nullable. These are usually fields with a
+    /// `#[serde(default)]` that aren't `Option`s, such as `replicas: u32`, so the apiserver would accept a
+    /// `null` that the Rust type can't deserialize.
+    ///
+    /// Defaults to `None`, leaving nullability as generated.
+    #[must_use]
+    pub fn nullable_policy(mut self, policy: Option<NullablePolicy>) -> Self {
+        self.rewriter.nullable_policy = policy;
+        self
+    }
+
+    /// Remove all descriptions from the schema
+    ///
+    /// Descriptions are usually the bulk of a large CRD, so this is the last resort for CRDs that exceed the
+    /// size limits of the apiserver (see [`check_schema_size`]). Note that `kubectl explain` won't be able
+    /// to document the fields anymore.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn strip_descriptions(mut self, enabled: bool) -> Self {
+        self.rewriter.strip_descriptions = enabled;
+        self
+    }
+
+    /// Replace the `description` of the root schema with `description`
+    ///
+    /// The root description is usually derived from the doc comment of the struct, which is shared by all
+    /// versions of a CRD. This is useful for adding version-specific notes, such as a deprecation notice.
+    /// The override is applied after [`strip_descriptions`](Self::strip_descriptions), so it is kept
+    /// either way.
+    ///
+    /// Defaults to `None`, keeping the derived description.
+    #[must_use]
+    pub fn root_description(mut self, description: Option<String>) -> Self {
+        self.rewriter.root_description = description;
+        self
+    }
+
+    /// Replace the root schema with `x-kubernetes-preserve-unknown-fields`, for fully dynamic custom resources
+    ///
+    /// The type is still rewritten as usual (so errors in it are still reported), and the root keeps its
+    /// description, but all of its structure is removed so that the apiserver accepts (and keeps) any object.
+    /// See [`dynamic_root_schema`] for a schema-less CRD that isn't generated from a type at all.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn preserve_unknown_root(mut self, enabled: bool) -> Self {
+        self.rewriter.preserve_unknown_root = enabled;
+        self
+    }
+
+    /// Minimize the size of the schema, while keeping all descriptions
+    ///
+    /// This strips annotations that Kubernetes doesn't use (`title`, `$comment` and `externalDocs`),
+    /// inlines `allOf`, `anyOf` and `oneOf` junctors that only wrap a single schema, and removes duplicate
+    /// `enum` values. Useful for large CRDs that would otherwise exceed the size limits of the apiserver.
+    ///
+    /// The `properties` and `required` properties of an inlined schema are merged into those of its wrapper.
+    /// Junctors whose schema defines any other keyword differently from the wrapper are kept as they are.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn compact(mut self, enabled: bool) -> Self {
+        self.rewriter.compact = enabled;
+        self
+    }
+
+    /// Rewrite `contentEncoding: base64` to `format: byte`, rather than removing it
+    ///
+    /// JSON Schema 2020-12 describes encoded strings with the `contentEncoding` and `contentMediaType`
+    /// keywords, which Kubernetes doesn't support. They are always removed from the schema, but enabling this
+    /// option keeps base64-encoded strings validated as such by Kubernetes (unless they already have a `format`).
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn content_encoding_as_format(mut self, enabled: bool) -> Self {
+        self.rewriter.content_encoding_as_format = enabled;
+        self
+    }
+
+    /// Fail with [`SchemaRewriteError::LossyTransform`] instead of losing information
+    ///
+    /// Some of the default transforms can't preserve everything that the original schema expresses, for
+    /// example the descriptions of unit enum variants or `uniqueItems`. With this option, the rewrite fails
+    /// instead, so that such losses have to be resolved (or accepted) explicitly. The options of this builder
+    /// are opt-in, so their transforms are never considered lossy.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn lossless(mut self, enabled: bool) -> Self {
+        self.rewriter.lossless = enabled;
+        self
+    }
+
+    /// Mark the type named `name` with `x-kubernetes-preserve-unknown-fields`, rather than describing its structure
+    ///
+    /// This is meant for types with an implementation-defined representation, such as durations that are
+    /// serialized as `{ secs, nanos }` objects. Types are matched by the name of their definition when they
+    /// are referenced (`$ref`), and by their `title` otherwise. The description (and nullability) of the type
+    /// is kept. Can be called multiple times to add several types.
+    ///
+    /// `#[derive(CustomResource)]` inlines all subschemas, which don't have a `title` unless it is set with
+    /// `#[schemars(title = "...")]` on the type or on the field:
+    ///
+    /// ```
+    /// use kube_core::schema::StructuralSchemaRewriter;
+    /// use schemars::{generate::SchemaSettings, JsonSchema};
+    /// use serde_json::json;
+    ///
+    /// #[derive(JsonSchema)]
+    /// struct Spec {
+    ///     #[schemars(title = "Duration")]
+    ///     timeout: std::time::Duration,
+    /// }
+    ///
+    /// let rewriter = StructuralSchemaRewriter::builder().opaque_type("Duration").build();
+    /// let schema = SchemaSettings::openapi3()
+    ///     .with(|s| s.inline_subschemas = true)
+    ///     .with_transform(rewriter)
+    ///     .into_generator()
+    ///     .into_root_schema_for::<Spec>();
+    /// assert_eq!(
+    ///     schema.get("properties"),
+    ///     Some(&json!({ "timeout": { "x-kubernetes-preserve-unknown-fields": true } }))
+    /// );
+    /// ```
+    #[must_use]
+    pub fn opaque_type(mut self, name: impl Into<String>) -> Self {
+        self.rewriter.opaque_types.push(name.into());
+        self
+    }
+
+    /// Replace the single-property object at `pointer` with the schema of its property
+    ///
+    /// Some `#[serde(with)]` adapters serialize a value wrapped in an object with a single field, which is
+    /// redundant in a CRD that the adapter is changed for (or that is also written by other clients). The
+    /// description and nullability of the wrapper are kept. Pointers refer to the schema after references have
+    /// been inlined, and the rewrite fails with [`SchemaRewriteError::NotSingleProperty`] if the object at
+    /// `pointer` doesn't have exactly one property. Can be called multiple times to unwrap several objects.
+    #[must_use]
+    pub fn unwrap_single_property(mut self, pointer: impl Into<String>) -> Self {
+        self.rewriter.unwrapped_properties.push(pointer.into());
+        self
+    }
+
+    /// Keep all `examples` in the `x-examples` extension, rather than only the first one
+    ///
+    /// Kubernetes only supports a single `example`, so by default the first of the `examples` becomes the
+    /// `example`. With this option, all of them are kept for documentation tools that want them. Note that
+    /// [`SchemaSettings::openapi3`] already replaces `examples` with the first one before the rewriter runs,
+    /// so this only has an effect for other settings or for existing schemas.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn preserve_examples(mut self, enabled: bool) -> Self {
+        self.rewriter.preserve_examples = enabled;
+        self
+    }
+
+    /// Annotate the schemas of named types with the name of their Rust type, in `x-kube-rs-source-type`
+    ///
+    /// This helps to trace parts of a large schema back to the code that generated them. The names are taken
+    /// from the `title` of the root schema and from the names of the definitions that are inlined, so nested
+    /// types are only annotated if they are referenced (`$ref`) rather than inlined by the [`SchemaSettings`].
+    /// Kubernetes doesn't accept the annotations, so they must be removed with [`strip_source_types`] before
+    /// installing the CRD.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn source_types(mut self, enabled: bool) -> Self {
+        self.rewriter.source_types = enabled;
+        self
+    }
+
+    /// Validate the tags of open enums with a CEL rule that lists their known values
+    ///
+    /// Enums marked with `x-kube-rs-open-enum` drop the `enum` of their values (and of the tag property of
+    /// internally tagged enums), so that values added by later versions are accepted. With this option, the tag
+    /// property instead gets an `x-kubernetes-validations` rule that only accepts the known tags. Unlike an
+    /// `enum`, the rule can be removed from (or relaxed in) an installed CRD without changing its structure,
+    /// and it reports the known tags in its message.
+    ///
+    /// Kubernetes has no way to only warn about a failed rule, so this rejects unknown tags just like an `enum`
+    /// would, which closes the enum again until the rule is removed. Only enable it for enums that should
+    /// be closed for now. The tag of a `#[serde(other)]` catch-all variant can't be told apart from the other
+    /// tags in the schema, so it is accepted as a known tag as well.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn open_enum_tag_validations(mut self, enabled: bool) -> Self {
+        self.rewriter.open_enum_tag_validations = enabled;
+        self
+    }
+
+    /// Validate that the fields of internally tagged enums match their tag, with CEL rules
+    ///
+    /// Hoisting the variants of tagged enums merges the fields of all variants into a single object, whose
+    /// `oneOf` only checks that the required fields of some variant are set. With this option, the enum also
+    /// gets an `x-kubernetes-validations` rule for each variant, which requires the required fields of the
+    /// variant and rejects the fields of all other variants whenever the tag selects it. For example,
+    /// `self.type != 'A' || (has(self.a) && !has(self.b))`.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn tagged_enum_validations(mut self, enabled: bool) -> Self {
+        self.rewriter.tagged_enum_validations = enabled;
+        self
+    }
+
+    /// Copy the descriptions of enum variants to their properties, rather than moving them
+    ///
+    /// The description of a tagged enum variant with a single field is moved to the hoisted property of that
+    /// field, since it is the only place where Kubernetes keeps it. With this option, the description of
+    /// every variant with fields is also kept on the variant in the `oneOf`, as an `x-variant-description`
+    /// extension (Kubernetes rejects `description` within `oneOf`), for documentation tools that describe the
+    /// variants themselves. `JSONSchemaProps` doesn't model extensions, so CRDs generated by
+    /// `#[derive(CustomResource)]` don't keep it.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn copy_variant_descriptions(mut self, enabled: bool) -> Self {
+        self.rewriter.copy_variant_descriptions = enabled;
+        self
+    }
+
+    /// Emit the keywords of every schema in [`CANONICAL_KEYWORD_ORDER`]
+    ///
+    /// This starts with the `type`, followed by its `description`, constraints and structure, and ends with the
+    /// Kubernetes extensions, which makes generated CRDs easier to read and keeps their diffs stable across
+    /// versions of this crate. Keywords that aren't listed keep their order, after all listed ones. Keywords are
+    /// always sorted by name unless the `preserve-order` feature is enabled, so this has no effect without it.
+    ///
+    /// This also has no effect on CRDs generated by `#[derive(CustomResource)]`, since their schema is
+    /// converted into a `JSONSchemaProps`, which serializes its keywords in the order of its fields. It only
+    /// applies to schemas that are serialized as they are, such as the output of [`rewrite_schema_json`].
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn canonical_keyword_order(mut self, enabled: bool) -> Self {
+        self.rewriter.canonical_keyword_order = enabled;
+        self
+    }
+
+    /// Merge the variants of enums (in a `oneOf` or `anyOf`) into a single structural schema
+    ///
+    /// Kubernetes doesn't allow subschemas to define properties or enum values, so disabling this leaves
+    /// enums that the apiserver rejects, unless they are rewritten afterwards. It is only meant as an escape
+    /// hatch for schemas that are hoisted incorrectly.
+    ///
+    /// Defaults to `true`.
+    #[must_use]
+    pub fn hoist_enums(mut self, enabled: bool) -> Self {
+        self.rewriter.skip_hoist_enums = !enabled;
+        self
+    }
+
+    /// Remove `uniqueItems`, which Kubernetes doesn't support (it has `x-kubernetes-list-type: set` instead)
+    ///
+    /// Defaults to `true`.
+    #[must_use]
+    pub fn strip_unique_items(mut self, enabled: bool) -> Self {
+        self.rewriter.skip_strip_unique_items = !enabled;
+        self
+    }
+
+    /// Rewrite the `additionalProperties` of structs with `#[serde(flatten)]` maps to
+    /// `x-kubernetes-preserve-unknown-fields`, since Kubernetes doesn't allow them next to `properties`
+    ///
+    /// Defaults to `true`.
+    #[must_use]
+    pub fn preserve_unknown_flattened_maps(mut self, enabled: bool) -> Self {
+        self.rewriter.skip_preserve_unknown_flattened_maps = !enabled;
+        self
+    }
+
+    /// Rewrite `anyOf: [T, null]` (such as optional enums) to a nullable `T`
+    ///
+    /// Without this, the `null` variant is hoisted like any other variant of the `anyOf`.
+    ///
+    /// Defaults to `true`.
+    #[must_use]
+    pub fn remove_null_enum_variant(mut self, enabled: bool) -> Self {
+        self.rewriter.skip_remove_null_enum_variant = !enabled;
+        self
+    }
+
+    /// Inline all references to definitions (`$ref`), and remove the definitions (`$defs`, `definitions` and
+    /// `components/schemas`) once they aren't referenced anymore
+    ///
+    /// Kubernetes doesn't support references, so this is needed for schemas from generators that don't set
+    /// `inline_subschemas` (kube-derive does), and for [`rewrite_existing_schema`]. Without this, references
+    /// and definitions are kept as they are, and [`break_cycles`](Self::break_cycles) has no effect.
+    ///
+    /// Defaults to `true`.
+    #[must_use]
+    pub fn inline_references(mut self, enabled: bool) -> Self {
+        self.rewriter.skip_inline_references = !enabled;
+        self
+    }
+
+    /// Replace recursive references with `x-kubernetes-preserve-unknown-fields`
+    ///
+    /// Structural schemas can't express recursive types (such as a tree of `Box`ed nodes), so references to
+    /// definitions that are already being inlined are left as they are by default, which Kubernetes rejects.
+    /// With this option, the recursion point preserves unknown fields instead, which accepts the nested
+    /// values without validating them. Every broken cycle is reported in
+    /// [`StructuralSchemaRewriter::warnings`]. When the rewriter is a transform of a `SchemaGenerator`,
+    /// it can be found with `generator.transforms_mut()` and `downcast_ref::<StructuralSchemaRewriter>()`.
+    ///
+    /// Defaults to `false`.
+    #[must_use]
+    pub fn break_cycles(mut self, enabled: bool) -> Self {
+        self.rewriter.break_cycles = enabled;
+        self
+    }
+
+    /// Build the configured [`StructuralSchemaRewriter`]
+    #[must_use]
+    pub fn build(self) -> StructuralSchemaRewriter {
+        self.rewriter
+    }
+}
+
+/// A JSON Schema.
+#[allow(clippy::large_enum_variant)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, JsonSchema)]
+#[serde(untagged)]
+enum Schema {
+    /// A trivial boolean JSON Schema.
+    ///
+    /// The schema `true` matches everything (always passes validation), whereas the schema `false`
+    /// matches nothing (always fails validation).
+    Bool(bool),
+    /// A JSON Schema object.
+    Object(SchemaObject),
+}
+
+/// A JSON Schema object.
+///
+/// Deserializing and serializing a schema must not lose any information, since only the nodes touched by
+/// a transformation are expected to change. Keywords that are present but empty (such as `required: []`)
+/// are kept, and keywords that aren't modelled here are preserved through `extensions`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+struct SchemaObject {
+    /// Properties which annotate the [`SchemaObject`] which typically have no effect when an object is being validated against the schema.
+    #[serde(flatten, deserialize_with = "skip_if_default")]
+    metadata: Option<Box<Metadata>>,
+    /// The `type` keyword.
+    ///
+    /// See [JSON Schema Validation 6.1.1. "type"](https://tools.ietf.org/html/draft-handrews-json-schema-validation-02#section-6.1.1)
+    /// and [JSON Schema 4.2.1. Instance Data Model](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-4.2.1).
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    instance_type: Option<SingleOrVec<InstanceType>>,
+    /// The `format` keyword.
+    ///
+    /// See [JSON Schema Validation 7. A Vocabulary for Semantic Content With "format"](https://tools.ietf.org/html/draft-handrews-json-schema-validation-02#section-7).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+    /// The `enum` keyword.
+    ///
+    /// See [JSON Schema Validation 6.1.2. "enum"](https://tools.ietf.org/html/draft-handrews-json-schema-validation-02#section-6.1.2)
+    #[serde(rename = "enum", skip_serializing_if = "Option::is_none")]
+    enum_values: Option<Vec<Value>>,
+    /// Properties of the [`SchemaObject`] which define validation assertions in terms of other schemas.
+    #[serde(flatten, deserialize_with = "skip_if_default")]
+    subschemas: Option<Box<SubschemaValidation>>,
+    /// Properties of the [`SchemaObject`] which define validation assertions for arrays.
+    #[serde(flatten, deserialize_with = "skip_if_default")]
+    array: Option<Box<ArrayValidation>>,
+    /// Properties of the [`SchemaObject`] which define validation assertions for objects.
+    #[serde(flatten, deserialize_with = "skip_if_default")]
+    object: Option<Box<ObjectValidation>>,
+    /// Arbitrary extra properties which are not part of the JSON Schema specification, or which `schemars` does not support.
+    #[serde(flatten)]
+    extensions: BTreeMap<String, Value>,
+    /// Arbitrary data.
+    #[serde(flatten)]
+    other: Value,
+}
+
+impl SchemaObject {
+    /// Remove an extension from the schema
+    ///
+    /// Unknown keys are captured by both `extensions` and `other`, so they need to be removed from both.
+    fn remove_extension(&mut self, key: &str) -> Option<Value> {
+        if let Value::Object(other) = &mut self.other {
+            other.remove(key);
+        }
+        self.extensions.remove(key)
+    }
+
+    /// Set an extension of the schema, replacing any previous value
+    fn set_extension(&mut self, key: &str, value: Value) {
+        self.remove_extension(key);
+        self.extensions.insert(key.into(), value);
+    }
+
+    /// Append a CEL validation rule to the `x-kubernetes-validations` of the schema, after the existing ones
+    fn add_validation(&mut self, rule: impl Into<Rule>) {
+        let Ok(rule) = serde_json::to_value(rule.into()) else {
+            return;
+        };
+        let mut rules = match self.remove_extension("x-kubernetes-validations") {
+            Some(Value::Array(rules)) => rules,
+            _ => Vec::new(),
+        };
+        rules.push(rule);
+        self.set_extension("x-kubernetes-validations", Value::Array(rules));
+    }
+}
+
+// Deserializing "null" to `Option<Value>` directly results in `None`,
+// this function instead makes it deserialize to `Some(Value::Null)`.
+fn allow_null<'de, D>(de: D) -> Result<Option<Value>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Value::deserialize(de).map(Option::Some)
+}
+
+fn skip_if_default<'de, D, T>(deserializer: D) -> Result<Option<Box<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de> + Default + PartialEq,
+{
+    let value = T::deserialize(deserializer)?;
+    if value == T::default() {
+        Ok(None)
+    } else {
+        Ok(Some(Box::new(value)))
+    }
+}
+
+/// Properties which annotate a [`SchemaObject`] which typically have no effect when an object is being validated against the schema.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+struct Metadata {
+    /// The `description` keyword.
+    ///
+    /// See [JSON Schema Validation 9.1. "title" and "description"](https://tools.ietf.org/html/draft-handrews-json-schema-validation-02#section-9.1).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// The `default` keyword.
+    ///
+    /// See [JSON Schema Validation 9.2. "default"](https://tools.ietf.org/html/draft-handrews-json-schema-validation-02#section-9.2).
+    #[serde(skip_serializing_if = "Option::is_none", deserialize_with = "allow_null")]
+    default: Option<Value>,
+    /// The `deprecated` keyword.
+    ///
+    /// See [JSON Schema Validation 9.3. "deprecated"](https://json-schema.org/draft/2020-12/json-schema-validation#section-9.3).
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    deprecated: bool,
+}
+
+/// Properties of a [`SchemaObject`] which define validation assertions in terms of other schemas.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+struct SubschemaValidation {
+    /// The `allOf` keyword.
+    ///
+    /// See [JSON Schema 9.2.1.1. "allOf"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.2.1.1).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    all_of: Option<Vec<Schema>>,
+    /// The `anyOf` keyword.
+    ///
+    /// See [JSON Schema 9.2.1.2. "anyOf"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.2.1.2).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    any_of: Option<Vec<Schema>>,
+    /// The `oneOf` keyword.
+    ///
+    /// See [JSON Schema 9.2.1.3. "oneOf"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.2.1.3).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    one_of: Option<Vec<Schema>>,
+}
+
+/// Properties of a [`SchemaObject`] which define validation assertions for arrays.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+struct ArrayValidation {
+    /// The `items` keyword.
+    ///
+    /// See [JSON Schema 9.3.1.1. "items"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.3.1.1).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    items: Option<SingleOrVec<Schema>>,
+    /// The `additionalItems` keyword.
+    ///
+    /// See [JSON Schema 9.3.1.2. "additionalItems"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.3.1.2).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    additional_items: Option<Box<Schema>>,
+    /// The `maxItems` keyword.
+    ///
+    /// See [JSON Schema Validation 6.4.1. "maxItems"](https://tools.ietf.org/html/draft-handrews-json-schema-validation-02#section-6.4.1).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_items: Option<u64>,
+    /// The `minItems` keyword.
+    ///
+    /// See [JSON Schema Validation 6.4.2. "minItems"](https://tools.ietf.org/html/draft-handrews-json-schema-validation-02#section-6.4.2).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_items: Option<u64>,
+    /// The `uniqueItems` keyword.
+    ///
+    /// See [JSON Schema Validation 6.4.3. "uniqueItems"](https://tools.ietf.org/html/draft-handrews-json-schema-validation-02#section-6.4.3).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unique_items: Option<bool>,
+    /// The `contains` keyword.
+    ///
+    /// See [JSON Schema 9.3.1.4. "contains"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.3.1.4).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    contains: Option<Box<Schema>>,
+    /// The `minContains` keyword.
+    ///
+    /// See [JSON Schema Validation 6.4.5. "minContains"](https://json-schema.org/draft/2020-12/json-schema-validation#section-6.4.5).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_contains: Option<u64>,
+    /// The `maxContains` keyword.
+    ///
+    /// See [JSON Schema Validation 6.4.4. "maxContains"](https://json-schema.org/draft/2020-12/json-schema-validation#section-6.4.4).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_contains: Option<u64>,
+}
+
+/// Properties of a [`SchemaObject`] which define validation assertions for objects.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default, JsonSchema)]
+#[serde(rename_all = "camelCase", default)]
+struct ObjectValidation {
+    /// The `maxProperties` keyword.
+    ///
+    /// See [JSON Schema Validation 6.5.1. "maxProperties"](https://tools.ietf.org/html/draft-handrews-json-schema-validation-02#section-6.5.1).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_properties: Option<u64>,
+    /// The `minProperties` keyword.
+    ///
+    /// See [JSON Schema Validation 6.5.2. "minProperties"](https://tools.ietf.org/html/draft-handrews-json-schema-validation-02#section-6.5.2).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    min_properties: Option<u64>,
+    /// The `required` keyword.
+    ///
+    /// See [JSON Schema Validation 6.5.3. "required"](https://tools.ietf.org/html/draft-handrews-json-schema-validation-02#section-6.5.3).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    required: Option<BTreeSet<String>>,
+    /// The `properties` keyword.
+    ///
+    /// See [JSON Schema 9.3.2.1. "properties"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.3.2.1).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    properties: Option<PropertyMap<Schema>>,
+    /// The `patternProperties` keyword.
+    ///
+    /// See [JSON Schema 9.3.2.2. "patternProperties"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.3.2.2).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pattern_properties: Option<PropertyMap<Schema>>,
+    /// The `additionalProperties` keyword.
+    ///
+    /// See [JSON Schema 9.3.2.3. "additionalProperties"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.3.2.3).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    additional_properties: Option<Box<Schema>>,
+    /// The `propertyNames` keyword.
+    ///
+    /// See [JSON Schema 9.3.2.5. "propertyNames"](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-9.3.2.5).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    property_names: Option<Box<Schema>>,
+}
+
+impl ObjectValidation {
+    /// Whether the object defines at least one property
+    fn has_properties(&self) -> bool {
+        self.properties
+            .as_ref()
+            .is_some_and(|properties| !properties.is_empty())
+    }
+
+    /// Whether the property called `name` is required
+    fn is_required(&self, name: &str) -> bool {
+        self.required
+            .as_ref()
+            .is_some_and(|required| required.contains(name))
+    }
+}
+
+/// The possible types of values in JSON Schema documents.
+///
+/// See [JSON Schema 4.2.1. Instance Data Model](https://tools.ietf.org/html/draft-handrews-json-schema-02#section-4.2.1).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+enum InstanceType {
+    /// Represents the JSON null type.
+    Null,
+    /// Represents the JSON boolean type.
+    Boolean,
+    /// Represents the JSON object type.
+    Object,
+    /// Represents the JSON array type.
+    Array,
+    /// Represents the JSON number type (floating point).
+    Number,
+    /// Represents the JSON string type.
+    String,
+    /// Represents the JSON integer type.
+    Integer,
+}
+
+impl InstanceType {
+    /// The name of the type, as used by the `type` keyword
+    fn name(self) -> &'static str {
+        match self {
+            InstanceType::Null => "null",
+            InstanceType::Boolean => "boolean",
+            InstanceType::Object => "object",
+            InstanceType::Array => "array",
+            InstanceType::Number => "number",
+            InstanceType::String => "string",
+            InstanceType::Integer => "integer",
+        }
+    }
+}
+
+/// A type which can be serialized as a single item, or multiple items.
+///
+/// In some contexts, a `Single` may be semantically distinct from a `Vec` containing only item.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, JsonSchema)]
+#[serde(untagged)]
+enum SingleOrVec<T> {
+    /// Represents a single item.
+    Single(Box<T>),
+    /// Represents a vector of items.
+    Vec(Vec<T>),
+}
+
+impl StructuralSchemaRewriter {
+    /// Create a [`StructuralSchemaRewriterBuilder`] to configure the optional transformations
+    pub fn builder() -> StructuralSchemaRewriterBuilder {
+        StructuralSchemaRewriterBuilder::default()
+    }
+
+    /// Apply the rewrite one transform at a time, returning a snapshot of the schema after each of them
+    ///
+    /// Every transform is applied to the whole schema before moving on to the next one. The returned
+    /// snapshots are labelled with the name of the transform that produced them, which makes it easy to
+    /// track down which transform is responsible for an unexpected change.
+    ///
+    /// Once all steps have been applied, `schema` is equal to the output of [`Transform::transform`].
+    /// If `schema` is not a valid JSON Schema, or one of the transforms fails, the error is returned and
+    /// `schema` is left untouched.
+    pub fn transform_steps(
+        &mut self,
+        schema: &mut Value,
+    ) -> Result<Vec<(String, Value)>, SchemaRewriteError> {
+        let mut rewritten = schemars::Schema::try_from(schema.clone())
+            .map_err(|source| SchemaRewriteError::InvalidSchema {
+                pointer: String::new(),
+                source,
+            })?
+            .to_value();
+        let mut warnings = Vec::new();
+        let mut steps = Vec::new();
+        for step in self.steps() {
+            self.apply_steps(&[step], &mut rewritten, &mut warnings, None)?;
+            steps.push((step.name().to_string(), rewritten.clone()));
+        }
+        self.warnings = warnings;
+        *schema = rewritten;
+        Ok(steps)
+    }
+
+    /// The warnings of the last rewrite, such as enums whose variants have different types (which are not
+    /// validated) or the cycles broken by [`StructuralSchemaRewriterBuilder::break_cycles`]
+    ///
+    /// ```
+    /// use kube_core::schema::StructuralSchemaRewriter;
+    /// use serde_json::json;
+    ///
+    /// let mut rewriter = StructuralSchemaRewriter::builder().break_cycles(true).build();
+    /// let mut schema = schemars::Schema::try_from(json!({
+    ///     "$defs": {
+    ///         "List": {
+    ///             "type": "object",
+    ///             "properties": { "next": { "$ref": "#/$defs/List" } },
+    ///         },
+    ///     },
+    ///     "$ref": "#/$defs/List",
+    /// }))?;
+    /// rewriter.try_transform(&mut schema)?;
+    /// assert_eq!(
+    ///     schema.to_value(),
+    ///     json!({
+    ///         "type": "object",
+    ///         "properties": { "next": { "x-kubernetes-preserve-unknown-fields": true } },
+    ///     })
+    /// );
+    /// assert_eq!(rewriter.warnings(), [
+    ///     "the recursive reference to \"#/$defs/List\" at \"/properties/next\" was replaced by \
+    ///      `x-kubernetes-preserve-unknown-fields`"
+    /// ]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// The [`Step`]s enabled for this rewriter, in order
+    fn steps(&self) -> Vec<Step> {
+        Step::ALL.into_iter().filter(|step| step.enabled(self)).collect()
+    }
+
+    /// Apply all enabled [`Step`]s to `schema`
+    fn rewrite(&self, schema: &mut Value) -> Result<(), SchemaRewriteError> {
+        self.apply_steps(&self.steps(), schema, &mut Vec::new(), None)
+    }
+
+    /// Apply `steps` to `schema` and every subschema within it, bottom-up, adding any warnings to `warnings`
+    ///
+    /// If set, `trace` is called after each step, see [`Trace`].
+    fn apply_steps(
+        &self,
+        steps: &[Step],
+        schema: &mut Value,
+        warnings: &mut Vec<String>,
+        mut trace: Option<Trace<'_>>,
+    ) -> Result<(), SchemaRewriteError> {
+        apply_to_root(Step::InlineReferences, steps, schema, &mut trace, |schema| {
+            inline_references(schema, self, warnings)
+        })?;
+        for_each_subschema_mut(schema, "", &mut |pointer, subschema| {
+            if subschema.is_boolean() {
+                return Ok(());
+            }
+            let mut node = serde_json::from_value::<SchemaObject>(subschema.clone()).map_err(|source| {
+                SchemaRewriteError::InvalidSchema {
+                    pointer: pointer.to_string(),
+                    source,
+                }
+            })?;
+            for step in steps {
+                if self.lossless {
+                    if let Some(detail) = step.loss(&node, self) {
+                        return Err(SchemaRewriteError::LossyTransform {
+                            path: pointer.to_string(),
+                            detail,
+                        });
+                    }
+                }
+                let before = trace.is_some().then(|| serde_json::to_value(&node));
+                step.apply(&mut node, pointer, self, warnings)?;
+                if let (Some(trace), Some(Ok(before))) = (trace.as_mut(), before) {
+                    if let Ok(after) = serde_json::to_value(&node) {
+                        trace(*step, pointer, &before, &after);
+                    }
+                }
+            }
+            *subschema = serde_json::to_value(node).map_err(|source| SchemaRewriteError::InvalidSchema {
+                pointer: pointer.to_string(),
+                source,
+            })?;
+            Ok(())
+        })?;
+        apply_to_root(Step::StripMarkers, steps, schema, &mut trace, |schema| {
+            strip_markers(schema, self.source_types);
+            Ok(())
+        })?;
+        apply_to_root(Step::CanonicalKeywordOrder, steps, schema, &mut trace, |schema| {
+            order_keywords(schema);
+            Ok(())
+        })
+    }
+}
+
+/// Callback of [`StructuralSchemaRewriter::apply_steps`], which is called with each applied step, the pointer
+/// to the (sub)schema that it was applied to, and that schema before and after the step
+type Trace<'a> = &'a mut dyn FnMut(Step, &str, &Value, &Value);
+
+/// Apply `step` to the whole `schema` with `apply`, if it is one of `steps`
+fn apply_to_root(
+    step: Step,
+    steps: &[Step],
+    schema: &mut Value,
+    trace: &mut Option<Trace<'_>>,
+    apply: impl FnOnce(&mut Value) -> Result<(), SchemaRewriteError>,
+) -> Result<(), SchemaRewriteError> {
+    if !steps.contains(&step) {
+        return Ok(());
+    }
+    let before = trace.is_some().then(|| schema.clone());
+    apply(schema)?;
+    if let (Some(trace), Some(before)) = (trace, before) {
+        trace(step, "", &before, schema);
+    }
+    Ok(())
+}
+
+/// Apply [`StructuralSchemaRewriter`] to an existing schema
+///
+/// This is useful for hand-written or legacy `openAPIV3Schema`s that were not generated by `schemars`,
+/// but still need the same structural fixups as derived schemas (for example hoisting `oneOf` enums).
+///
+/// ```
+/// use kube_core::schema::rewrite_existing_schema;
+/// use serde_json::json;
+///
+/// let schema = json!({
+///     "oneOf": [
+///         { "type": "string", "enum": ["A"], "description": "The A variant" },
+///         { "type": "string", "enum": ["B"], "description": "The B variant" },
+///     ],
+/// });
+/// assert_eq!(
+///     rewrite_existing_schema(schema)?,
+///     json!({ "type": "string", "enum": ["A", "B"] })
+/// );
+/// # Ok::<(), kube_core::schema::SchemaRewriteError>(())
+/// ```
+pub fn rewrite_existing_schema(schema: Value) -> Result<Value, SchemaRewriteError> {
+    let mut schema = schemars::Schema::try_from(schema)
+        .map_err(|source| SchemaRewriteError::InvalidSchema {
+            pointer: String::new(),
+            source,
+        })?
+        .to_value();
+    StructuralSchemaRewriter::default().rewrite(&mut schema)?;
+    Ok(schema)
+}
+
+/// Generate both a plain JSON Schema and the structural schema of a CRD for `T`
+///
+/// The first schema is generated with the default (JSON Schema 2020-12) settings of `schemars`, keeping
+/// references to `$defs` and junctors such as `oneOf`, which makes it suitable for validating instances in tools
+/// that aren't aware of Kubernetes. The second schema is generated the same way as by
+/// `#[derive(CustomResource)]`, with everything inlined and rewritten by [`StructuralSchemaRewriter`], and is
+/// meant for the `openAPIV3Schema` of the CRD. Both describe the same values, but the structural schema can
+/// validate them less precisely (see [`StructuralSchemaRewriterBuilder::lossless`]).
+///
+/// ```
+/// use kube_core::schema::dual_schemas;
+/// use schemars::JsonSchema;
+///
+/// #[derive(JsonSchema)]
+/// struct Spec {
+///     replicas: u32,
+/// }
+///
+/// let (plain, structural) = dual_schemas::<Spec>();
+/// assert_eq!(plain["$schema"], "https://json-schema.org/draft/2020-12/schema");
+/// assert_eq!(structural.get("$schema"), None);
+/// assert_eq!(plain["properties"]["replicas"]["type"], structural["properties"]["replicas"]["type"]);
+/// ```
+pub fn dual_schemas<T: JsonSchema>() -> (Value, Value) {
+    let plain = SchemaSettings::default()
+        .into_generator()
+        .into_root_schema_for::<T>()
+        .to_value();
+    let structural = SchemaSettings::openapi3()
+        .with(|s| {
+            s.inline_subschemas = true;
+            s.meta_schema = None;
+        })
+        .with_transform(schemars::transform::AddNullable::default())
+        .with_transform(StructuralSchemaRewriter::default())
+        .into_generator()
+        .into_root_schema_for::<T>()
+        .to_value();
+    (plain, structural)
+}
+
+/// Apply [`rewrite_existing_schema`] to a schema serialized as JSON, returning the rewritten schema as JSON
+///
+/// This only depends on JSON strings, which makes it easy to expose from environments such as WebAssembly.
+/// All failures (including invalid JSON) are returned as error messages.
+///
+/// ```
+/// use kube_core::schema::rewrite_schema_json;
+///
+/// let schema = r#"{ "type": ["integer", "string"] }"#;
+/// assert_eq!(
+///     rewrite_schema_json(schema).as_deref(),
+///     Ok(r#"{"x-kubernetes-int-or-string":true}"#)
+/// );
+/// assert!(rewrite_schema_json("not json").is_err());
+/// ```
+pub fn rewrite_schema_json(schema: &str) -> Result<String, String> {
+    let schema = serde_json::from_str(schema).map_err(|err| format!("invalid JSON: {err}"))?;
+    let rewritten = rewrite_existing_schema(schema).map_err(|err| err.to_string())?;
+    serde_json::to_string(&rewritten).map_err(|err| err.to_string())
+}
+
+/// Convert a schema into the [`JSONSchemaProps`] of a [`CustomResourceDefinition`]
+///
+/// `JSONSchemaProps` lists the `required` properties as a `Vec`, so they are sorted (and deduplicated) at
+/// every level of the schema, keeping the conversion deterministic regardless of how the schema was built.
+///
+/// ```
+/// use kube_core::schema::to_json_schema_props;
+/// use serde_json::json;
+///
+/// let schema = json!({
+///     "type": "object",
+///     "properties": { "b": { "type": "string" }, "a": { "type": "string" } },
+///     "required": ["b", "a"],
+/// });
+/// let props = to_json_schema_props(&schema)?;
+/// assert_eq!(props.required, Some(vec!["a".to_string(), "b".to_string()]));
+/// # Ok::<(), kube_core::schema::SchemaRewriteError>(())
+/// ```
+///
+/// [`CustomResourceDefinition`]: `k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition`
+pub fn to_json_schema_props(schema: &Value) -> Result<JSONSchemaProps, SchemaRewriteError> {
+    let mut schema = schema.clone();
+    sort_required(&mut schema);
+    serde_json::from_value(schema).map_err(|source| SchemaRewriteError::InvalidSchema {
+        pointer: String::new(),
+        source,
+    })
+}
+
+fn sort_required(schema: &mut Value) {
+    if let Some(Value::Array(required)) = schema.get_mut("required") {
+        required.sort_by(|a, b| a.as_str().cmp(&b.as_str()));
+        required.dedup();
+    }
+    for (_, subschema) in subschemas_mut(schema) {
+        sort_required(subschema);
+    }
+}
+
+/// A schema that accepts any object, for custom resources that don't have a schema at all
+///
+/// This can be used as-is as the `openAPIV3Schema` of a CRD version. To keep the description of a type, use
+/// [`StructuralSchemaRewriterBuilder::preserve_unknown_root`] instead.
+///
+/// ```
+/// use kube_core::schema::dynamic_root_schema;
+///
+/// assert_eq!(
+///     dynamic_root_schema(),
+///     serde_json::json!({ "type": "object", "x-kubernetes-preserve-unknown-fields": true })
+/// );
+/// ```
+pub fn dynamic_root_schema() -> Value {
+    serde_json::json!({
+        "type": "object",
+        "x-kubernetes-preserve-unknown-fields": true,
+    })
+}
+
+/// Mark the subschema at `pointer` with `x-kubernetes-preserve-unknown-fields`, dropping its structure
+///
+/// This is useful for subtrees that shouldn't be validated (or pruned) strictly by the apiserver, such as the
+/// `status` of a custom resource whose operator doesn't want to model it in full. The subschema keeps its
+/// description (and nullability), but all of its properties are removed. Only objects (or subschemas without
+/// a `type`, which become objects) can preserve unknown fields, other types are rejected with
+/// [`SchemaRewriteError::NotAnObject`].
+///
+/// ```
+/// use kube_core::schema::preserve_unknown_fields_at;
+/// use serde_json::json;
+///
+/// let mut schema = json!({
+///     "type": "object",
+///     "properties": {
+///         "status": {
+///             "type": "object",
+///             "properties": { "ready": { "type": "boolean" } },
+///         },
+///     },
+/// });
+/// preserve_unknown_fields_at(&mut schema, "/properties/status")?;
+/// assert_eq!(
+///     schema["properties"]["status"],
+///     json!({ "type": "object", "x-kubernetes-preserve-unknown-fields": true })
+/// );
+/// # Ok::<(), kube_core::schema::SchemaRewriteError>(())
+/// ```
+pub fn preserve_unknown_fields_at(schema: &mut Value, pointer: &str) -> Result<(), SchemaRewriteError> {
+    let subschema = schema
+        .pointer_mut(pointer)
+        .ok_or_else(|| SchemaRewriteError::PointerNotFound {
+            pointer: pointer.into(),
+        })?;
+    let mut node = serde_json::from_value::<SchemaObject>(subschema.clone()).map_err(|source| {
+        SchemaRewriteError::InvalidSchema {
+            pointer: pointer.into(),
+            source,
+        }
+    })?;
+    let instance_type = match node.instance_type.take() {
+        None => SingleOrVec::Single(Box::new(InstanceType::Object)),
+        Some(SingleOrVec::Single(tpe)) if *tpe == InstanceType::Object => SingleOrVec::Single(tpe),
+        // Nullable objects, see `StructuralSchemaRewriterBuilder::nullable_type_arrays`
+        Some(SingleOrVec::Vec(types))
+            if types.contains(&InstanceType::Object)
+                && types
+                    .iter()
+                    .all(|t| matches!(t, InstanceType::Object | InstanceType::Null)) =>
+        {
+            SingleOrVec::Vec(types)
+        }
+        Some(other) => {
+            let types = match other {
+                SingleOrVec::Single(tpe) => vec![tpe.name().to_string()],
+                SingleOrVec::Vec(types) => types.iter().map(|t| t.name().to_string()).collect(),
+            };
+            return Err(SchemaRewriteError::NotAnObject {
+                path: pointer.into(),
+                types,
+            });
+        }
+    };
+    preserve_unknown_fields(&mut node);
+    node.instance_type = Some(instance_type);
+    *subschema = serde_json::to_value(node).map_err(|source| SchemaRewriteError::InvalidSchema {
+        pointer: pointer.into(),
+        source,
+    })?;
+    Ok(())
+}
+
+/// Remove every `default` from the subschema at `pointer` and all subschemas within it
+///
+/// The `status` of a custom resource is owned by its controller, so defaults in it (such as from
+/// `#[serde(default)]` fields of the status type) would only hide fields that the controller didn't set.
+/// This removes them from the status, while the rest of the schema keeps its defaults.
+///
+/// ```
+/// use kube_core::schema::strip_defaults_at;
+/// use serde_json::json;
+///
+/// let mut schema = json!({
+///     "type": "object",
+///     "properties": {
+///         "status": {
+///             "type": "object",
+///             "properties": { "replicas": { "type": "integer", "default": 0 } },
+///         },
+///     },
+/// });
+/// strip_defaults_at(&mut schema, "/properties/status")?;
+/// assert_eq!(
+///     schema["properties"]["status"]["properties"]["replicas"],
+///     json!({ "type": "integer" })
+/// );
+/// # Ok::<(), kube_core::schema::SchemaRewriteError>(())
+/// ```
+pub fn strip_defaults_at(schema: &mut Value, pointer: &str) -> Result<(), SchemaRewriteError> {
+    let subschema = schema
+        .pointer_mut(pointer)
+        .ok_or_else(|| SchemaRewriteError::PointerNotFound {
+            pointer: pointer.into(),
+        })?;
+    strip_defaults(subschema);
+    Ok(())
+}
+
+fn strip_defaults(schema: &mut Value) {
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("default");
+    }
+    for (_, subschema) in subschemas_mut(schema) {
+        strip_defaults(subschema);
+    }
+}
+
+/// Rename properties of the schema, without changing the Rust types that it was generated from
+///
+/// `renames` maps the JSON pointers of properties (such as `/properties/spec/properties/replicas`) to their new
+/// names. Pointers refer to the original names, even when renaming a property along with some of its nested
+/// properties. All properties of an object are renamed at once, so names can also be swapped.
+///
+/// Besides `properties`, the names are updated in `required` (including the `required` of the variants in
+/// `oneOf`, `anyOf` and `allOf`), in the `x-kubernetes-list-map-keys` of the list that the object is an item
+/// of, and in the `self.<field>` (and `oldSelf.<field>`) references of the object's CEL rules. The schema is
+/// left untouched if any pointer doesn't refer to a property, or if a new name is already used by another
+/// property.
+///
+/// ```
+/// use kube_core::schema::rename_properties;
+/// use serde_json::json;
+/// use std::collections::BTreeMap;
+///
+/// let mut schema = json!({
+///     "type": "object",
+///     "properties": { "replicaCount": { "type": "integer" } },
+///     "required": ["replicaCount"],
+///     "x-kubernetes-validations": [{ "rule": "self.replicaCount >= 0" }],
+/// });
+/// let renames = BTreeMap::from([("/properties/replicaCount".to_string(), "replicas".to_string())]);
+/// rename_properties(&mut schema, &renames)?;
+/// assert_eq!(
+///     schema,
+///     json!({
+///         "type": "object",
+///         "properties": { "replicas": { "type": "integer" } },
+///         "required": ["replicas"],
+///         "x-kubernetes-validations": [{ "rule": "self.replicas >= 0" }],
+///     })
+/// );
+/// # Ok::<(), kube_core::schema::SchemaRewriteError>(())
+/// ```
+pub fn rename_properties(
+    schema: &mut Value,
+    renames: &BTreeMap<String, String>,
+) -> Result<(), SchemaRewriteError> {
+    // Group the renames by the object that defines the properties, mapping old names to new ones
+    let mut by_parent = BTreeMap::<&str, BTreeMap<String, (&str, &str)>>::new();
+    for (pointer, new_name) in renames {
+        let not_found = || SchemaRewriteError::PointerNotFound {
+            pointer: pointer.clone(),
+        };
+        let (properties_pointer, segment) = pointer.rsplit_once('/').ok_or_else(not_found)?;
+        let parent = properties_pointer
+            .strip_suffix("/properties")
+            .ok_or_else(not_found)?;
+        let name = segment.replace("~1", "/").replace("~0", "~");
+        by_parent
+            .entry(parent)
+            .or_default()
+            .insert(name, (pointer.as_str(), new_name.as_str()));
+    }
+    let mut renamed = schema.clone();
+    // Nested objects sort after their parents, so renaming their properties first keeps the other pointers
+    // valid
+    for (parent, names) in by_parent.iter().rev() {
+        rename_sibling_properties(&mut renamed, parent, names)?;
+    }
+    *schema = renamed;
+    Ok(())
+}
+
+/// Remove a property from the schema, such as an internal field that shouldn't be published
+///
+/// `pointer` is the JSON pointer of the property (such as `/properties/spec/properties/debug`), which is
+/// removed from both `properties` and `required`. Fails if the pointer doesn't refer to a property.
+///
+/// The property is also removed from the `required` of the variants in `oneOf`, `anyOf` and `allOf`, such as
+/// the variants of hoisted enums. Variants that are left empty are removed, since they were selected by the
+/// removed property, and would accept any value otherwise.
+///
+/// ```
+/// use kube_core::schema::remove_property;
+/// use serde_json::json;
+///
+/// let mut schema = json!({
+///     "type": "object",
+///     "properties": {
+///         "replicas": { "type": "integer" },
+///         "debug": { "type": "boolean" },
+///     },
+///     "required": ["debug", "replicas"],
+/// });
+/// remove_property(&mut schema, "/properties/debug")?;
+/// assert_eq!(
+///     schema,
+///     json!({
+///         "type": "object",
+///         "properties": { "replicas": { "type": "integer" } },
+///         "required": ["replicas"],
+///     })
+/// );
+/// # Ok::<(), kube_core::schema::SchemaRewriteError>(())
+/// ```
+pub fn remove_property(schema: &mut Value, pointer: &str) -> Result<(), SchemaRewriteError> {
+    let (node, _, name) = property_parent(schema, pointer)?;
+    if let Some(Value::Object(properties)) = node.get_mut("properties") {
+        properties.remove(&name);
+    }
+    remove_required(node, &name);
+    for keyword in ["oneOf", "anyOf", "allOf"] {
+        let Some(Value::Array(variants)) = node.get_mut(keyword) else {
+            continue;
+        };
+        variants.retain_mut(|variant| match variant {
+            Value::Object(variant) => {
+                let required = variant.contains_key("required");
+                remove_required(variant, &name);
+                !required || !variant.is_empty()
+            }
+            _ => true,
+        });
+        if variants.is_empty() {
+            node.remove(keyword);
+        }
+    }
+    Ok(())
+}
+
+/// Remove `name` from the `required` properties of `schema`, along with `required` itself if it becomes empty
+fn remove_required(schema: &mut serde_json::Map<String, Value>, name: &str) {
+    if let Some(Value::Array(required)) = schema.get_mut("required") {
+        required.retain(|required| *required != *name);
+        if required.is_empty() {
+            schema.remove("required");
+        }
+    }
+}
+
+/// Resolve the JSON pointer of a property to the schema that defines it (along with its pointer), and the name
+/// of the property
+fn property_parent<'a, 'p>(
+    schema: &'a mut Value,
+    pointer: &'p str,
+) -> Result<(&'a mut serde_json::Map<String, Value>, &'p str, String), SchemaRewriteError> {
+    let not_found = || SchemaRewriteError::PointerNotFound {
+        pointer: pointer.into(),
+    };
+    let (properties_pointer, segment) = pointer.rsplit_once('/').ok_or_else(not_found)?;
+    let parent = properties_pointer
+        .strip_suffix("/properties")
+        .ok_or_else(not_found)?;
+    let name = segment.replace("~1", "/").replace("~0", "~");
+    let Some(Value::Object(node)) = schema.pointer_mut(parent) else {
+        return Err(not_found());
+    };
+    match node.get("properties") {
+        Some(Value::Object(properties)) if properties.contains_key(&name) => Ok((node, parent, name)),
+        _ => Err(not_found()),
+    }
+}
+
+/// Rename properties of the object at `parent` at once, `names` maps their old names to their pointers and
+/// new names
+fn rename_sibling_properties(
+    schema: &mut Value,
+    parent: &str,
+    names: &BTreeMap<String, (&str, &str)>,
+) -> Result<(), SchemaRewriteError> {
+    let not_found = |pointer: &str| SchemaRewriteError::PointerNotFound {
+        pointer: pointer.into(),
+    };
+    let Some(Value::Object(node)) = schema.pointer_mut(parent) else {
+        let (pointer, _) = names.values().next().copied().unwrap_or_default();
+        return Err(not_found(pointer));
+    };
+    let Some(Value::Object(properties)) = node.get_mut("properties") else {
+        let (pointer, _) = names.values().next().copied().unwrap_or_default();
+        return Err(not_found(pointer));
+    };
+    let mut new_names = BTreeSet::new();
+    for (name, (pointer, new_name)) in names {
+        if !properties.contains_key(name) {
+            return Err(not_found(pointer));
+        }
+        // Names of properties that are renamed as well become available
+        let taken = properties.contains_key(*new_name) && !names.contains_key(*new_name);
+        if taken || !new_names.insert(*new_name) {
+            return Err(SchemaRewriteError::PropertyExists {
+                path: format!("{parent}/properties/{}", escape_pointer(new_name)),
+            });
+        }
+    }
+    let rename = |name: &str| match names.get(name) {
+        Some((_, new_name)) => new_name.to_string(),
+        None => name.to_string(),
+    };
+    // Rebuild the map to keep the position of the properties
+    *properties = std::mem::take(properties)
+        .into_iter()
+        .map(|(key, value)| (rename(&key), value))
+        .collect();
+
+    let required_lists = node
+        .iter_mut()
+        .flat_map(|(keyword, value)| match (keyword.as_str(), value) {
+            ("required", required) => vec![required],
+            ("oneOf" | "anyOf" | "allOf", Value::Array(variants)) => variants
+                .iter_mut()
+                .filter_map(|variant| variant.get_mut("required"))
+                .collect(),
+            _ => Vec::new(),
+        });
+    for required in required_lists {
+        if let Value::Array(required) = required {
+            for name in required.iter_mut() {
+                if let Value::String(name) = name {
+                    *name = rename(name);
+                }
+            }
+        }
+    }
+
+    let fields = names
+        .iter()
+        .filter_map(|(name, (_, new_name))| Some((cel_field(name)?, cel_field(new_name)?)))
+        .collect::<BTreeMap<_, _>>();
+    if let Some(Value::Array(rules)) = node.get_mut("x-kubernetes-validations") {
+        for rule in rules.iter_mut().filter_map(Value::as_object_mut) {
+            for key in ["rule", "messageExpression"] {
+                if let Some(Value::String(expression)) = rule.get_mut(key) {
+                    *expression = rename_cel_fields(expression, &fields);
+                }
+            }
+        }
+    }
+
+    if let Some(array) = parent.strip_suffix("/items") {
+        if let Some(Value::Array(keys)) = schema
+            .pointer_mut(array)
+            .and_then(|array| array.get_mut("x-kubernetes-list-map-keys"))
+        {
+            for key in keys.iter_mut() {
+                if let Value::String(key) = key {
+                    *key = rename(key);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replace the fields of `self` and `oldSelf` in a CEL `expression` according to `fields`, all at once
+///
+/// String literals are copied as they are.
+fn rename_cel_fields(expression: &str, fields: &BTreeMap<String, String>) -> String {
+    let identifier_end = |start: usize| {
+        expression[start..]
+            .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .map_or(expression.len(), |end| start + end)
+    };
+    let bytes = expression.as_bytes();
+    let mut renamed = String::with_capacity(expression.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"') => {
+                let mut end = i + 1;
+                while end < bytes.len() && bytes[end] != quote {
+                    end += if bytes[end] == b'\\' { 2 } else { 1 };
+                }
+                let end = (end + 1).min(bytes.len());
+                renamed.push_str(&expression[i..end]);
+                i = end;
+            }
+            b if b.is_ascii_alphanumeric() || b == b'_' => {
+                let end = identifier_end(i);
+                let word = &expression[i..end];
+                renamed.push_str(word);
+                let is_receiver = matches!(word, "self" | "oldSelf") && (i == 0 || bytes[i - 1] != b'.');
+                i = end;
+                if is_receiver && bytes.get(i) == Some(&b'.') {
+                    let end = identifier_end(i + 1);
+                    let field = &expression[i + 1..end];
+                    renamed.push('.');
+                    renamed.push_str(fields.get(field).map_or(field, String::as_str));
+                    i = end;
+                }
+            }
+            _ => {
+                let c = expression[i..].chars().next().unwrap_or_default();
+                renamed.push(c);
+                i += c.len_utf8();
+            }
+        }
+    }
+    renamed
+}
+
+/// Mark the property (or any other subschema) at `pointer` as deprecated in its description
+///
+/// This is the equivalent of `#[deprecated]` on a field, for schemas that are generated from types that
+/// can't be changed. The description is prefixed with `Deprecated: ` (or set to `Deprecated.` if there is
+/// none), so that it is shown by `kubectl explain` and other tooling that reads descriptions. The
+/// `deprecated` keyword isn't used, since CRDs (and [`JSONSchemaProps`]) don't support it.
+///
+/// ```
+/// use kube_core::schema::mark_deprecated;
+/// use serde_json::json;
+///
+/// let mut schema = json!({
+///     "type": "object",
+///     "properties": {
+///         "replicaCount": { "type": "integer", "description": "Number of replicas" },
+///     },
+/// });
+/// mark_deprecated(&mut schema, "/properties/replicaCount")?;
+/// assert_eq!(
+///     schema["properties"]["replicaCount"],
+///     json!({ "type": "integer", "description": "Deprecated: Number of replicas" })
+/// );
+/// # Ok::<(), kube_core::schema::SchemaRewriteError>(())
+/// ```
+pub fn mark_deprecated(schema: &mut Value, pointer: &str) -> Result<(), SchemaRewriteError> {
+    let Some(Value::Object(subschema)) = schema.pointer_mut(pointer) else {
+        return Err(SchemaRewriteError::PointerNotFound {
+            pointer: pointer.into(),
+        });
+    };
+    let description = match subschema.get("description").and_then(Value::as_str) {
+        Some(description) if description.starts_with("Deprecated") => return Ok(()),
+        Some(description) => format!("Deprecated: {description}"),
+        None => "Deprecated.".to_string(),
+    };
+    subschema.insert("description".into(), description.into());
+    Ok(())
+}
+
+/// Make every property of the schema optional, such as for validating partial updates (patches)
+///
+/// All `required` keywords are removed, including the ones of enum variants, along with `oneOf` and `anyOf`
+/// whose variants don't hold any other validations. Subschemas with `x-kubernetes-preserve-unknown-fields`
+/// are opaque, so they are left untouched.
+///
+/// ```
+/// use kube_core::schema::make_all_optional;
+/// use serde_json::json;
+///
+/// let mut schema = json!({
+///     "type": "object",
+///     "properties": {
+///         "spec": {
+///             "type": "object",
+///             "properties": { "replicas": { "type": "integer" } },
+///             "required": ["replicas"],
+///         },
+///     },
+///     "required": ["spec"],
+/// });
+/// make_all_optional(&mut schema);
+/// assert_eq!(
+///     schema,
+///     json!({
+///         "type": "object",
+///         "properties": {
+///             "spec": { "type": "object", "properties": { "replicas": { "type": "integer" } } },
+///         },
+///     })
+/// );
+/// ```
+pub fn make_all_optional(schema: &mut Value) {
+    if schema.get("x-kubernetes-preserve-unknown-fields") == Some(&Value::Bool(true)) {
+        return;
+    }
+    for (_, subschema) in subschemas_mut(schema) {
+        make_all_optional(subschema);
+    }
+    let Some(object) = schema.as_object_mut() else {
+        return;
+    };
+    object.remove("required");
+    for junctor in ["oneOf", "anyOf"] {
+        let is_empty = |variant: &Value| variant.as_object().is_some_and(|variant| variant.is_empty());
+        if let Some(Value::Array(variants)) = object.get(junctor) {
+            if variants.iter().all(is_empty) {
+                object.remove(junctor);
+            }
+        }
+    }
+}
+
+/// Remove the `x-kube-rs-source-type` annotations added by [`StructuralSchemaRewriterBuilder::source_types`]
+///
+/// ```
+/// use kube_core::schema::strip_source_types;
+/// use serde_json::json;
+///
+/// let mut schema = json!({ "type": "object", "x-kube-rs-source-type": "FooSpec" });
+/// strip_source_types(&mut schema);
+/// assert_eq!(schema, json!({ "type": "object" }));
+/// ```
+pub fn strip_source_types(schema: &mut Value) {
+    if let Some(object) = schema.as_object_mut() {
+        object.remove(SOURCE_TYPE);
+    }
+    for (_, subschema) in subschemas_mut(schema) {
+        strip_source_types(subschema);
+    }
+}
+
+impl StructuralSchemaRewriter {
+    /// Rewrite `schema` into a structural schema, like [`Transform::transform`], but return errors instead of
+    /// panicking
+    ///
+    /// This includes subschemas that can't be parsed (such as a `maxItems` that isn't a number), which are
+    /// reported with their JSON pointer. `schema` is left unchanged if rewriting it fails.
+    ///
+    /// ```
+    /// use kube_core::schema::{SchemaRewriteError, StructuralSchemaRewriter};
+    /// use serde_json::json;
+    ///
+    /// let mut schema = schemars::Schema::try_from(json!({
+    ///     "type": "object",
+    ///     "properties": { "tags": { "type": "array", "maxItems": "ten" } },
+    /// }))?;
+    /// let err = StructuralSchemaRewriter::default().try_transform(&mut schema).unwrap_err();
+    /// assert!(matches!(
+    ///     err,
+    ///     SchemaRewriteError::InvalidSchema { pointer, .. } if pointer == "/properties/tags"
+    /// ));
+    /// # Ok::<(), serde_json::Error>(())
+    /// ```
+    pub fn try_transform(
+        &mut self,
+        transform_schema: &mut schemars::Schema,
+    ) -> Result<(), SchemaRewriteError> {
+        let mut schema = transform_schema.clone().to_value();
+        let mut warnings = Vec::new();
+        self.apply_steps(&self.steps(), &mut schema, &mut warnings, None)?;
+        self.warnings = warnings;
+        *transform_schema = schema
+            .try_into()
+            .map_err(|source| SchemaRewriteError::InvalidSchema {
+                pointer: String::new(),
+                source,
+            })?;
+        Ok(())
+    }
+}
+
+impl Transform for StructuralSchemaRewriter {
+    fn transform(&mut self, transform_schema: &mut schemars::Schema) {
+        if let Err(err) = self.try_transform(transform_schema) {
+            panic!("failed to rewrite schema: {err}");
+        }
+    }
+}
+
+/// The individual transforms applied by [`StructuralSchemaRewriter`], in order
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    InlineReferences,
+    RejectUninhabitedFields,
+    RejectUnsupportedKeywords,
+    UnwrapSingleProperties,
+    ConstValues,
+    IntOrStringTypes,
+    NullOnlyFields,
+    TupleItems,
+    BooleanItems,
+    AnyValueFields,
+    MergeAllOfObjects,
+    HoistOneOf,
+    HoistAnyOf,
+    UnevaluatedProperties,
+    OpenEnums,
+    NullableEnums,
+    PreserveUnknownObjectEnums,
+    PreserveUnknownFlattenedMaps,
+    StripUniqueItems,
+    ContentKeywords,
+    SingleExample,
+    StripEmptyKeywords,
+    NormalizeByteArrays,
+    NullableTypeArrays,
+    BoundMapKeys,
+    BoundEnumValues,
+    NullablePolicy,
+    StripDescriptions,
+    RootDescription,
+    PreserveUnknownRoot,
+    Compact,
+    StripMarkers,
+    CanonicalKeywordOrder,
+}
+
+impl Step {
+    const ALL: [Step; 33] = [
+        Step::InlineReferences,
+        Step::RejectUninhabitedFields,
+        Step::RejectUnsupportedKeywords,
+        Step::UnwrapSingleProperties,
+        Step::ConstValues,
+        Step::IntOrStringTypes,
+        Step::NullOnlyFields,
+        Step::TupleItems,
+        Step::BooleanItems,
+        Step::AnyValueFields,
+        Step::MergeAllOfObjects,
+        Step::HoistOneOf,
+        Step::HoistAnyOf,
+        Step::UnevaluatedProperties,
+        Step::OpenEnums,
+        Step::NullableEnums,
+        Step::PreserveUnknownObjectEnums,
+        Step::PreserveUnknownFlattenedMaps,
+        Step::StripUniqueItems,
+        Step::ContentKeywords,
+        Step::SingleExample,
+        Step::StripEmptyKeywords,
+        Step::NormalizeByteArrays,
+        Step::NullableTypeArrays,
+        Step::BoundMapKeys,
+        Step::BoundEnumValues,
+        Step::NullablePolicy,
+        Step::StripDescriptions,
+        Step::RootDescription,
+        Step::PreserveUnknownRoot,
+        Step::Compact,
+        Step::StripMarkers,
+        Step::CanonicalKeywordOrder,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Step::InlineReferences => "inline_references",
+            Step::RejectUninhabitedFields => "reject_uninhabited_fields",
+            Step::RejectUnsupportedKeywords => "reject_unsupported_keywords",
+            Step::UnwrapSingleProperties => "unwrap_single_properties",
+            Step::ConstValues => "const_values",
+            Step::IntOrStringTypes => "int_or_string_types",
+            Step::NullOnlyFields => "null_only_fields",
+            Step::UnevaluatedProperties => "unevaluated_properties",
+            Step::TupleItems => "tuple_items",
+            Step::BooleanItems => "boolean_items",
+            Step::AnyValueFields => "any_value_fields",
+            Step::MergeAllOfObjects => "merge_all_of_objects",
+            Step::HoistOneOf => "hoist_one_of",
+            Step::HoistAnyOf => "hoist_any_of",
+            Step::OpenEnums => "open_enums",
+            Step::NullableEnums => "nullable_enums",
+            Step::PreserveUnknownObjectEnums => "preserve_unknown_object_enums",
+            Step::PreserveUnknownFlattenedMaps => "preserve_unknown_flattened_maps",
+            Step::StripUniqueItems => "strip_unique_items",
+            Step::ContentKeywords => "content_keywords",
+            Step::SingleExample => "single_example",
+            Step::StripEmptyKeywords => "strip_empty_keywords",
+            Step::NormalizeByteArrays => "normalize_byte_arrays",
+            Step::NullableTypeArrays => "nullable_type_arrays",
+            Step::BoundMapKeys => "bound_map_keys",
+            Step::BoundEnumValues => "bound_enum_values",
+            Step::NullablePolicy => "nullable_policy",
+            Step::StripDescriptions => "strip_descriptions",
+            Step::RootDescription => "root_description",
+            Step::PreserveUnknownRoot => "preserve_unknown_root",
+            Step::Compact => "compact",
+            Step::StripMarkers => "strip_markers",
+            Step::CanonicalKeywordOrder => "canonical_keyword_order",
+        }
+    }
+
+    fn enabled(self, rewriter: &StructuralSchemaRewriter) -> bool {
+        match self {
+            Step::UnwrapSingleProperties => !rewriter.unwrapped_properties.is_empty(),
+            Step::NormalizeByteArrays => rewriter.normalize_byte_arrays,
+            Step::NullableTypeArrays => rewriter.nullable_type_arrays,
+            Step::BoundMapKeys => rewriter.map_key_max_length.is_some(),
+            Step::BoundEnumValues => rewriter.enum_value_max_length.is_some(),
+            Step::NullablePolicy => rewriter.nullable_policy.is_some(),
+            Step::StripDescriptions => rewriter.strip_descriptions,
+            Step::RootDescription => rewriter.root_description.is_some(),
+            Step::PreserveUnknownRoot => rewriter.preserve_unknown_root,
+            Step::Compact => rewriter.compact,
+            Step::CanonicalKeywordOrder => rewriter.canonical_keyword_order,
+            Step::InlineReferences => !rewriter.skip_inline_references,
+            Step::HoistOneOf => !rewriter.skip_hoist_enums,
+            Step::HoistAnyOf => !rewriter.skip_hoist_enums || !rewriter.skip_remove_null_enum_variant,
+            Step::StripUniqueItems => !rewriter.skip_strip_unique_items,
+            Step::PreserveUnknownFlattenedMaps => !rewriter.skip_preserve_unknown_flattened_maps,
+            _ => true,
+        }
+    }
+
+    /// Describe the information that applying this step to `schema` would lose, if any
+    ///
+    /// Only the steps that are enabled by default are checked, see [`StructuralSchemaRewriterBuilder::lossless`].
+    fn loss(self, schema: &SchemaObject, rewriter: &StructuralSchemaRewriter) -> Option<String> {
+        let has = |keyword: &str| schema.extensions.contains_key(keyword);
+        match self {
+            Step::NullOnlyFields => {
+                let mut rewritten = schema.clone();
+                null_only_fields(&mut rewritten, "");
+                (rewritten != *schema).then(|| "fields that can only be null accept any value".into())
+            }
+            Step::UnevaluatedProperties => {
+                let without_properties = !schema.object.as_ref().is_some_and(|o| o.has_properties());
+                match schema.extensions.get("unevaluatedProperties") {
+                    None => None,
+                    Some(Value::Bool(false)) if without_properties => None,
+                    Some(_) => Some("`unevaluatedProperties` can't be expressed exactly".into()),
+                }
+            }
+            Step::TupleItems => {
+                let items = match (schema.extensions.get("prefixItems"), &schema.array) {
+                    (Some(Value::Array(items)), _) => items.clone(),
+                    (_, Some(array)) => match &array.items {
+                        Some(SingleOrVec::Vec(items)) => items
+                            .iter()
+                            .filter_map(|i| serde_json::to_value(i).ok())
+                            .collect(),
+                        _ => return None,
+                    },
+                    _ => return None,
+                };
+                let mixed = items.iter().any(|item| Some(item) != items.first());
+                mixed.then(|| "the items of a tuple with different types are not validated".into())
+            }
+            Step::HoistOneOf | Step::HoistAnyOf => {
+                if rewriter.skip_hoist_enums {
+                    return None;
+                }
+                let subschemas = schema.subschemas.as_ref()?;
+                let variants = if matches!(self, Step::HoistOneOf) {
+                    subschemas.one_of.as_ref()?
+                } else {
+                    subschemas.any_of.as_ref()?
+                };
+                let variants = variants
+                    .iter()
+                    .filter(|variant| !is_null_schema(variant))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if variants.len() == 1 {
+                    // Optional enums are merged with their only variant, see `remove_null_variant`
+                    return None;
+                }
+                if variants.iter().any(is_annotation_only) {
+                    return Some("enums with a variant that accepts any value are not validated".into());
+                }
+                if has_mixed_instance_types(&variants) {
+                    return Some("enum variants of different types are not validated".into());
+                }
+                let mut unwrapped = variants.clone();
+                unwrapped.iter_mut().for_each(unwrap_documented_variant);
+                unwrapped.iter_mut().for_each(flatten_all_of);
+                if mixes_enum_values_and_properties(&unwrapped) {
+                    return Some("enums mixing unit and struct variants are not validated".into());
+                }
+                drops_variant_descriptions(&unwrapped, rewriter.copy_variant_descriptions)
+                    .then(|| "the descriptions of enum variants are removed".into())
+            }
+            Step::PreserveUnknownObjectEnums => {
+                let mut preserved = schema.clone();
+                preserve_unknown_object_enums(&mut preserved);
+                (preserved != *schema).then(|| "the fields of enum values are not validated".into())
+            }
+            Step::PreserveUnknownFlattenedMaps => {
+                let object = schema.object.as_ref()?;
+                let is_typed_map = matches!(
+                    object.additional_properties.as_deref(),
+                    Some(values @ Schema::Object(_)) if !is_empty_schema(values)
+                );
+                (object.has_properties() && is_typed_map)
+                    .then(|| "the values of flattened maps are not validated".into())
+            }
+            Step::StripUniqueItems => {
+                let unique = schema
+                    .array
+                    .as_ref()
+                    .is_some_and(|a| a.unique_items == Some(true));
+                unique.then(|| "`uniqueItems` is removed".into())
+            }
+            Step::ContentKeywords => ["contentEncoding", "contentMediaType", "contentSchema"]
+                .into_iter()
+                .find(|keyword| has(keyword))
+                .map(|keyword| format!("`{keyword}` is removed")),
+            Step::SingleExample => match schema.extensions.get("examples") {
+                Some(Value::Array(examples)) if examples.len() > 1 && !rewriter.preserve_examples => {
+                    Some("only the first of the `examples` is kept".into())
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    fn apply(
+        self,
+        schema: &mut SchemaObject,
+        pointer: &str,
+        rewriter: &StructuralSchemaRewriter,
+        warnings: &mut Vec<String>,
+    ) -> Result<(), SchemaRewriteError> {
+        match self {
+            // Applied to the whole schema at once by `StructuralSchemaRewriter::apply_steps`
+            Step::InlineReferences | Step::StripMarkers | Step::CanonicalKeywordOrder => {}
+            Step::RejectUninhabitedFields => return reject_uninhabited_fields(schema, pointer),
+            Step::RejectUnsupportedKeywords => return reject_unsupported_keywords(schema, pointer),
+            Step::UnwrapSingleProperties => {
+                if rewriter
+                    .unwrapped_properties
+                    .iter()
+                    .any(|unwrapped| unwrapped == pointer)
+                {
+                    return unwrap_single_property(schema, pointer);
+                }
+            }
+            Step::ConstValues => const_values(schema),
+            Step::IntOrStringTypes => return int_or_string_types(schema, pointer),
+            Step::NullOnlyFields => null_only_fields(schema, pointer),
+            Step::UnevaluatedProperties => unevaluated_properties(schema),
+            Step::TupleItems => tuple_items(schema),
+            Step::BooleanItems => return boolean_items(schema, pointer),
+            Step::AnyValueFields => any_value_fields(schema),
+            Step::MergeAllOfObjects => return merge_all_of_objects(schema, pointer),
+            Step::HoistOneOf => {
+                return hoist_one_of(
+                    schema,
+                    pointer,
+                    rewriter.copy_variant_descriptions,
+                    rewriter.tagged_enum_validations,
+                    warnings,
+                )
+            }
+            Step::HoistAnyOf => {
+                if !rewriter.skip_remove_null_enum_variant {
+                    remove_null_variant(schema);
+                }
+                if !rewriter.skip_hoist_enums {
+                    return hoist_any_of(schema, pointer, rewriter.copy_variant_descriptions, warnings);
+                }
+            }
+            Step::OpenEnums => open_enums(schema, rewriter.open_enum_tag_validations),
+            Step::NullableEnums => return nullable_enums(schema, pointer),
+            Step::PreserveUnknownObjectEnums => preserve_unknown_object_enums(schema),
+            Step::PreserveUnknownFlattenedMaps => preserve_unknown_flattened_maps(schema),
+            Step::StripUniqueItems => strip_unique_items(schema),
+            Step::ContentKeywords => content_keywords(schema, rewriter.content_encoding_as_format),
+            Step::SingleExample => single_example(schema, rewriter.preserve_examples),
+            Step::StripEmptyKeywords => strip_empty_keywords(schema),
+            Step::NormalizeByteArrays => normalize_byte_arrays(schema),
+            Step::NullableTypeArrays => nullable_type_arrays(schema),
+            Step::BoundEnumValues => {
+                if let Some(max_length) = rewriter.enum_value_max_length {
+                    return bound_enum_values(schema, pointer, max_length);
+                }
+            }
+            Step::BoundMapKeys => {
+                if let Some(max_length) = rewriter.map_key_max_length {
+                    bound_map_keys(schema, max_length)
+                }
+            }
+            Step::NullablePolicy => {
+                if let Some(policy) = rewriter.nullable_policy {
+                    nullable_policy(schema, policy, rewriter.nullable_type_arrays)
+                }
+            }
+            Step::StripDescriptions => strip_descriptions(schema),
+            Step::RootDescription => {
+                if let (true, Some(description)) = (pointer.is_empty(), &rewriter.root_description) {
+                    schema.metadata.get_or_insert_with(Default::default).description =
+                        Some(description.clone());
+                }
+            }
+            Step::PreserveUnknownRoot => {
+                if pointer.is_empty() {
+                    preserve_unknown_fields(schema);
+                    schema.instance_type = Some(SingleOrVec::Single(Box::new(InstanceType::Object)));
+                }
+            }
+            Step::Compact => compact(schema),
+        }
+        Ok(())
+    }
+}
+
+/// Replace a wrapper object with the schema of its only property, keeping the description and nullability of
+/// the wrapper
+fn unwrap_single_property(schema: &mut SchemaObject, pointer: &str) -> Result<(), SchemaRewriteError> {
+    let not_single_property = || SchemaRewriteError::NotSingleProperty { path: pointer.into() };
+    let properties = schema
+        .object
+        .as_mut()
+        .and_then(|object| object.properties.take())
+        .filter(|properties| properties.len() == 1)
+        .ok_or_else(not_single_property)?;
+    let Some((_, Schema::Object(mut inner))) = properties.into_iter().next() else {
+        return Err(not_single_property());
+    };
+    if let Some(description) = schema.metadata.as_mut().and_then(|m| m.description.take()) {
+        inner.metadata.get_or_insert_with(Default::default).description = Some(description);
+    }
+    if schema.extensions.get("nullable") == Some(&Value::Bool(true)) {
+        inner.set_extension("nullable", true.into());
+    }
+    *schema = inner;
+    Ok(())
+}
+
+fn reject_uninhabited_fields(schema: &SchemaObject, pointer: &str) -> Result<(), SchemaRewriteError> {
+    // A `false` schema can never be satisfied, which Kubernetes has no way to express
+    let Some(object) = &schema.object else {
+        return Ok(());
+    };
+    match object
+        .properties
+        .iter()
+        .flatten()
+        .find(|(_, property)| is_uninhabited(property))
+    {
+        Some((name, _)) => Err(SchemaRewriteError::UninhabitedField {
+            path: format!("{pointer}/properties/{}", escape_pointer(name)),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Keywords for dynamic references, which are resolved while validating and can't be inlined
+const UNSUPPORTED_KEYWORDS: [&str; 4] = [
+    "$dynamicRef",
+    "$dynamicAnchor",
+    "$recursiveRef",
+    "$recursiveAnchor",
+];
+
+fn reject_unsupported_keywords(schema: &SchemaObject, pointer: &str) -> Result<(), SchemaRewriteError> {
+    // CRDs can't express `contains` (along with `minContains` and `maxContains`), and `JSONSchemaProps` would
+    // drop it silently. Bounds without `contains` don't validate anything, and are removed instead.
+    let contains = schema
+        .array
+        .as_ref()
+        .is_some_and(|array| array.contains.is_some())
+        .then_some("contains");
+    match UNSUPPORTED_KEYWORDS
+        .into_iter()
+        .find(|keyword| schema.extensions.contains_key(*keyword))
+        .or(contains)
+    {
+        Some(keyword) => Err(SchemaRewriteError::UnsupportedKeyword {
+            path: pointer.into(),
+            keyword: keyword.into(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Whether the schema is `false`, or its OpenAPI v3 equivalent `{ "not": {} }`
+fn is_uninhabited(schema: &Schema) -> bool {
+    match schema {
+        Schema::Bool(valid) => !valid,
+        Schema::Object(schema) => serde_json::to_value(schema).ok() == Some(serde_json::json!({ "not": {} })),
+    }
+}
+
+fn null_only_fields(schema: &mut SchemaObject, pointer: &str) {
+    // The `None` variants of optional enums are removed by `remove_null_variant` instead
+    if matches!(pointer.rsplit('/').nth(1), Some("anyOf" | "oneOf" | "allOf")) {
+        return;
+    }
+    // Fields that can only be `null` (such as `()`) have no type that Kubernetes accepts, so the closest
+    // structural schema is a nullable field without any structure
+    let is_null_only = match (&schema.instance_type, schema.enum_values.as_deref()) {
+        (None, None) => false,
+        (instance_type, enum_values) => {
+            instance_type
+                .as_ref()
+                .is_none_or(|tpe| *tpe == SingleOrVec::Single(Box::new(InstanceType::Null)))
+                && enum_values.is_none_or(|values| values == [Value::Null])
+        }
+    } && schema.object.is_none()
+        && schema.array.is_none()
+        && schema.subschemas.is_none();
+    if !is_null_only {
+        return;
+    }
+    schema.instance_type = None;
+    schema.enum_values = None;
+    schema.set_extension("nullable", true.into());
+    schema.set_extension("x-kubernetes-preserve-unknown-fields", true.into());
+}
+
+/// Rewrite `const` to an `enum` with a single value, which is all that Kubernetes supports
+///
+/// [`SchemaSettings::openapi3`] does this already, but only for schemas that aren't referenced from
+/// `/components/schemas`, such as the tags of internally tagged enums with variants of separate struct types.
+/// Such references are inlined before any other step, so their tags are still merged when the enum is
+/// hoisted.
+fn const_values(schema: &mut SchemaObject) {
+    if let Some(value) = schema.remove_extension("const") {
+        schema.enum_values.get_or_insert_with(|| vec![value]);
+    }
+}
+
+fn int_or_string_types(schema: &mut SchemaObject, pointer: &str) -> Result<(), SchemaRewriteError> {
+    // Structural schemas only allow a single type per node, the only union Kubernetes understands is int-or-string
+    let Some(SingleOrVec::Vec(types)) = &schema.instance_type else {
+        return Ok(());
+    };
+    let types = types.iter().copied().collect::<BTreeSet<_>>();
+    let nullable = types.contains(&InstanceType::Null);
+    let non_null = types
+        .into_iter()
+        .filter(|tpe| *tpe != InstanceType::Null)
+        .collect::<Vec<_>>();
+    match non_null.as_slice() {
+        [] => schema.instance_type = None,
+        [tpe] => schema.instance_type = Some(SingleOrVec::Single(Box::new(*tpe))),
+        [InstanceType::String, InstanceType::Integer] => {
+            schema.instance_type = None;
+            schema.set_extension("x-kubernetes-int-or-string", true.into());
+        }
+        _ => {
+            return Err(SchemaRewriteError::MultipleTypes {
+                path: pointer.into(),
+                types: non_null.iter().map(|tpe| tpe.name().to_string()).collect(),
+            })
+        }
+    }
+    if nullable {
+        schema.set_extension("nullable", true.into());
+    }
+    Ok(())
+}
+
+fn unevaluated_properties(schema: &mut SchemaObject) {
+    // `unevaluatedProperties` (JSON Schema 2020-12) isn't supported by Kubernetes
+    match schema.remove_extension("unevaluatedProperties") {
+        Some(Value::Bool(false)) => {
+            // The apiserver prunes unknown fields anyway, and doesn't allow combining `properties` with
+            // `additionalProperties`, so this only needs to be expressed for objects without properties
+            let object = schema.object.get_or_insert_with(Default::default);
+            if !object.has_properties() && object.additional_properties.is_none() {
+                object.additional_properties = Some(Box::new(Schema::Bool(false)));
+            }
+        }
+        Some(_) => {
+            schema.set_extension("x-kubernetes-preserve-unknown-fields", true.into());
+        }
+        None => {}
+    }
+}
+
+fn tuple_items(schema: &mut SchemaObject) {
+    // Tuples are arrays with a schema per position (`items` in OpenAPI v3, `prefixItems` in JSON Schema 2020-12),
+    // while Kubernetes only supports a single schema for all items
+    let prefix_items = match schema.remove_extension("prefixItems") {
+        Some(prefix_items) => serde_json::from_value::<Vec<Schema>>(prefix_items).ok(),
+        None => None,
+    };
+    let array = match prefix_items {
+        Some(prefix_items) => {
+            let array = schema.array.get_or_insert_with(Default::default);
+            array.items = Some(SingleOrVec::Vec(prefix_items));
+            array
+        }
+        None => match &mut schema.array {
+            Some(array) => array,
+            None => return,
+        },
+    };
+    let Some(SingleOrVec::Vec(items)) = &array.items else {
+        return;
+    };
+    let Some(first) = items.first() else {
+        return;
+    };
+    if items.iter().any(|item| item != first) {
+        // Positions with different schemas can't be expressed in a structural schema
+        preserve_unknown_fields(schema);
+        return;
+    }
+    let len = items.len() as u64;
+    array.items = Some(SingleOrVec::Single(Box::new(first.clone())));
+    array.additional_items = None;
+    array.min_items.get_or_insert(len);
+    array.max_items.get_or_insert(len);
+}
+
+fn boolean_items(schema: &mut SchemaObject, pointer: &str) -> Result<(), SchemaRewriteError> {
+    // JSON Schema 2020-12 allows `items` to be a boolean schema, which Kubernetes doesn't
+    let Some(array) = &mut schema.array else {
+        return Ok(());
+    };
+    match array.items.as_ref() {
+        Some(SingleOrVec::Single(items)) if **items == Schema::Bool(true) => {
+            array.items = Some(SingleOrVec::Single(Box::new(Schema::Object(SchemaObject {
+                extensions: [("x-kubernetes-preserve-unknown-fields".into(), true.into())].into(),
+                ..SchemaObject::default()
+            }))));
+            Ok(())
+        }
+        Some(SingleOrVec::Single(items)) if **items == Schema::Bool(false) => {
+            Err(SchemaRewriteError::UninhabitedField {
+                path: format!("{pointer}/items"),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Mark the properties and `items` of `schema` that accept any value as preserving unknown fields
+///
+/// Kubernetes requires every field to have a `type`, unless it preserves unknown fields. Fields of type
+/// `serde_json::Value` are rendered as `true`, which [`SchemaSettings::openapi3`] replaces with `{}`.
+fn any_value_fields(schema: &mut SchemaObject) {
+    let properties = schema
+        .object
+        .iter_mut()
+        .flat_map(|object| object.properties.iter_mut().flatten())
+        .map(|(_, property)| property);
+    let items = schema
+        .array
+        .iter_mut()
+        .filter_map(|array| match &mut array.items {
+            Some(SingleOrVec::Single(items)) => Some(&mut **items),
+            _ => None,
+        });
+    for field in properties.chain(items) {
+        if !accepts_any_value(field) {
+            continue;
+        }
+        let mut any_value = match std::mem::replace(field, Schema::Bool(true)) {
+            Schema::Object(any_value) => any_value,
+            Schema::Bool(_) => SchemaObject::default(),
+        };
+        any_value.set_extension("x-kubernetes-preserve-unknown-fields", true.into());
+        *field = Schema::Object(any_value);
+    }
+}
+
+/// Whether `schema` is `true` or has nothing but annotations (and a `default`), so that any value is valid
+fn accepts_any_value(schema: &Schema) -> bool {
+    match schema {
+        Schema::Bool(accepts) => *accepts,
+        Schema::Object(_) => matches!(
+            serde_json::to_value(schema),
+            Ok(Value::Object(keywords)) if keywords
+                .keys()
+                .all(|keyword| keyword == "default" || ANNOTATION_KEYWORDS.contains(&keyword.as_str()))
+        ),
+    }
+}
+
+fn hoist_one_of(
+    schema: &mut SchemaObject,
+    pointer: &str,
+    copy_descriptions: bool,
+    tag_validations: bool,
+    warnings: &mut Vec<String>,
+) -> Result<(), SchemaRewriteError> {
+    // Tagged enums (and "plain" enums with doc tags) are serialized using `one_of`
+    let Some(one_of) = schema.subschemas.as_mut().and_then(|s| s.one_of.take()) else {
+        return Ok(());
+    };
+    let one_of = hoist_variants(
+        schema,
+        one_of,
+        pointer,
+        copy_descriptions,
+        tag_validations,
+        false,
+        warnings,
+    )?;
+    if let Some(subschemas) = &mut schema.subschemas {
+        subschemas.one_of = one_of;
+    }
+    Ok(())
+}
+
+fn hoist_any_of(
+    schema: &mut SchemaObject,
+    pointer: &str,
+    copy_descriptions: bool,
+    warnings: &mut Vec<String>,
+) -> Result<(), SchemaRewriteError> {
+    // Untagged enums are serialized using `any_of`
+    let Some(any_of) = schema.subschemas.as_mut().and_then(|s| s.any_of.take()) else {
+        return Ok(());
+    };
+    let any_of = hoist_variants(schema, any_of, pointer, copy_descriptions, false, true, warnings)?;
+    if let Some(subschemas) = &mut schema.subschemas {
+        subschemas.any_of = any_of;
+    }
+    Ok(())
+}
+
+/// Hoist the properties and enum values of the variants of an enum into `schema`
+///
+/// Returns the remaining variants, which only hold value validations (such as the `required` properties of
+/// each variant), or `None` if there are none left. Enums that can only be preserved as a whole are reported
+/// in `warnings`. If `merge_optional` is set, see [`merge_nullable`].
+fn hoist_variants(
+    schema: &mut SchemaObject,
+    mut variants: Vec<Schema>,
+    pointer: &str,
+    copy_descriptions: bool,
+    tag_validations: bool,
+    merge_optional: bool,
+    warnings: &mut Vec<String>,
+) -> Result<Option<Vec<Schema>>, SchemaRewriteError> {
+    let accepts_anything = variants.iter().any(is_annotation_only);
+    variants.iter_mut().for_each(unwrap_documented_variant);
+    variants.iter_mut().for_each(flatten_all_of);
+    let tag_rules = if tag_validations {
+        tagged_variant_rules(&variants)
+    } else {
+        Vec::new()
+    };
+    let mixed_types = has_mixed_instance_types(&variants);
+    if accepts_anything || mixed_types || mixes_enum_values_and_properties(&variants) {
+        // Enums mixing objects, arrays and primitives (such as unit variants and struct variants) can't be
+        // merged into a single structural schema (a node can't define both `enum` and `properties`), and
+        // variants without any constraints accept every value, so the best we can do is to let the apiserver
+        // accept anything
+        let reason = if accepts_anything {
+            "has a variant that accepts any value"
+        } else if mixed_types {
+            "has variants of different types"
+        } else {
+            "mixes unit and struct variants"
+        };
+        warnings.push(format!(
+            "the enum at {pointer:?} {reason}, so its values are not validated (it was replaced by \
+             `x-kubernetes-preserve-unknown-fields`)"
+        ));
+        preserve_unknown_fields(schema);
+        return Ok(None);
+    }
+    hoist_subschema_properties(
+        &mut variants,
+        &mut schema.object,
+        &mut schema.instance_type,
+        pointer,
+        copy_descriptions,
+        merge_optional,
+    )?;
+    hoist_subschema_enum_values(
+        &mut variants,
+        &mut schema.enum_values,
+        &mut schema.instance_type,
+        pointer,
+    )?;
+    for rule in tag_rules {
+        schema.add_validation(rule);
+    }
+    Ok((!variants.is_empty()).then_some(variants))
+}
+
+/// Rewrite `anyOf: [T, null]` (such as optional enums that are referenced or have variants) to a nullable `T`
+///
+/// If `T` is the only remaining variant, it is merged into the schema itself, so that it can be made structural
+/// like any other schema. Keywords of the schema take precedence over the keywords of `T`, except for the
+/// validation rules (`x-kubernetes-validations`) of both, which are all kept.
+fn remove_null_variant(schema: &mut SchemaObject) {
+    let Some(subschemas) = &mut schema.subschemas else {
+        return;
+    };
+    let Some(any_of) = &mut subschemas.any_of else {
+        return;
+    };
+    let variants = any_of.len();
+    any_of.retain(|variant| !is_null_schema(variant));
+    if any_of.len() == variants {
+        return;
+    }
+    let variant = match any_of.as_mut_slice() {
+        [Schema::Object(variant)] => Some(std::mem::take(variant)),
+        _ => None,
+    };
+    if variant.is_some() {
+        subschemas.any_of = None;
+        if **subschemas == SubschemaValidation::default() {
+            schema.subschemas = None;
+        }
+    }
+    schema.set_extension("nullable", true.into());
+    let Some(variant) = variant else {
+        return;
+    };
+    let (Ok(Value::Object(mut merged)), Ok(Value::Object(mut keywords))) =
+        (serde_json::to_value(variant), serde_json::to_value(&*schema))
+    else {
+        return;
+    };
+    let mut rules = Vec::new();
+    for rules_of in [&mut merged, &mut keywords] {
+        if let Some(Value::Array(more_rules)) = rules_of.remove("x-kubernetes-validations") {
+            rules.extend(more_rules);
+        }
+    }
+    merged.extend(keywords);
+    if !rules.is_empty() {
+        merged.insert("x-kubernetes-validations".into(), Value::Array(rules));
+    }
+    if let Ok(merged) = serde_json::from_value(Value::Object(merged)) {
+        *schema = merged;
+    }
+}
+
+/// Whether `schema` only allows `null`, as generated for the `None` variant of an [`Option`]
+fn is_null_schema(schema: &Schema) -> bool {
+    serde_json::to_value(schema).is_ok_and(|schema| is_null_value(&schema))
+}
+
+/// Like [`is_null_schema`], for a schema that hasn't been parsed
+fn is_null_value(schema: &Value) -> bool {
+    let Value::Object(keywords) = schema else {
+        return false;
+    };
+    let is_null = keywords.get("type") == Some(&Value::from("null"))
+        || keywords.get("enum") == Some(&Value::Array(vec![Value::Null]));
+    is_null
+        && keywords
+            .keys()
+            .all(|keyword| ["type", "enum", "nullable"].contains(&keyword.as_str()))
+}
+
+/// Keywords that only document a schema, without constraining its values
+const ANNOTATION_KEYWORDS: [&str; 5] = ["title", "description", "deprecated", "$comment", "examples"];
+
+/// Whether `variant` consists of nothing but annotations, so that it accepts any value
+fn is_annotation_only(variant: &Schema) -> bool {
+    match serde_json::to_value(variant) {
+        Ok(Value::Object(keywords)) => keywords
+            .keys()
+            .all(|keyword| ANNOTATION_KEYWORDS.contains(&keyword.as_str())),
+        Ok(Value::Bool(accepts)) => accepts,
+        _ => false,
+    }
+}
+
+/// Replace an enum variant that only documents another schema (`{ description, allOf: [T] }`) with `T`
+///
+/// The annotations of the wrapper take precedence over those of `T`.
+fn unwrap_documented_variant(variant: &mut Schema) {
+    let Ok(Value::Object(mut keywords)) = serde_json::to_value(&*variant) else {
+        return;
+    };
+    let is_annotation = |keyword: &String| ANNOTATION_KEYWORDS.contains(&keyword.as_str());
+    let inner = match keywords.get("allOf") {
+        Some(Value::Array(inner)) if inner.len() == 1 && inner[0].is_object() => inner[0].clone(),
+        _ => return,
+    };
+    if !keywords
+        .keys()
+        .all(|keyword| keyword == "allOf" || is_annotation(keyword))
+    {
+        return;
+    }
+    keywords.remove("allOf");
+    if let Value::Object(inner) = inner {
+        for (keyword, value) in inner {
+            keywords.entry(keyword).or_insert(value);
+        }
+    }
+    if let Ok(unwrapped) = serde_json::from_value(Value::Object(keywords)) {
+        *variant = unwrapped;
+    }
+}
+
+/// Merge the `allOf` of an enum variant into the variant itself, so that its properties can be hoisted
+///
+/// This happens when variants are composed of a shared base struct and their own fields. The variant is left
+/// as-is unless all of the composed schemas are plain objects with compatible properties.
+fn flatten_all_of(variant: &mut Schema) {
+    if let Schema::Object(schema) = variant {
+        if let Ok(Some(merged)) = merge_all_of(schema) {
+            *schema = merged;
+        }
+    }
+}
+
+/// Merge an `allOf` of several object schemas into a single object schema
+///
+/// Kubernetes doesn't allow the members of an `allOf` to define the structure of the object, so their
+/// `properties` and `required` are merged into the schema itself. A property that is defined with different
+/// schemas can't be merged, and is reported as an error. Other `allOf`s (such as ones that compose value
+/// validations) are left as-is.
+fn merge_all_of_objects(schema: &mut SchemaObject, pointer: &str) -> Result<(), SchemaRewriteError> {
+    // Wrappers of a single schema are inlined by `inline_references` and `compact`
+    let members = schema
+        .subschemas
+        .as_ref()
+        .and_then(|s| s.all_of.as_ref())
+        .map_or(0, Vec::len);
+    if members < 2 {
+        return Ok(());
+    }
+    match merge_all_of(schema) {
+        Ok(Some(merged)) => *schema = merged,
+        Ok(None) => {}
+        Err(property) => {
+            return Err(SchemaRewriteError::ConflictingProperty {
+                path: pointer.into(),
+                property,
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Merge the members of the `allOf` of `schema` into `schema` itself
+///
+/// Returns `None` unless all members are plain objects, or the name of the property that the members
+/// define with different schemas.
+fn merge_all_of(schema: &SchemaObject) -> Result<Option<SchemaObject>, String> {
+    let mut merged = schema.clone();
+    let Some(subschemas) = merged.subschemas.as_mut() else {
+        return Ok(None);
+    };
+    let Some(all_of) = subschemas.all_of.take() else {
+        return Ok(None);
+    };
+    if **subschemas == SubschemaValidation::default() {
+        merged.subschemas = None;
+    }
+    for member in all_of {
+        let Schema::Object(member) = member else {
+            return Ok(None);
+        };
+        let is_plain_object = member.subschemas.is_none()
+            && member.enum_values.is_none()
+            && member.array.is_none()
+            && member.format.is_none()
+            && member.extensions.is_empty();
+        if !is_plain_object {
+            return Ok(None);
+        }
+        match (&merged.instance_type, member.instance_type) {
+            (_, None) => {}
+            (None, Some(tpe)) => merged.instance_type = Some(tpe),
+            (Some(merged_tpe), Some(tpe)) if *merged_tpe == tpe => {}
+            (Some(_), Some(_)) => return Ok(None),
+        }
+        let Some(mut member_object) = member.object else {
+            continue;
+        };
+        let object = merged.object.get_or_insert_with(Default::default);
+        for (name, property) in member_object.properties.take().into_iter().flatten() {
+            match object.properties.get_or_insert_with(PropertyMap::new).entry(name) {
+                Entry::Vacant(entry) => {
+                    entry.insert(property);
+                }
+                Entry::Occupied(entry) if *entry.get() == property => {}
+                Entry::Occupied(entry) => return Err(entry.key().clone()),
+            }
+        }
+        if let Some(mut required) = member_object.required.take() {
+            object
+                .required
+                .get_or_insert_with(BTreeSet::new)
+                .append(&mut required);
+        }
+        // Other object validations (such as `additionalProperties`) can't be merged safely
+        if *member_object != ObjectValidation::default() {
+            return Ok(None);
+        }
+    }
+    Ok(Some(merged))
+}
+
+/// Replace the structure of `schema` with `x-kubernetes-preserve-unknown-fields`,
+/// for unions that can't be expressed as a structural schema
+fn preserve_unknown_fields(schema: &mut SchemaObject) {
+    schema.subschemas = None;
+    schema.instance_type = None;
+    schema.enum_values = None;
+    schema.object = None;
+    schema.array = None;
+    schema.set_extension("x-kubernetes-preserve-unknown-fields", true.into());
+}
+
+/// Prefix of the extensions used to pass hints to [`StructuralSchemaRewriter`], which are removed from its output
+const MARKER_PREFIX: &str = "x-kube-rs-";
+
+/// Extension marking an enum as open, meaning that values other than the listed ones are accepted
+const OPEN_ENUM: &str = "x-kube-rs-open-enum";
+
+/// Extension marking a subschema that must not be rewritten, including all of its subschemas
+const SKIP_REWRITE: &str = "x-kube-rs-skip-rewrite";
+
+/// Extension keeping the description of an enum variant,
+/// see [`StructuralSchemaRewriterBuilder::copy_variant_descriptions`]
+const VARIANT_DESCRIPTION: &str = "x-variant-description";
+
+/// Extension naming the Rust type that a subschema was generated from,
+/// see [`StructuralSchemaRewriterBuilder::source_types`]
+const SOURCE_TYPE: &str = "x-kube-rs-source-type";
+
+/// The order of keywords emitted by [`StructuralSchemaRewriterBuilder::canonical_keyword_order`]
+///
+/// Keywords that aren't listed here follow all listed keywords, in their original order.
+pub const CANONICAL_KEYWORD_ORDER: &[&str] = &[
+    "type",
+    "format",
+    "description",
+    "nullable",
+    "default",
+    "example",
+    "enum",
+    "minimum",
+    "exclusiveMinimum",
+    "maximum",
+    "exclusiveMaximum",
+    "multipleOf",
+    "minLength",
+    "maxLength",
+    "pattern",
+    "minItems",
+    "maxItems",
+    "uniqueItems",
+    "items",
+    "contains",
+    "minContains",
+    "maxContains",
+    "minProperties",
+    "maxProperties",
+    "properties",
+    "required",
+    "additionalProperties",
+    "propertyNames",
+    "allOf",
+    "anyOf",
+    "oneOf",
+    "not",
+    "x-kubernetes-int-or-string",
+    "x-kubernetes-embedded-resource",
+    "x-kubernetes-preserve-unknown-fields",
+    "x-kubernetes-list-type",
+    "x-kubernetes-list-map-keys",
+    "x-kubernetes-map-type",
+    "x-kubernetes-validations",
+];
+
+fn order_keywords(schema: &mut Value) {
+    if let Some(object) = schema.as_object_mut() {
+        let mut keywords = std::mem::take(object).into_iter().collect::<Vec<_>>();
+        // Sorting is stable, so keywords that aren't listed keep their order
+        keywords.sort_by_key(|(keyword, _)| {
+            CANONICAL_KEYWORD_ORDER
+                .iter()
+                .position(|canonical| canonical == keyword)
+                .unwrap_or(CANONICAL_KEYWORD_ORDER.len())
+        });
+        object.extend(keywords);
+    }
+    for (_, subschema) in subschemas_mut(schema) {
+        order_keywords(subschema);
+    }
+}
+
+fn is_skipped(schema: &Value) -> bool {
+    schema.get(SKIP_REWRITE) == Some(&Value::Bool(true))
+}
+
+fn strip_markers(schema: &mut Value, keep_source_types: bool) {
+    if let Some(object) = schema.as_object_mut() {
+        object.retain(|keyword, _| {
+            !keyword.starts_with(MARKER_PREFIX) || (keep_source_types && keyword == SOURCE_TYPE)
+        });
+    }
+    for (_, subschema) in subschemas_mut(schema) {
+        strip_markers(subschema, keep_source_types);
+    }
+}
+
+fn open_enums(schema: &mut SchemaObject, tag_validations: bool) {
+    if schema.remove_extension(OPEN_ENUM) != Some(Value::Bool(true)) {
+        return;
+    }
+    schema.enum_values = None;
+
+    // The tag of internally tagged enums is the property that is required by all variants
+    let Some(one_of) = schema.subschemas.as_ref().and_then(|s| s.one_of.as_ref()) else {
+        return;
+    };
+    let mut variant_required = one_of.iter().map(|variant| match variant {
+        Schema::Object(SchemaObject {
+            object: Some(object), ..
+        }) => object.required.clone().unwrap_or_default(),
+        _ => BTreeSet::new(),
+    });
+    let first = variant_required.next().unwrap_or_default();
+    let tags = variant_required.fold(first, |tags, required| &tags & &required);
+    if let Some(object) = &mut schema.object {
+        for tag in tags {
+            if let Some(Schema::Object(tag)) = object.properties.as_mut().and_then(|p| p.get_mut(&tag)) {
+                let known = tag.enum_values.take().unwrap_or_default();
+                if tag_validations {
+                    push_known_tags_rule(tag, &known);
+                }
+            }
+        }
+    }
+}
+
+/// Add a CEL rule to the `tag` property of an open enum that only accepts the `known` string tags
+fn push_known_tags_rule(tag: &mut SchemaObject, known: &[Value]) {
+    let known = known.iter().filter_map(Value::as_str).collect::<Vec<_>>();
+    if known.is_empty() {
+        return;
+    }
+    let literals = known.iter().map(|tag| cel_string(tag)).collect::<Vec<_>>();
+    tag.add_validation(
+        Rule::new(format!("self in [{}]", literals.join(", ")))
+            .message(format!("must be one of the known values: {}", known.join(", ")).as_str()),
+    );
+}
+
+/// A CEL string literal of `value`
+fn cel_string(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// The name of the property `name` in CEL expressions, escaped like the apiserver does
+///
+/// Returns `None` for names that can't be accessed from CEL at all.
+fn cel_field(name: &str) -> Option<String> {
+    const RESERVED: [&str; 21] = [
+        "true",
+        "false",
+        "null",
+        "in",
+        "as",
+        "break",
+        "const",
+        "continue",
+        "else",
+        "for",
+        "function",
+        "if",
+        "import",
+        "let",
+        "loop",
+        "package",
+        "namespace",
+        "return",
+        "var",
+        "void",
+        "while",
+    ];
+    let is_allowed = |c: char| c.is_ascii_alphanumeric() || "_.-/".contains(c);
+    let is_allowed_first = |c: char| c.is_ascii_alphabetic() || "_.-/".contains(c);
+    if !name.starts_with(is_allowed_first) || !name.chars().all(is_allowed) {
+        return None;
+    }
+    if RESERVED.contains(&name) {
+        return Some(format!("__{name}__"));
+    }
+    Some(
+        name.replace("__", "__underscores__")
+            .replace('.', "__dot__")
+            .replace('-', "__dash__")
+            .replace('/', "__slash__"),
+    )
+}
+
+/// CEL rules that only accept the fields of the variant selected by the tag of an internally tagged enum,
+/// see [`StructuralSchemaRewriterBuilder::tagged_enum_validations`]
+///
+/// Each variant gets a rule that requires its required fields, and rejects the fields of all other variants,
+/// whenever the tag selects it.
+fn tagged_variant_rules(variants: &[Schema]) -> Vec<Rule> {
+    let mut objects = Vec::new();
+    for variant in variants {
+        let Schema::Object(SchemaObject {
+            object: Some(object), ..
+        }) = variant
+        else {
+            return Vec::new();
+        };
+        objects.push(object);
+    }
+    // The tag of each variant is a required property with a single value
+    let tag_value = |object: &ObjectValidation, name: &str| match object.properties.as_ref()?.get(name)? {
+        Schema::Object(SchemaObject {
+            enum_values: Some(values),
+            ..
+        }) if object.is_required(name) => match values.as_slice() {
+            [Value::String(value)] => Some(value.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    let Some(first) = objects.first() else {
+        return Vec::new();
+    };
+    let Some(tag) = first
+        .properties
+        .iter()
+        .flatten()
+        .map(|(name, _)| name)
+        .find(|name| objects.iter().all(|object| tag_value(object, name).is_some()))
+    else {
+        return Vec::new();
+    };
+    let Some(tag_field) = cel_field(tag) else {
+        return Vec::new();
+    };
+    let fields = objects
+        .iter()
+        .flat_map(|object| object.properties.iter().flatten().map(|(name, _)| name))
+        .filter(|name| *name != tag)
+        .collect::<BTreeSet<_>>();
+
+    let mut rules = Vec::new();
+    for object in objects {
+        let Some(value) = tag_value(object, tag) else {
+            continue;
+        };
+        let mut conditions = Vec::new();
+        for name in &fields {
+            let Some(field) = cel_field(name) else {
+                return Vec::new();
+            };
+            let defined = object.properties.as_ref().is_some_and(|p| p.contains_key(*name));
+            if !defined {
+                conditions.push(format!("!has(self.{field})"));
+            } else if object.is_required(name) {
+                conditions.push(format!("has(self.{field})"));
+            }
+        }
+        let condition = match conditions.as_slice() {
+            [] => continue,
+            [condition] => condition.clone(),
+            conditions => format!("({})", conditions.join(" && ")),
+        };
+        rules.push(
+            Rule::new(format!(
+                "self.{tag_field} != {} || {condition}",
+                cel_string(&value)
+            ))
+            .message(format!("the fields must match {tag} {value}").as_str()),
+        );
+    }
+    rules
+}
+
+/// Make the `enum` of a schema agree with whether it is `nullable`
+///
+/// The apiserver validates `null` against the `enum` of nullable schemas too, so `null` is added to nullable
+/// enums that don't list it, such as optional enums whose `None` variant was removed by `remove_null_variant`.
+/// A literal `null` in the `enum` of a typed schema that isn't nullable can never be valid, so it is removed
+/// (and typed enums that only list `null` are rejected, since no value is valid for them), whereas untyped
+/// enums that list `null` do accept it, and are made nullable instead.
+fn nullable_enums(schema: &mut SchemaObject, pointer: &str) -> Result<(), SchemaRewriteError> {
+    let Some(enum_values) = &mut schema.enum_values else {
+        return Ok(());
+    };
+    let has_null = enum_values.contains(&Value::Null);
+    if schema.extensions.get("nullable") == Some(&Value::Bool(true)) {
+        if !has_null {
+            enum_values.push(Value::Null);
+        }
+    } else if has_null {
+        if schema.instance_type.is_none() {
+            schema.set_extension("nullable", true.into());
+        } else if enum_values.iter().any(|value| !value.is_null()) {
+            enum_values.retain(|value| !value.is_null());
+        } else {
+            return Err(SchemaRewriteError::UninhabitedField { path: pointer.into() });
+        }
+    }
+    Ok(())
+}
+
+fn preserve_unknown_object_enums(schema: &mut SchemaObject) {
+    // Enums of hard-coded objects would have all of their fields pruned by the apiserver
+    // (and then fail the enum validation), unless the object structure is preserved.
+    // The `enum` itself still restricts the allowed values.
+    let Some(enum_values) = &schema.enum_values else {
+        return;
+    };
+    if enum_values.is_empty() || !enum_values.iter().all(Value::is_object) {
+        return;
+    }
+    if schema.object.as_ref().is_some_and(|o| o.has_properties()) {
+        return;
+    }
+    match &schema.instance_type {
+        None => schema.instance_type = Some(SingleOrVec::Single(Box::new(InstanceType::Object))),
+        Some(SingleOrVec::Single(tpe)) if **tpe == InstanceType::Object => {}
+        Some(_) => return,
+    }
+    schema.set_extension("x-kubernetes-preserve-unknown-fields", true.into());
+}
+
+fn preserve_unknown_flattened_maps(schema: &mut SchemaObject) {
+    // check for maps without with properties (i.e. flattened maps)
+    // and allow these to persist dynamically
+    if let Some(object) = &mut schema.object {
+        // An empty schema allows any value, just like `true`
+        if object
+            .additional_properties
+            .as_deref()
+            .is_some_and(is_empty_schema)
+        {
+            object.additional_properties = Some(Box::new(Schema::Bool(true)));
+        }
+        // Kubernetes doesn't allow both `properties` and `additionalProperties`, so the value schema of
+        // flattened typed maps is lost here. Maps without other fields keep it, since they aren't merged
+        // with any `properties`.
+        let is_flattened_map = !matches!(
+            object.additional_properties.as_deref(),
+            None | Some(Schema::Bool(false))
+        );
+        if object.has_properties() && is_flattened_map {
+            object.additional_properties = None;
+            schema.set_extension("x-kubernetes-preserve-unknown-fields", true.into());
+        }
+    }
+}
+
+/// Whether `schema` is `{}`, which doesn't validate anything
+fn is_empty_schema(schema: &Schema) -> bool {
+    matches!(serde_json::to_value(schema), Ok(Value::Object(keywords)) if keywords.is_empty())
+}
+
+fn strip_unique_items(schema: &mut SchemaObject) {
+    // As of version 1.30 Kubernetes does not support setting `uniqueItems` to `true`,
+    // so we need to remove this fields.
+    // Users can still set `x-kubernetes-list-type=set` in case they want the apiserver
+    // to do validation, but we can't make an assumption about the Set contents here.
+    // See https://kubernetes.io/docs/reference/using-api/server-side-apply/ for details.
+    if let Some(array) = &mut schema.array {
+        array.unique_items = None;
+    }
+}
+
+fn content_keywords(schema: &mut SchemaObject, encoding_as_format: bool) {
+    let encoding = schema.remove_extension("contentEncoding");
+    schema.remove_extension("contentMediaType");
+    schema.remove_extension("contentSchema");
+    if encoding_as_format && encoding.as_ref().and_then(Value::as_str) == Some("base64") {
+        schema.format.get_or_insert_with(|| "byte".into());
+    }
+}
+
+fn single_example(schema: &mut SchemaObject, preserve_all: bool) {
+    let Some(Value::Array(examples)) = schema.remove_extension("examples") else {
+        return;
+    };
+    if let Some(first) = examples.first() {
+        if !schema.extensions.contains_key("example") {
+            schema.set_extension("example", first.clone());
+        }
+    }
+    if preserve_all && examples.len() > 1 {
+        schema.set_extension("x-examples", Value::Array(examples));
+    }
+}
+
+fn strip_empty_keywords(schema: &mut SchemaObject) {
+    // Hoisting enum variants can leave keywords behind that don't constrain anything
+    if let Some(object) = &mut schema.object {
+        if object
+            .properties
+            .as_ref()
+            .is_some_and(|properties| properties.is_empty())
+        {
+            object.properties = None;
+        }
+        if object
+            .required
+            .as_ref()
+            .is_some_and(|required| required.is_empty())
+        {
+            object.required = None;
+        }
+    }
+    // `minContains` and `maxContains` only bound the number of items that match `contains`
+    if let Some(array) = &mut schema.array {
+        if array.contains.is_none() {
+            array.min_contains = None;
+            array.max_contains = None;
+        }
+    }
+    if let Some(subschemas) = &mut schema.subschemas {
+        let is_empty = |subschema: &Schema| match subschema {
+            Schema::Bool(allowed) => *allowed,
+            // Compare the serialized form, since empty keywords may still be present as `Some(Default::default())`
+            Schema::Object(subschema) => serde_json::to_value(subschema)
+                .is_ok_and(|value| value.as_object().is_some_and(|value| value.is_empty())),
+        };
+        // Any value matches an `anyOf` with an empty variant
+        if subschemas
+            .any_of
+            .as_ref()
+            .is_some_and(|any_of| any_of.iter().any(is_empty))
+        {
+            subschemas.any_of = None;
+        }
+        if subschemas
+            .all_of
+            .as_ref()
+            .is_some_and(|all_of| all_of.iter().all(is_empty))
+        {
+            subschemas.all_of = None;
+        }
+        if matches!(subschemas.one_of.as_deref(), Some([one_of]) if is_empty(one_of)) {
+            subschemas.one_of = None;
+        }
+        if **subschemas == SubschemaValidation::default() {
+            schema.subschemas = None;
+        }
+    }
+}
+
+fn normalize_byte_arrays(schema: &mut SchemaObject) {
+    let is_byte_array = schema.instance_type == Some(SingleOrVec::Single(Box::new(InstanceType::Array)))
+        && matches!(
+            schema.array.as_ref().and_then(|a| a.items.as_ref()),
+            Some(SingleOrVec::Single(items)) if matches!(
+                items.as_ref(),
+                Schema::Object(SchemaObject { format: Some(format), .. }) if format == "uint8"
+            )
+        );
+    let is_binary_string = schema.instance_type == Some(SingleOrVec::Single(Box::new(InstanceType::String)))
+        && schema.format.as_deref() == Some("binary");
+
+    if is_byte_array || is_binary_string {
+        schema.instance_type = Some(SingleOrVec::Single(Box::new(InstanceType::String)));
+        schema.format = Some("byte".into());
+        schema.array = None;
+    }
+}
+
+fn nullable_type_arrays(schema: &mut SchemaObject) {
+    let Some(SingleOrVec::Single(tpe)) = schema.instance_type.clone() else {
+        return;
+    };
+    if schema.extensions.get("nullable") != Some(&Value::Bool(true)) {
+        return;
+    }
+    schema.remove_extension("nullable");
+    schema.instance_type = Some(SingleOrVec::Vec(vec![*tpe, InstanceType::Null]));
+    if let Some(enum_values) = &mut schema.enum_values {
+        if !enum_values.contains(&Value::Null) {
+            enum_values.push(Value::Null);
+        }
+    }
+}
+
+fn bound_enum_values(
+    schema: &SchemaObject,
+    pointer: &str,
+    max_length: u64,
+) -> Result<(), SchemaRewriteError> {
+    let too_long = schema
+        .enum_values
+        .iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .find(|value| value.chars().count() as u64 > max_length);
+    match too_long {
+        Some(value) => Err(SchemaRewriteError::EnumValueTooLong {
+            path: pointer.into(),
+            value: value.into(),
+            max_length,
+        }),
+        None => Ok(()),
+    }
+}
+
+fn bound_map_keys(schema: &mut SchemaObject, max_length: u64) {
+    let Some(object) = &schema.object else {
+        return;
+    };
+    if !matches!(object.additional_properties.as_deref(), Some(Schema::Object(_))) {
+        return;
+    }
+    let max_length = match object.property_names.as_deref() {
+        Some(Schema::Object(property_names)) => property_names
+            .extensions
+            .get("maxLength")
+            .and_then(Value::as_u64)
+            .unwrap_or(max_length),
+        _ => max_length,
+    };
+    let has_key_rule = matches!(
+        schema.extensions.get("x-kubernetes-validations"),
+        Some(Value::Array(rules)) if rules.iter().any(|rule| is_map_key_rule(&rule["rule"]))
+    );
+    if !has_key_rule {
+        schema.add_validation(
+            Rule::new(format!("self.all(k, size(k) <= {max_length})"))
+                .message(format!("keys must be at most {max_length} characters long").as_str()),
+        );
+    }
+}
+
+/// Whether `rule` is the validation rule added by [`StructuralSchemaRewriterBuilder::map_key_max_length`]
+fn is_map_key_rule(rule: &Value) -> bool {
+    rule.as_str()
+        .and_then(|rule| rule.strip_prefix("self.all(k, size(k) <= "))
+        .and_then(|rule| rule.strip_suffix(')'))
+        .is_some_and(|max_length| max_length.parse::<u64>().is_ok())
+}
+
+/// Apply `policy` to the properties of `schema`
+///
+/// Properties are only rewritten once their own subschemas have been rewritten, so this also has to express
+/// nullability as type arrays if [`StructuralSchemaRewriterBuilder::nullable_type_arrays`] is enabled.
+fn nullable_policy(schema: &mut SchemaObject, policy: NullablePolicy, type_arrays: bool) {
+    let Some(object) = &mut schema.object else {
+        return;
+    };
+    let required = object.required.clone().unwrap_or_default();
+    for (name, property) in object.properties.iter_mut().flatten() {
+        let Schema::Object(property) = property else {
+            continue;
+        };
+        let mut types = match &property.instance_type {
+            Some(SingleOrVec::Vec(types)) => types.clone(),
+            _ => Vec::new(),
+        };
+        if !required.contains(name) {
+            // Fields with a non-null default (such as `#[serde(default)] replicas: u32`) can't deserialize
+            // `null`
+            let has_default = property
+                .metadata
+                .as_ref()
+                .and_then(|metadata| metadata.default.as_ref())
+                .is_some_and(|default| !default.is_null());
+            if types.contains(&InstanceType::Null) || has_default {
+                continue;
+            }
+            property.set_extension("nullable", true.into());
+            if let Some(enum_values) = &mut property.enum_values {
+                if !enum_values.contains(&Value::Null) {
+                    enum_values.push(Value::Null);
+                }
+            }
+            if type_arrays {
+                nullable_type_arrays(property);
+            }
+        } else if policy == NullablePolicy::NullableIffOptional {
+            property.remove_extension("nullable");
+            if types.contains(&InstanceType::Null) {
+                types.retain(|tpe| *tpe != InstanceType::Null);
+                property.instance_type = match <[_; 1]>::try_from(types) {
+                    Ok([tpe]) => Some(SingleOrVec::Single(Box::new(tpe))),
+                    Err(types) => Some(SingleOrVec::Vec(types)),
+                };
+            }
+            if let Some(enum_values) = &mut property.enum_values {
+                enum_values.retain(|value| !value.is_null());
+            }
+        }
+    }
+}
+
+fn strip_descriptions(schema: &mut SchemaObject) {
+    if let Some(metadata) = &mut schema.metadata {
+        metadata.description = None;
+    }
+}
+
+fn compact(schema: &mut SchemaObject) {
+    for annotation in ["title", "$comment", "externalDocs"] {
+        schema.remove_extension(annotation);
+    }
+
+    if let Some(enum_values) = &mut schema.enum_values {
+        let mut seen = Vec::with_capacity(enum_values.len());
+        enum_values.retain(|value| {
+            let duplicate = seen.contains(value);
+            if !duplicate {
+                seen.push(value.clone());
+            }
+            !duplicate
+        });
+    }
+
+    // Inline junctors wrapping a single schema, unless its keywords conflict with those of the wrapper
+    let Ok(Value::Object(mut object)) = serde_json::to_value(&*schema) else {
+        return;
+    };
+    let mut changed = false;
+    for junctor in ["allOf", "anyOf", "oneOf"] {
+        let inner = match object.get(junctor) {
+            Some(Value::Array(inner)) if inner.len() == 1 => match &inner[0] {
+                Value::Object(inner) => inner.clone(),
+                _ => continue,
+            },
+            _ => continue,
+        };
+        let mut merged = object.clone();
+        merged.remove(junctor);
+        if merge_junctor_keywords(&mut merged, inner) {
+            object = merged;
+            changed = true;
+        }
+    }
+    if changed {
+        if let Ok(compacted) = serde_json::from_value(Value::Object(object)) {
+            *schema = compacted;
+        }
+    }
+}
+
+/// Merge the keywords of a schema wrapped in a single-element junctor into its wrapper
+///
+/// `properties` are merged and `required` properties are combined, while the wrapper's annotations (such as
+/// its `description`) take precedence. Returns `false` if any other keyword is defined differently by both, in
+/// which case merging them would change what the schema accepts.
+fn merge_junctor_keywords(
+    object: &mut serde_json::Map<String, Value>,
+    inner: serde_json::Map<String, Value>,
+) -> bool {
+    for (keyword, value) in inner {
+        let Some(existing) = object.get_mut(&keyword) else {
+            object.insert(keyword, value);
+            continue;
+        };
+        if *existing == value || keyword == "default" || ANNOTATION_KEYWORDS.contains(&keyword.as_str()) {
+            continue;
+        }
+        match (keyword.as_str(), existing, value) {
+            ("properties", Value::Object(properties), Value::Object(more)) => {
+                for (name, property) in more {
+                    match properties.get(&name) {
+                        Some(existing) if *existing != property => return false,
+                        Some(_) => {}
+                        None => {
+                            properties.insert(name, property);
+                        }
+                    }
+                }
+            }
+            ("required", Value::Array(required), Value::Array(more)) => {
+                for name in more {
+                    if !required.contains(&name) {
+                        required.push(name);
+                    }
+                }
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// Bring all plain enum values up to the root schema,
+/// since Kubernetes doesn't allow subschemas to define enum options.
+///
+/// (Enum here means a list of hard-coded values, not a tagged union.)
+fn hoist_subschema_enum_values(
+    subschemas: &mut Vec<Schema>,
+    common_enum_values: &mut Option<Vec<serde_json::Value>>,
+    instance_type: &mut Option<SingleOrVec<InstanceType>>,
+    pointer: &str,
+) -> Result<(), SchemaRewriteError> {
+    for variant in subschemas.iter() {
+        if let Schema::Object(SchemaObject {
+            instance_type: Some(variant_type),
+            enum_values: Some(_),
+            ..
+        }) = variant
+        {
+            check_variant_type(instance_type, variant, pointer)?;
+            instance_type.get_or_insert_with(|| variant_type.clone());
+        }
+    }
+    subschemas.retain(|variant| {
+        if let Schema::Object(SchemaObject {
+            enum_values: Some(variant_enum_values),
+            ..
+        }) = variant
+        {
+            // `oneOf` and `enum` aren't necessarily mutually exclusive, so values that are already listed
+            // by the parent are kept first, followed by the new values of each variant
+            let common_enum_values = common_enum_values.get_or_insert_with(Vec::new);
+            for value in variant_enum_values.iter() {
+                if !common_enum_values.contains(value) {
+                    common_enum_values.push(value.clone());
+                }
+            }
+            false
+        } else {
+            true
+        }
+    });
+    Ok(())
+}
+
+/// Fail if `variant` has a different `type` than the schema that it is merged into
+fn check_variant_type(
+    instance_type: &Option<SingleOrVec<InstanceType>>,
+    variant: &Schema,
+    pointer: &str,
+) -> Result<(), SchemaRewriteError> {
+    if let (
+        Some(expected),
+        Schema::Object(SchemaObject {
+            instance_type: Some(found),
+            ..
+        }),
+    ) = (instance_type, variant)
+    {
+        if expected != found {
+            return Err(SchemaRewriteError::Hoist {
+                path: pointer.into(),
+                source: Box::new(HoistError::MismatchedTypes {
+                    expected: serde_json::to_value(expected).unwrap_or_default(),
+                    found: serde_json::to_value(found).unwrap_or_default(),
+                    variant: serde_json::to_value(variant).unwrap_or_default(),
+                }),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Bring all property definitions from subschemas up to the root schema,
+/// since Kubernetes doesn't allow subschemas to define properties.
+fn hoist_subschema_properties(
+    subschemas: &mut Vec<Schema>,
+    common_obj: &mut Option<Box<ObjectValidation>>,
+    instance_type: &mut Option<SingleOrVec<InstanceType>>,
+    pointer: &str,
+    copy_descriptions: bool,
+    merge_optional: bool,
+) -> Result<(), SchemaRewriteError> {
+    // How many variants define each property, regardless of the order of the variants
+    let mut variants_by_property = BTreeMap::<String, usize>::new();
+    for variant in subschemas.iter() {
+        if let Schema::Object(SchemaObject {
+            object: Some(variant_obj),
+            ..
+        }) = variant
+        {
+            for property_name in variant_obj.properties.iter().flat_map(|p| p.keys()) {
+                *variants_by_property.entry(property_name.clone()).or_default() += 1;
+            }
+        }
+    }
+
+    for variant in subschemas {
+        if matches!(variant, Schema::Object(SchemaObject { object: Some(_), .. })) {
+            check_variant_type(instance_type, variant, pointer)?;
+        }
+        if let Schema::Object(variant_schema @ SchemaObject { object: Some(_), .. }) = variant {
+            let description = variant_schema
+                .metadata
+                .as_ref()
+                .and_then(|m| m.description.clone());
+            if let Some(description) = description.filter(|_| copy_descriptions) {
+                variant_schema.set_extension(VARIANT_DESCRIPTION, description.into());
+            }
+        }
+        // Rendered before its properties are moved, in case they conflict with those of other variants
+        let variant_value = match variant {
+            Schema::Object(SchemaObject { object: Some(_), .. }) => serde_json::to_value(&*variant).ok(),
+            _ => None,
+        };
+        if let Schema::Object(SchemaObject {
+            instance_type: variant_type,
+            object: Some(variant_obj),
+            metadata: variant_metadata,
+            ..
+        }) = variant
+        {
+            let common_obj = common_obj.get_or_insert_with(Box::<ObjectValidation>::default);
+
+            if let Some(variant_metadata) = variant_metadata {
+                // Move enum variant description (and deprecation) from oneOf clause to its corresponding
+                // property, unless other variants share the property (which would make its schemas differ)
+                let description = std::mem::take(&mut variant_metadata.description);
+                let deprecated = std::mem::take(&mut variant_metadata.deprecated);
+                if description.is_some() || deprecated {
+                    if let Some((_, Schema::Object(variant_object))) =
+                        only_item(variant_obj.properties.iter_mut().flatten())
+                            .filter(|(property_name, _)| variants_by_property[*property_name] == 1)
+                    {
+                        let metadata = variant_object
+                            .metadata
+                            .get_or_insert_with(Box::<Metadata>::default);
+                        if description.is_some() {
+                            metadata.description = description;
+                        }
+                        metadata.deprecated |= deprecated;
+                    }
+                }
+            }
+
+            // Move all properties
+            let variant_properties = variant_obj.properties.take().unwrap_or_default();
+            for (property_name, property) in variant_properties {
+                match common_obj
+                    .properties
+                    .get_or_insert_with(PropertyMap::new)
+                    .entry(property_name)
+                {
+                    Entry::Vacant(entry) => {
+                        entry.insert(property);
+                    }
+                    Entry::Occupied(mut entry) => {
+                        if &property != entry.get()
+                            && !merge_discriminator(entry.get_mut(), &property)
+                            && !(merge_optional && merge_nullable(entry.get_mut(), &property))
+                        {
+                            if is_discriminator(entry.get()) || is_discriminator(&property) {
+                                return Err(SchemaRewriteError::DiscriminatorCollision {
+                                    path: pointer.into(),
+                                    property: entry.key().clone(),
+                                });
+                            }
+                            return Err(SchemaRewriteError::Hoist {
+                                path: pointer.into(),
+                                source: Box::new(HoistError::MismatchedProperty {
+                                    property: entry.key().clone(),
+                                    expected: serde_json::to_value(entry.get()).unwrap_or_default(),
+                                    found: serde_json::to_value(&property).unwrap_or_default(),
+                                    variant: variant_value.unwrap_or_default(),
+                                }),
+                            });
+                        }
+                    }
+                }
+            }
+
+            // Kubernetes doesn't allow variants to set additionalProperties
+            variant_obj.additional_properties = None;
+
+            merge_metadata(instance_type, variant_type.take());
+        }
+    }
+    Ok(())
+}
+
+/// Whether hoisting `variants` drops any of their descriptions
+///
+/// The description of a variant is kept if it moves to the variant's only property (see
+/// [`hoist_subschema_properties`]), or if descriptions are copied. Other descriptions are dropped, such as
+/// those of unit variants, or of variants whose properties are shared with other variants.
+fn drops_variant_descriptions(variants: &[Schema], copy_descriptions: bool) -> bool {
+    let mut variants_by_property = BTreeMap::<&str, usize>::new();
+    for variant in variants {
+        if let Schema::Object(SchemaObject {
+            object: Some(object), ..
+        }) = variant
+        {
+            for property_name in object.properties.iter().flat_map(|p| p.keys()) {
+                *variants_by_property.entry(property_name).or_default() += 1;
+            }
+        }
+    }
+    variants.iter().any(|variant| {
+        let Schema::Object(SchemaObject {
+            metadata: Some(metadata),
+            object,
+            ..
+        }) = variant
+        else {
+            return false;
+        };
+        if metadata.description.is_none() {
+            return false;
+        }
+        match object {
+            Some(_) if copy_descriptions => false,
+            Some(object) => only_item(object.properties.iter().flat_map(|p| p.keys()))
+                .is_none_or(|property_name| variants_by_property[property_name.as_str()] > 1),
+            None => true,
+        }
+    })
+}
+
+/// Whether the schema looks like the tag of an internally tagged enum, meaning a string `enum`
+fn is_discriminator(schema: &Schema) -> bool {
+    let Schema::Object(schema) = schema else {
+        return false;
+    };
+    let is_string =
+        matches!(&schema.instance_type, Some(SingleOrVec::Single(tpe)) if **tpe == InstanceType::String);
+    is_string
+        && schema
+            .enum_values
+            .as_ref()
+            .is_some_and(|values| values.iter().all(Value::is_string))
+}
+
+/// Merge the schemas of an internally tagged enum's tag property, which only differ in their `enum` values
+///
+/// Returns `false` (leaving `common` untouched) if the schemas differ in any other way.
+fn merge_discriminator(common: &mut Schema, variant: &Schema) -> bool {
+    let (Schema::Object(common), Schema::Object(variant)) = (common, variant) else {
+        return false;
+    };
+    let Some(variant_values) = &variant.enum_values else {
+        return false;
+    };
+    let without_enum_values = |schema: &SchemaObject| SchemaObject {
+        enum_values: None,
+        ..schema.clone()
+    };
+    if common.enum_values.is_none() || without_enum_values(common) != without_enum_values(variant) {
+        return false;
+    }
+    let common_values = common.enum_values.get_or_insert_with(Vec::new);
+    for value in variant_values {
+        if !common_values.contains(value) {
+            common_values.push(value.clone());
+        }
+    }
+    true
+}
+
+/// Merge a property that is optional in some variants and required in others
+///
+/// The optional property is `nullable`, which is the only difference between the schemas. The merged
+/// property is nullable, since the `required` properties of each variant are kept in its subschema anyway.
+///
+/// This only applies to untagged enums (`anyOf`). The variants of a tagged enum are chosen by the tag rather
+/// than by their fields, so a nullable merged property would accept `null` for variants that require it.
+fn merge_nullable(common: &mut Schema, variant: &Schema) -> bool {
+    let (Schema::Object(common), Schema::Object(variant)) = (common, variant) else {
+        return false;
+    };
+    let without_nullable = |schema: &SchemaObject| {
+        let mut schema = schema.clone();
+        schema.remove_extension("nullable");
+        schema
+    };
+    if without_nullable(common) != without_nullable(variant) {
+        return false;
+    }
+    common.set_extension("nullable", true.into());
+    true
+}
+
+/// Whether the subschemas define conflicting types (for example an array and an object),
+/// in which case they can't be merged into a single schema.
+fn has_mixed_instance_types(subschemas: &[Schema]) -> bool {
+    let mut types = subschemas.iter().filter_map(|variant| match variant {
+        Schema::Object(SchemaObject {
+            instance_type: Some(instance_type),
+            ..
+        }) => Some(instance_type),
+        _ => None,
+    });
+    match types.next() {
+        Some(first) => types.any(|tpe| tpe != first),
+        None => false,
+    }
+}
+
+/// Whether some subschemas are plain enums while others define properties,
+/// in which case merging them would produce a node with both `enum` and `properties`.
+fn mixes_enum_values_and_properties(subschemas: &[Schema]) -> bool {
+    let has_enum_values = subschemas.iter().any(|variant| {
+        matches!(
+            variant,
+            Schema::Object(SchemaObject {
+                enum_values: Some(_),
+                ..
+            })
+        )
+    });
+    let has_properties = subschemas.iter().any(|variant| {
+        matches!(variant, Schema::Object(SchemaObject { object: Some(object), .. }) if object.has_properties())
+    });
+    has_enum_values && has_properties
+}
+
+/// Call `f` with `schema` and every subschema nested within it, along with their JSON pointers
+/// (relative to `schema`), parents before children.
+fn for_each_subschema(schema: &Value, pointer: &str, f: &mut dyn FnMut(&str, &Value)) {
+    f(pointer, schema);
+    for (path, subschema) in subschemas(schema) {
+        for_each_subschema(subschema, &format!("{pointer}{path}"), f);
+    }
+}
+
+/// Call `f` with every subschema nested within `schema` and then `schema` itself, along with their JSON
+/// pointers (relative to `schema`), children before parents.
+///
+/// Subschemas marked with [`SKIP_REWRITE`] are skipped, along with everything nested within them.
+/// Stops at the first error returned by `f`.
+fn for_each_subschema_mut(
+    schema: &mut Value,
+    pointer: &str,
+    f: &mut dyn FnMut(&str, &mut Value) -> Result<(), SchemaRewriteError>,
+) -> Result<(), SchemaRewriteError> {
+    if is_skipped(schema) {
+        return Ok(());
+    }
+    for (path, subschema) in subschemas_mut(schema) {
+        for_each_subschema_mut(subschema, &format!("{pointer}{path}"), f)?;
+    }
+    f(pointer, schema)
+}
+
+/// The immediate subschemas of `schema`, along with their JSON pointers relative to `schema`
+///
+/// Covers the same keywords as [`schemars::transform::transform_subschemas`].
+fn subschemas(schema: &Value) -> Vec<(String, &Value)> {
+    let mut subschemas = Vec::new();
+    for (keyword, value) in schema.as_object().into_iter().flatten() {
+        match (keyword.as_str(), value) {
+            ("properties" | "patternProperties" | "$defs" | "definitions", Value::Object(named)) => {
+                for (name, subschema) in named {
+                    subschemas.push((format!("/{keyword}/{}", escape_pointer(name)), subschema));
+                }
+            }
+            ("allOf" | "anyOf" | "oneOf" | "prefixItems" | "items", Value::Array(list)) => {
+                for (i, subschema) in list.iter().enumerate() {
+                    subschemas.push((format!("/{keyword}/{i}"), subschema));
+                }
+            }
+            (
+                "items"
+                | "additionalItems"
+                | "additionalProperties"
+                | "contains"
+                | "propertyNames"
+                | "not"
+                | "if"
+                | "then"
+                | "else",
+                _,
+            ) => subschemas.push((format!("/{keyword}"), value)),
+            _ => {}
+        }
+    }
+    subschemas
+}
+
+/// Mutable version of [`subschemas`]
+fn subschemas_mut(schema: &mut Value) -> Vec<(String, &mut Value)> {
+    let mut subschemas = Vec::new();
+    for (keyword, value) in schema.as_object_mut().into_iter().flatten() {
+        match (keyword.as_str(), value) {
+            ("properties" | "patternProperties" | "$defs" | "definitions", Value::Object(named)) => {
+                for (name, subschema) in named.iter_mut() {
+                    subschemas.push((format!("/{keyword}/{}", escape_pointer(name)), subschema));
+                }
+            }
+            ("allOf" | "anyOf" | "oneOf" | "prefixItems" | "items", Value::Array(list)) => {
+                for (i, subschema) in list.iter_mut().enumerate() {
+                    subschemas.push((format!("/{keyword}/{i}"), subschema));
+                }
+            }
+            (
+                "items"
+                | "additionalItems"
+                | "additionalProperties"
+                | "contains"
+                | "propertyNames"
+                | "not"
+                | "if"
+                | "then"
+                | "else",
+                value,
+            ) => subschemas.push((format!("/{keyword}"), value)),
+            _ => {}
+        }
+    }
+    subschemas
+}
+
+/// Inline all references to definitions within `schema`, since Kubernetes doesn't support `$ref`
+///
+/// Keywords set next to the reference (such as the `description` of the field that uses the referenced type)
+/// take precedence over the keywords of the referenced schema, except for `properties` and `required`, which are
+/// merged (such as the tag of an internally tagged enum, next to the struct of a variant). References to definitions that are
+/// (directly or indirectly) recursive can't be inlined, and are left as they are, unless
+/// [`StructuralSchemaRewriterBuilder::break_cycles`] is enabled. Properties set next to the reference that the
+/// referenced schema defines differently (such as a tag with the same name as a field of the variant) are
+/// rejected.
+fn inline_references(
+    schema: &mut Value,
+    rewriter: &StructuralSchemaRewriter,
+    warnings: &mut Vec<String>,
+) -> Result<(), SchemaRewriteError> {
+    let root = schema.clone();
+    if rewriter.source_types {
+        if let (Some(Value::String(title)), Some(object)) = (root.get("title"), schema.as_object_mut()) {
+            object.insert(SOURCE_TYPE.into(), title.clone().into());
+        }
+    }
+    let mut definitions = Vec::new();
+    if rewriter.break_cycles {
+        // All references can be inlined, so cycles don't need to be broken within the definitions themselves
+        remove_definitions(schema);
+        definitions = stripped_definitions(&root);
+    }
+    if inline_references_in(
+        schema,
+        &root,
+        &definitions,
+        "",
+        &mut Vec::new(),
+        rewriter,
+        warnings,
+    )? {
+        remove_definitions(schema);
+    }
+    Ok(())
+}
+
+fn remove_definitions(schema: &mut Value) {
+    if let Some(object) = schema.as_object_mut() {
+        object.remove("$defs");
+        object.remove("definitions");
+        if let Some(Value::Object(components)) = object.get_mut("components") {
+            components.remove("schemas");
+            if components.is_empty() {
+                object.remove("components");
+            }
+        }
+    }
+}
+
+/// Returns whether all references could be inlined
+///
+/// `definitions` are the definitions of `root` as listed by [`stripped_definitions`], if cycles are broken.
+fn inline_references_in(
+    schema: &mut Value,
+    root: &Value,
+    definitions: &[(String, Value)],
+    pointer: &str,
+    stack: &mut Vec<String>,
+    rewriter: &StructuralSchemaRewriter,
+    warnings: &mut Vec<String>,
+) -> Result<bool, SchemaRewriteError> {
+    if is_skipped(schema) {
+        // References within skipped subschemas still need their definitions
+        let mut has_references = false;
+        for_each_subschema(schema, "", &mut |_, subschema| {
+            has_references |= subschema.get("$ref").is_some();
+        });
+        return Ok(!has_references);
+    }
+    if let Some(Value::String(title)) = schema.get("title") {
+        // Inlined subschemas can only be recognized by their title
+        if rewriter.opaque_types.contains(title) {
+            *schema = opaque_schema(schema);
+            return Ok(true);
+        }
+    }
+    let mut resolved = true;
+    if let Some(reference) = wrapped_reference(schema) {
+        match root.pointer(reference.trim_start_matches('#')) {
+            Some(target) if !stack.contains(&reference) => {
+                let name = reference.rsplit('/').next().unwrap_or_default().to_string();
+                let mut target = if rewriter.opaque_types.contains(&name) {
+                    opaque_schema(target)
+                } else {
+                    target.clone()
+                };
+                stack.push(reference);
+                let inlined =
+                    inline_references_in(&mut target, root, definitions, pointer, stack, rewriter, warnings);
+                stack.pop();
+                resolved &= inlined?;
+                if let (true, Value::Object(target)) = (rewriter.source_types, &mut target) {
+                    target.insert(SOURCE_TYPE.into(), name.into());
+                }
+
+                if let (Some(wrapper), Value::Object(target)) = (schema.as_object_mut(), &mut target) {
+                    wrapper.remove("$ref");
+                    wrapper.remove("allOf");
+                    let mut keywords = Value::Object(std::mem::take(wrapper));
+                    resolved &= inline_references_in(
+                        &mut keywords,
+                        root,
+                        definitions,
+                        pointer,
+                        stack,
+                        rewriter,
+                        warnings,
+                    )?;
+                    for (keyword, value) in keywords.as_object_mut().map(std::mem::take).unwrap_or_default() {
+                        // Tags of internally tagged enums are added next to the reference to the variant
+                        match (keyword.as_str(), target.get_mut(&keyword), value) {
+                            ("properties", Some(Value::Object(properties)), Value::Object(more)) => {
+                                for (name, property) in more {
+                                    match properties.get(&name) {
+                                        Some(existing) if *existing != property => {
+                                            return Err(SchemaRewriteError::DiscriminatorCollision {
+                                                path: pointer.into(),
+                                                property: name,
+                                            });
+                                        }
+                                        _ => {
+                                            properties.insert(name, property);
+                                        }
+                                    }
+                                }
+                            }
+                            ("required", Some(Value::Array(required)), Value::Array(more)) => {
+                                for name in more {
+                                    if !required.contains(&name) {
+                                        required.push(name);
+                                    }
+                                }
+                            }
+                            (_, _, value) => {
+                                target.insert(keyword, value);
+                            }
+                        }
+                    }
+                }
+                *schema = target;
+                // The target has already been inlined, going over it again would inline unresolved
+                // (recursive) references once more, indefinitely
+                return Ok(resolved);
+            }
+            Some(target) if rewriter.break_cycles => {
+                if let Some(wrapper) = schema.as_object_mut() {
+                    wrapper.remove("$ref");
+                    wrapper.remove("allOf");
+                    if let (false, Some(description)) =
+                        (wrapper.contains_key("description"), target.get("description"))
+                    {
+                        wrapper.insert("description".into(), description.clone());
+                    }
+                    wrapper.insert("x-kubernetes-preserve-unknown-fields".into(), true.into());
+                }
+                warnings.push(format!(
+                    "the recursive reference to {reference:?} at {pointer:?} was replaced by \
+                     `x-kubernetes-preserve-unknown-fields`"
+                ));
+                return Ok(true);
+            }
+            _ => return Ok(false),
+        }
+    }
+    // Generators that inline subschemas (such as kube-derive's) also inline the outermost occurrence of a
+    // recursive type, whose references back to itself are the ones that close the cycle
+    let inlined = inlined_definition(schema, definitions).filter(|reference| !stack.contains(reference));
+    let inlined = inlined.map(|reference| stack.push(reference)).is_some();
+    for (path, subschema) in subschemas_mut(schema) {
+        let pointer = format!("{pointer}{path}");
+        resolved &= inline_references_in(subschema, root, definitions, &pointer, stack, rewriter, warnings)?;
+    }
+    if inlined {
+        stack.pop();
+    }
+    Ok(resolved)
+}
+
+/// The references to the definitions of `root`, along with the definitions without their annotations (see
+/// [`without_annotations`]), leaving out definitions that don't constrain anything
+fn stripped_definitions(root: &Value) -> Vec<(String, Value)> {
+    ["/$defs", "/definitions", "/components/schemas"]
+        .into_iter()
+        .filter_map(|path| Some((path, root.pointer(path)?.as_object()?)))
+        .flat_map(|(path, definitions)| definitions.iter().map(move |definition| (path, definition)))
+        .map(|(path, (name, definition))| {
+            let reference = format!("#{path}/{}", escape_pointer(name));
+            (reference, without_annotations(definition))
+        })
+        .filter(|(_, definition)| {
+            definition
+                .as_object()
+                .is_some_and(|keywords| !keywords.is_empty())
+        })
+        .collect()
+}
+
+/// The reference to the definition (of [`stripped_definitions`]) that `schema` is a copy of, apart from its
+/// annotations
+fn inlined_definition(schema: &Value, definitions: &[(String, Value)]) -> Option<String> {
+    // Only schemas with the same type and properties as a definition are compared in full, which needs a
+    // stripped copy of the schema
+    fn shape(schema: &Value) -> (Option<&Value>, Option<BTreeSet<&String>>) {
+        let properties = schema.get("properties").and_then(Value::as_object);
+        (
+            schema.get("type"),
+            properties.map(|properties| properties.keys().collect()),
+        )
+    }
+    let schema_shape = shape(schema);
+    let mut candidates = definitions
+        .iter()
+        .filter(|(_, definition)| shape(definition) == schema_shape)
+        .peekable();
+    candidates.peek()?;
+    let schema = without_annotations(schema);
+    candidates
+        .find(|(_, definition)| *definition == schema)
+        .map(|(reference, _)| reference.clone())
+}
+
+/// Remove the annotations and `nullable` from `schema` and all of its subschemas, and replace the schemas
+/// that only allow `null` with `null`, since generators may describe them differently for the same type
+fn without_annotations(schema: &Value) -> Value {
+    fn strip(schema: &mut Value) {
+        if is_null_value(schema) {
+            *schema = Value::Null;
+            return;
+        }
+        if let Some(keywords) = schema.as_object_mut() {
+            keywords.retain(|keyword, _| {
+                !ANNOTATION_KEYWORDS.contains(&keyword.as_str()) && keyword != "nullable"
+            });
+        }
+        for (_, subschema) in subschemas_mut(schema) {
+            strip(subschema);
+        }
+    }
+    let mut schema = schema.clone();
+    strip(&mut schema);
+    schema
+}
+
+/// Replace the structure of `schema` with `x-kubernetes-preserve-unknown-fields`,
+/// see [`StructuralSchemaRewriterBuilder::opaque_type`]
+fn opaque_schema(schema: &Value) -> Value {
+    let mut opaque = serde_json::json!({ "x-kubernetes-preserve-unknown-fields": true });
+    for keyword in ["description", "nullable"] {
+        if let Some(value) = schema.get(keyword) {
+            opaque[keyword] = value.clone();
+        }
+    }
+    opaque
+}
+
+/// The local reference of a `{ "$ref": ... }` schema, or an `{ "allOf": [{ "$ref": ... }] }` wrapper
+fn wrapped_reference(schema: &Value) -> Option<String> {
+    let reference = match schema.get("allOf") {
+        Some(Value::Array(all_of)) if all_of.len() == 1 && all_of[0].as_object()?.len() == 1 => {
+            all_of[0].get("$ref")
+        }
+        _ => schema.get("$ref"),
+    }?;
+    reference
+        .as_str()
+        .filter(|r| r.starts_with("#/"))
+        .map(String::from)
+}
+
+/// Escape a single JSON pointer segment, according to [RFC 6901](https://www.rfc-editor.org/rfc/rfc6901#section-3)
+fn escape_pointer(segment: &str) -> String {
+    segment.replace('~', "~0").replace('/', "~1")
+}
+
+fn only_item<I: Iterator>(mut i: I) -> Option<I::Item> {
+    let item = i.next()?;
+    if i.next().is_some() {
+        return None;
+    }
+    Some(item)
+}
+
+fn merge_metadata(
+    instance_type: &mut Option<SingleOrVec<InstanceType>>,
+    variant_type: Option<SingleOrVec<InstanceType>>,
+) {
+    match (instance_type, variant_type) {
+        (_, None) => {}
+        (common_type @ None, variant_type) => {
+            *common_type = variant_type;
+        }
+        // Conflicting types are rejected by `check_variant_type`
+        (Some(_), Some(_)) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        check_schema_size, check_structural, dual_schemas, dynamic_root_schema, for_each_subschema,
+        make_all_optional, mark_deprecated, preserve_unknown_fields_at, remove_property, rename_properties,
+        rewrite_existing_schema, rewrite_schema_json, strip_defaults_at, strip_source_types,
+        to_json_schema_props, FieldValidations, HoistError, NullablePolicy, SchemaRewriteError,
+        StructuralSchemaRewriter,
+    };
+    use crate::{Message, Reason, Rule};
+    use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+        JSONSchemaProps, JSONSchemaPropsOrArray,
+    };
+    use schemars::{generate::SchemaSettings, transform::Transform, JsonSchema};
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+    use std::collections::{BTreeMap, BTreeSet};
+
+    /// Generate a schema the same way `#[derive(CustomResource)]` does
+    pub(crate) fn schema_for<T: JsonSchema>() -> Value {
+        schema_with_rewriter_for::<T>(StructuralSchemaRewriter::default())
+    }
+
+    /// Generate a schema like [`schema_for`], but with a custom `rewriter`
+    pub(crate) fn schema_with_rewriter_for<T: JsonSchema>(rewriter: StructuralSchemaRewriter) -> Value {
+        SchemaSettings::openapi3()
+            .with(|s| {
+                s.inline_subschemas = true;
+                s.meta_schema = None;
+            })
+            .with_transform(schemars::transform::AddNullable::default())
+            .with_transform(rewriter)
+            .into_generator()
+            .into_root_schema_for::<T>()
+            .to_value()
+    }
+
+    #[test]
+    fn untagged_enum_with_array_and_object_variants_preserves_unknown_fields() {
+        /// List or map
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(untagged)]
+        #[allow(dead_code)]
+        enum ListOrMap {
+            List(Vec<String>),
+            Map(BTreeMap<String, String>),
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            value: ListOrMap,
+        }
+
+        assert_eq!(
+            schema_for::<Spec>(),
+            json!({
+                "title": "Spec",
+                "type": "object",
+                "properties": {
+                    "value": {
+                        "description": "List or map",
+                        "x-kubernetes-preserve-unknown-fields": true,
+                    },
+                },
+                "required": ["value"],
+            })
+        );
+    }
+
+    #[test]
+    fn transform_steps_snapshots_every_transform() {
+        let mut schema = json!({
+            "type": "object",
+            "properties": {
+                "tagged": {
+                    "oneOf": [
+                        {
+                            "type": "object",
+                            "properties": { "a": { "type": "string" } },
+                            "required": ["a"],
+                        },
+                        {
+                            "type": "object",
+                            "properties": { "b": { "type": "string" } },
+                            "required": ["b"],
+                        },
+                    ],
+                },
+                "set": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "uniqueItems": true,
+                },
+            },
+        });
+        let mut expected = schemars::Schema::try_from(schema.clone()).unwrap();
+        StructuralSchemaRewriter::default().transform(&mut expected);
+
+        let steps = StructuralSchemaRewriter::default()
+            .transform_steps(&mut schema)
+            .unwrap();
+        assert_eq!(steps.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>(), [
+            "inline_references",
+            "reject_uninhabited_fields",
+            "reject_unsupported_keywords",
+            "const_values",
+            "int_or_string_types",
+            "null_only_fields",
+            "tuple_items",
+            "boolean_items",
+            "any_value_fields",
+            "merge_all_of_objects",
+            "hoist_one_of",
+            "hoist_any_of",
+            "unevaluated_properties",
+            "open_enums",
+            "nullable_enums",
+            "preserve_unknown_object_enums",
+            "preserve_unknown_flattened_maps",
+            "strip_unique_items",
+            "content_keywords",
+            "single_example",
+            "strip_empty_keywords",
+            "strip_markers",
+        ]);
+        assert_eq!(
+            steps[10].1.pointer("/properties/tagged/oneOf"),
+            Some(&json!([
+                { "required": ["a"] },
+                { "required": ["b"] },
+            ]))
+        );
+        assert_eq!(
+            steps[10].1.pointer("/properties/set/uniqueItems"),
+            Some(&json!(true))
+        );
+        assert_eq!(steps[18].1.pointer("/properties/set/uniqueItems"), None);
+        assert_eq!(schema, expected.to_value());
+    }
+
+    #[test]
+    fn transform_steps_report_errors_and_leave_the_schema_untouched() {
+        let mut schema = json!({
+            "type": "object",
+            "properties": { "tags": { "type": "array", "maxItems": "ten" } },
+        });
+        let original = schema.clone();
+        let err = StructuralSchemaRewriter::default()
+            .transform_steps(&mut schema)
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            SchemaRewriteError::InvalidSchema { pointer, .. } if pointer == "/properties/tags"
+        ));
+        assert_eq!(schema, original);
+
+        let mut schema = json!("not a schema");
+        assert!(StructuralSchemaRewriter::default()
+            .transform_steps(&mut schema)
+            .is_err());
+        assert_eq!(schema, json!("not a schema"));
+    }
+
+    #[test]
+    fn dual_schemas_only_rewrite_the_structural_schema() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Source {
+            Image { name: String },
+            Url { url: String },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            source: Source,
+        }
+
+        let (plain, structural) = dual_schemas::<Spec>();
+        assert_eq!(plain["properties"]["source"], json!({ "$ref": "#/$defs/Source" }));
+        assert_eq!(
+            plain["$defs"]["Source"]["oneOf"].as_array().map(Vec::len),
+            Some(2)
+        );
+        assert_eq!(structural, schema_for::<Spec>());
+        assert_eq!(
+            structural["properties"]["source"]["oneOf"],
+            json!([{ "required": ["Image"] }, { "required": ["Url"] }])
+        );
+        assert_eq!(structural["properties"]["source"]["type"], "object");
+        assert_eq!(check_structural(&structural), []);
+    }
+
+    #[test]
+    fn contains_is_rejected() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "containers": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "contains": { "type": "string", "enum": ["main"] },
+                    "minContains": 1,
+                    "maxContains": 1,
+                },
+            },
+        });
+        let err = rewrite_existing_schema(schema).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                SchemaRewriteError::UnsupportedKeyword { path, keyword }
+                    if path == "/properties/containers" && keyword == "contains"
+            ),
+            "{err:?}"
+        );
+
+        // Bounds without `contains` don't validate anything
+        let schema = json!({
+            "type": "array",
+            "items": { "type": "string" },
+            "minContains": 2,
+            "maxContains": 3,
+        });
+        assert_eq!(
+            rewrite_existing_schema(schema).unwrap(),
+            json!({ "type": "array", "items": { "type": "string" } })
+        );
+    }
+
+    #[test]
+    fn enum_of_objects_keeps_object_structure() {
+        fn fixed_endpoint(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+            schemars::json_schema!({
+                "oneOf": [
+                    { "enum": [{ "host": "a.example.com", "port": 80 }] },
+                    { "enum": [{ "host": "b.example.com", "port": 443 }] },
+                ],
+            })
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            #[schemars(schema_with = "fixed_endpoint")]
+            endpoint: Value,
+        }
+
+        assert_eq!(
+            schema_for::<Spec>(),
+            json!({
+                "title": "Spec",
+                "type": "object",
+                "properties": {
+                    "endpoint": {
+                        "type": "object",
+                        "enum": [
+                            { "host": "a.example.com", "port": 80 },
+                            { "host": "b.example.com", "port": 443 },
+                        ],
+                        "x-kubernetes-preserve-unknown-fields": true,
+                    },
+                },
+                "required": ["endpoint"],
+            })
+        );
+    }
+
+    #[test]
+    fn enum_of_arrays_keeps_array_type() {
+        fn fixed_args(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+            schemars::json_schema!({
+                "type": "array",
+                "items": { "type": "string" },
+                "enum": [["--verbose"], ["--quiet", "--no-color"]],
+            })
+        }
+        fn fixed_ports(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+            schemars::json_schema!({
+                "type": "array",
+                "items": { "type": "integer" },
+                "oneOf": [
+                    { "type": "array", "enum": [[80, 443]] },
+                    { "type": "array", "enum": [[8080]] },
+                ],
+            })
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            #[schemars(schema_with = "fixed_args")]
+            args: Vec<String>,
+            #[schemars(schema_with = "fixed_ports")]
+            ports: Vec<u16>,
+        }
+
+        let schema = schema_for::<Spec>();
+        assert_eq!(
+            schema,
+            json!({
+                "title": "Spec",
+                "type": "object",
+                "properties": {
+                    "args": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "enum": [["--verbose"], ["--quiet", "--no-color"]],
+                    },
+                    "ports": {
+                        "type": "array",
+                        "items": { "type": "integer" },
+                        "enum": [[80, 443], [8080]],
+                    },
+                },
+                "required": ["args", "ports"],
+            })
+        );
+        assert_eq!(check_structural(&schema), []);
+    }
+
+    #[test]
+    fn rewrite_existing_schema_hoists_legacy_enums() {
+        let legacy = json!({
+            "type": "object",
+            "properties": {
+                "mode": {
+                    "description": "How to run",
+                    "oneOf": [
+                        { "type": "string", "enum": ["Fast"], "description": "Quickly" },
+                        { "type": "string", "enum": ["Slow"], "description": "Carefully" },
+                    ],
+                },
+                "source": {
+                    "oneOf": [
+                        {
+                            "type": "object",
+                            "description": "From git",
+                            "properties": {
+                                "git": { "type": "object", "properties": { "url": { "type": "string" } } },
+                            },
+                            "required": ["git"],
+                        },
+                        {
+                            "type": "object",
+                            "description": "From a local path",
+                            "properties": {
+                                "path": { "type": "string" },
+                            },
+                            "required": ["path"],
+                        },
+                    ],
+                },
+            },
+        });
+
+        assert_eq!(
+            rewrite_existing_schema(legacy).unwrap(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "mode": {
+                        "description": "How to run",
+                        "type": "string",
+                        "enum": ["Fast", "Slow"],
+                    },
+                    "source": {
+                        "type": "object",
+                        "oneOf": [
+                            { "required": ["git"] },
+                            { "required": ["path"] },
+                        ],
+                        "properties": {
+                            "git": {
+                                "description": "From git",
+                                "type": "object",
+                                "properties": { "url": { "type": "string" } },
+                            },
+                            "path": {
+                                "description": "From a local path",
+                                "type": "string",
+                            },
+                        },
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn boolean_items_preserve_unknown_fields() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "values": { "type": "array", "items": true },
+            },
+        });
+        assert_eq!(
+            rewrite_existing_schema(schema).unwrap()["properties"]["values"],
+            json!({
+                "type": "array",
+                "items": { "x-kubernetes-preserve-unknown-fields": true },
+            })
+        );
+    }
+
+    #[test]
+    fn json_value_fields_preserve_unknown_fields() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            /// Passed on to the plugin as-is
+            config: serde_json::Value,
+            extra: Option<serde_json::Value>,
+            values: Vec<serde_json::Value>,
+        }
+
+        let schema = schema_for::<Spec>();
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "config": {
+                    "description": "Passed on to the plugin as-is",
+                    "x-kubernetes-preserve-unknown-fields": true,
+                },
+                "extra": { "x-kubernetes-preserve-unknown-fields": true },
+                "values": {
+                    "type": "array",
+                    "items": { "x-kubernetes-preserve-unknown-fields": true },
+                },
+            })
+        );
+        assert_eq!(check_structural(&schema), []);
+        // ..as do `true` schemas that weren't replaced by `{}`
+        let schema = json!({ "type": "object", "properties": { "config": true } });
+        assert_eq!(
+            rewrite_existing_schema(schema).unwrap()["properties"]["config"],
+            json!({ "x-kubernetes-preserve-unknown-fields": true })
+        );
+    }
+
+    #[test]
+    fn false_items_are_rejected() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "empty": { "type": "array", "items": false },
+            },
+        });
+        let err = rewrite_existing_schema(schema).unwrap_err();
+        assert!(
+            matches!(&err, SchemaRewriteError::UninhabitedField { path } if path == "/properties/empty/items"),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn recursive_references_can_be_broken() {
+        /// A node
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Node {
+            value: String,
+            children: Vec<Node>,
+            next: Option<Box<Node>>,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            tree: Node,
+        }
+
+        let generate = || {
+            SchemaSettings::openapi3()
+                .with(|s| {
+                    s.inline_subschemas = true;
+                    s.meta_schema = None;
+                })
+                .with_transform(schemars::transform::AddNullable::default())
+                .into_generator()
+                .into_root_schema_for::<Spec>()
+        };
+
+        // The recursive references are kept by default, which isn't structural
+        let mut schema = generate();
+        StructuralSchemaRewriter::default().transform(&mut schema);
+        assert_eq!(
+            schema.pointer("/properties/tree/properties/children/items/properties/children/items/$ref"),
+            Some(&json!("#/components/schemas/Node"))
+        );
+
+        let mut schema = generate();
+        let mut rewriter = StructuralSchemaRewriter::builder().break_cycles(true).build();
+        rewriter.try_transform(&mut schema).unwrap();
+        let schema = schema.to_value();
+        // The cycle is broken at the first reference back to the (inlined) tree
+        let tree = &schema["properties"]["tree"];
+        assert_eq!(tree["properties"]["value"], json!({ "type": "string" }));
+        assert_eq!(
+            tree["properties"]["children"]["items"],
+            json!({ "description": "A node", "x-kubernetes-preserve-unknown-fields": true })
+        );
+        assert_eq!(
+            tree["properties"]["next"],
+            json!({
+                "description": "A node",
+                "nullable": true,
+                "x-kubernetes-preserve-unknown-fields": true,
+            })
+        );
+        assert_eq!(schema.get("components"), None);
+        assert_eq!(check_structural(&schema), []);
+        assert_eq!(rewriter.warnings().len(), 2);
+        assert_eq!(
+            rewriter.warnings()[0],
+            "the recursive reference to \"#/components/schemas/Node\" at \
+             \"/properties/tree/properties/children/items\" was replaced by \
+             `x-kubernetes-preserve-unknown-fields`"
+        );
+
+        // The warnings can also be read from the rewriter of a generator
+        let mut generator = SchemaSettings::openapi3()
+            .with(|s| {
+                s.inline_subschemas = true;
+                s.meta_schema = None;
+            })
+            .with_transform(schemars::transform::AddNullable::default())
+            .with_transform(StructuralSchemaRewriter::builder().break_cycles(true).build())
+            .into_generator();
+        assert_eq!(generator.root_schema_for::<Spec>().to_value(), schema);
+        let rewriter = generator
+            .transforms_mut()
+            .find_map(|transform| transform.downcast_ref::<StructuralSchemaRewriter>())
+            .unwrap();
+        assert_eq!(rewriter.warnings().len(), 2);
+    }
+
+    #[test]
+    fn dynamic_references_are_rejected() {
+        let schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "$defs": {
+                "node": {
+                    "$dynamicAnchor": "node",
+                    "type": "object",
+                    "properties": {
+                        "children": { "type": "array", "items": { "$dynamicRef": "#node" } },
+                    },
+                },
+            },
+            "type": "object",
+            "properties": {
+                "tree": { "$ref": "#/$defs/node" },
+            },
+        });
+        let err = rewrite_existing_schema(schema).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                SchemaRewriteError::UnsupportedKeyword { path, keyword }
+                    if path == "/properties/tree/properties/children/items" && keyword == "$dynamicRef"
+            ),
+            "{err:?}"
+        );
+        assert_eq!(
+            err.to_string(),
+            "the keyword \"$dynamicRef\" at \"/properties/tree/properties/children/items\" is not supported by Kubernetes"
+        );
+    }
+
+    #[test]
+    fn try_transform_reports_subschemas_that_cannot_be_parsed() {
+        let original = json!({
+            "type": "object",
+            "properties": {
+                "tags": { "type": "array", "maxItems": "ten" },
+                "mode": {
+                    "oneOf": [{ "type": "string", "enum": ["A"] }, { "type": "string", "enum": ["B"] }],
+                },
+            },
+        });
+        let mut schema = schemars::Schema::try_from(original.clone()).unwrap();
+        let err = StructuralSchemaRewriter::default()
+            .try_transform(&mut schema)
+            .unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                SchemaRewriteError::InvalidSchema { pointer, .. } if pointer == "/properties/tags"
+            ),
+            "{err:?}"
+        );
+        assert!(err
+            .to_string()
+            .starts_with("invalid schema at \"/properties/tags\": "));
+        // Nothing is rewritten, not even the subschemas that could be parsed
+        assert_eq!(schema.to_value(), original);
+        assert!(matches!(
+            rewrite_existing_schema(original),
+            Err(SchemaRewriteError::InvalidSchema { pointer, .. }) if pointer == "/properties/tags"
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "failed to rewrite schema: invalid schema at \"/properties/tags\"")]
+    fn transform_panics_on_subschemas_that_cannot_be_parsed() {
+        let mut schema = schemars::Schema::try_from(json!({
+            "type": "object",
+            "properties": { "tags": { "type": "array", "maxItems": "ten" } },
+        }))
+        .unwrap();
+        StructuralSchemaRewriter::default().transform(&mut schema);
+    }
+
+    #[test]
+    fn rewrite_existing_schema_rejects_non_schemas() {
+        assert!(matches!(
+            rewrite_existing_schema(json!("not a schema")),
+            Err(SchemaRewriteError::InvalidSchema { .. })
+        ));
+    }
+
+    #[test]
+    fn merges_all_of_objects() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "description": "Composed of two flattened structs",
+                    "allOf": [
+                        {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "replicas": { "type": "integer" },
+                            },
+                            "required": ["name"],
+                        },
+                        {
+                            "type": "object",
+                            "properties": {
+                                "name": { "type": "string" },
+                                "image": { "type": "string" },
+                            },
+                            "required": ["image"],
+                        },
+                    ],
+                },
+            },
+        });
+        assert_eq!(
+            rewrite_existing_schema(schema).unwrap(),
+            json!({
+                "type": "object",
+                "properties": {
+                    "spec": {
+                        "description": "Composed of two flattened structs",
+                        "type": "object",
+                        "properties": {
+                            "image": { "type": "string" },
+                            "name": { "type": "string" },
+                            "replicas": { "type": "integer" },
+                        },
+                        "required": ["image", "name"],
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn merging_all_of_objects_rejects_conflicting_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "allOf": [
+                        {
+                            "type": "object",
+                            "properties": { "port": { "type": "integer" } },
+                        },
+                        {
+                            "type": "object",
+                            "properties": { "port": { "type": "string" } },
+                        },
+                    ],
+                },
+            },
+        });
+        let err = rewrite_existing_schema(schema).unwrap_err();
+        assert!(
+            matches!(
+                &err,
+                SchemaRewriteError::ConflictingProperty { path, property }
+                    if path == "/properties/spec" && property == "port"
+            ),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn json_schema_props_list_required_properties_sorted() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Container {
+            name: String,
+            image: String,
+            args: Vec<String>,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            replicas: u32,
+            containers: Vec<Container>,
+            affinity: Option<String>,
+        }
+
+        let required = |props: &JSONSchemaProps| props.required.clone().unwrap_or_default();
+        let props = to_json_schema_props(&schema_for::<Spec>()).unwrap();
+        assert_eq!(required(&props), ["containers", "replicas"]);
+        let Some(JSONSchemaPropsOrArray::Schema(container)) = props
+            .properties
+            .as_ref()
+            .and_then(|p| p["containers"].items.as_ref())
+        else {
+            panic!("containers should have a single items schema");
+        };
+        assert_eq!(required(container), ["args", "image", "name"]);
+
+        // Hand-written schemas are sorted as well
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "nested": {
+                    "type": "object",
+                    "properties": { "z": { "type": "string" }, "a": { "type": "string" } },
+                    "required": ["z", "a", "z"],
+                },
+            },
+            "required": ["nested"],
+        });
+        let props = to_json_schema_props(&schema).unwrap();
+        assert_eq!(required(&props.properties.unwrap()["nested"]), ["a", "z"]);
+    }
+
+    #[test]
+    fn rewrite_schema_json_returns_errors_as_strings() {
+        let schema = json!({
+            "oneOf": [
+                { "type": "string", "enum": ["A"] },
+                { "type": "string", "enum": ["B"] },
+            ],
+        });
+        let rewritten = rewrite_schema_json(&schema.to_string()).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&rewritten).unwrap(),
+            json!({ "type": "string", "enum": ["A", "B"] })
+        );
+
+        assert!(rewrite_schema_json("{").unwrap_err().starts_with("invalid JSON"));
+        assert_eq!(
+            rewrite_schema_json(r#""not a schema""#).unwrap_err(),
+            rewrite_existing_schema(json!("not a schema"))
+                .unwrap_err()
+                .to_string()
+        );
+        let uninhabited = json!({ "type": "object", "properties": { "never": false } });
+        assert!(rewrite_schema_json(&uninhabited.to_string()).is_err());
+    }
+
+    #[test]
+    fn normalize_byte_arrays_rewrites_all_representations() {
+        fn binary(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+            schemars::json_schema!({ "type": "string", "format": "binary" })
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            array: Vec<u8>,
+            #[schemars(schema_with = "binary")]
+            binary: Vec<u8>,
+            numbers: Vec<u16>,
+        }
+
+        let byte_string = json!({ "type": "string", "format": "byte" });
+        let normalized = schema_with_rewriter_for::<Spec>(
+            StructuralSchemaRewriter::builder()
+                .normalize_byte_arrays(true)
+                .build(),
+        );
+        assert_eq!(normalized.pointer("/properties/array"), Some(&byte_string));
+        assert_eq!(normalized.pointer("/properties/binary"), Some(&byte_string));
+        assert_eq!(
+            normalized.pointer("/properties/numbers/type"),
+            Some(&json!("array"))
+        );
+
+        let default = schema_for::<Spec>();
+        assert_eq!(default.pointer("/properties/array/type"), Some(&json!("array")));
+        assert_eq!(
+            default.pointer("/properties/binary/format"),
+            Some(&json!("binary"))
+        );
+    }
+
+    #[test]
+    fn hoisted_enum_variants_only_require_existing_properties() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        #[serde(tag = "kind")]
+        enum Source {
+            Git { url: String, revision: Option<String> },
+            Bucket { url: String, bucket: String },
+            Inline { content: String },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            source: Source,
+        }
+
+        let schema = schema_for::<Spec>();
+        assert_eq!(check_structural(&schema), []);
+
+        // The variants' `required` lists refer to the hoisted properties
+        let source = &schema["properties"]["source"];
+        for variant in source["oneOf"].as_array().unwrap() {
+            for name in variant["required"].as_array().unwrap() {
+                let name = name.as_str().unwrap();
+                assert!(source["properties"].get(name).is_some(), "{name} is not hoisted");
+            }
+        }
+    }
+
+    #[test]
+    fn documented_tagged_variants_with_several_fields_are_hoisted() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        #[serde(tag = "type", rename_all = "camelCase")]
+        enum Probe {
+            /// Send an HTTP request
+            Http {
+                path: String,
+                port: u16,
+                scheme: Option<String>,
+            },
+            /// Open a TCP connection
+            Tcp { port: u16, timeout_seconds: Option<u32> },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            probe: Probe,
+        }
+
+        // All fields are hoisted, and each variant keeps requiring its own fields. The variant descriptions
+        // can't be attached to a single property, and value validations can't have descriptions.
+        let schema = schema_for::<Spec>();
+        assert_eq!(
+            schema["properties"]["probe"],
+            json!({
+                "type": "object",
+                "properties": {
+                    "type": { "type": "string", "enum": ["http", "tcp"] },
+                    "path": { "type": "string" },
+                    "port": { "type": "integer", "format": "uint16", "minimum": 0, "maximum": 65535 },
+                    "scheme": { "type": "string", "nullable": true },
+                    "timeout_seconds": { "type": "integer", "format": "uint32", "minimum": 0, "nullable": true },
+                },
+                "oneOf": [
+                    { "required": ["path", "port", "type"] },
+                    { "required": ["port", "type"] },
+                ],
+            })
+        );
+        assert_eq!(check_structural(&schema), []);
+    }
+
+    #[test]
+    fn tagged_enum_fields_can_be_validated_by_rules() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type")]
+        #[allow(dead_code)]
+        enum Source {
+            Image {
+                image: String,
+                tag: Option<String>,
+            },
+            Url {
+                url: String,
+                #[serde(rename = "max-size")]
+                max_size: Option<u32>,
+            },
+            Empty,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            source: Source,
+        }
+
+        let rewriter = StructuralSchemaRewriter::builder()
+            .tagged_enum_validations(true)
+            .build();
+        let schema = schema_with_rewriter_for::<Spec>(rewriter);
+        let source = &schema["properties"]["source"];
+        assert_eq!(
+            source["x-kubernetes-validations"],
+            json!([
+                {
+                    "rule": "self.type != 'Image' || (has(self.image) && !has(self.max__dash__size) && !has(self.url))",
+                    "message": "the fields must match type Image",
+                },
+                {
+                    "rule": "self.type != 'Url' || (!has(self.image) && !has(self.tag) && has(self.url))",
+                    "message": "the fields must match type Url",
+                },
+                {
+                    "rule": "self.type != 'Empty' || (!has(self.image) && !has(self.max__dash__size) && !has(self.tag) && !has(self.url))",
+                    "message": "the fields must match type Empty",
+                },
+            ])
+        );
+        // The structural hoisting is unchanged
+        let mut hoisted = source.clone();
+        hoisted
+            .as_object_mut()
+            .unwrap()
+            .remove("x-kubernetes-validations");
+        assert_eq!(hoisted, schema_for::<Spec>()["properties"]["source"]);
+        assert_eq!(check_structural(&schema), []);
+    }
+
+    #[test]
+    fn validation_rules_from_extensions_are_kept_in_order() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type")]
+        #[schemars(extend("x-kubernetes-validations" = [
+            {
+                "rule": "self.type == oldSelf.type",
+                "messageExpression": "'type is immutable, it is ' + oldSelf.type",
+                "reason": "FieldValueForbidden",
+            },
+            { "rule": "self.type != 'Url' || self.url.startsWith('https://')", "fieldPath": ".url" },
+        ]))]
+        #[allow(dead_code)]
+        enum Source {
+            Image { image: String },
+            Url { url: String },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            source: Source,
+        }
+
+        let rules = &schema_for::<Spec>()["properties"]["source"]["x-kubernetes-validations"];
+        let parsed = serde_json::from_value::<Vec<Rule>>(rules.clone()).unwrap();
+        assert_eq!(parsed[0].rule, "self.type == oldSelf.type");
+        assert_eq!(
+            parsed[0].message,
+            Some(Message::Expression(
+                "'type is immutable, it is ' + oldSelf.type".into()
+            ))
+        );
+        assert_eq!(parsed[0].reason, Some(Reason::FieldValueForbidden));
+        assert_eq!(
+            parsed[1].rule,
+            "self.type != 'Url' || self.url.startsWith('https://')"
+        );
+        assert_eq!(parsed[1].field_path.as_deref(), Some(".url"));
+        assert_eq!(serde_json::to_value(&parsed).unwrap(), *rules);
+
+        // Generated rules are added after the ones of the extension
+        let rewriter = StructuralSchemaRewriter::builder()
+            .tagged_enum_validations(true)
+            .build();
+        let schema = schema_with_rewriter_for::<Spec>(rewriter);
+        let validated = schema["properties"]["source"]["x-kubernetes-validations"]
+            .as_array()
+            .unwrap();
+        assert_eq!(validated.len(), 4);
+        assert_eq!(validated[..2], rules.as_array().unwrap()[..]);
+        assert_eq!(validated[2]["message"], "the fields must match type Image");
+        assert_eq!(validated[3]["message"], "the fields must match type Url");
+    }
+
+    #[test]
+    fn variant_descriptions_can_be_copied_instead_of_moved() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        #[serde(rename_all = "camelCase")]
+        enum Probe {
+            /// Send an HTTP request
+            Http { path: String },
+            /// Open a TCP connection
+            Tcp { port: u16 },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            probe: Probe,
+        }
+
+        let moved = schema_for::<Spec>();
+        let probe = &moved["properties"]["probe"];
+        assert_eq!(probe["properties"]["http"]["description"], "Send an HTTP request");
+        assert_eq!(probe["properties"]["tcp"]["description"], "Open a TCP connection");
+        assert_eq!(
+            probe["oneOf"],
+            json!([{ "required": ["http"] }, { "required": ["tcp"] }])
+        );
+        assert_eq!(check_structural(&moved), []);
+
+        let copied = schema_with_rewriter_for::<Spec>(
+            StructuralSchemaRewriter::builder()
+                .copy_variant_descriptions(true)
+                .build(),
+        );
+        assert_eq!(copied["properties"]["probe"]["properties"], probe["properties"]);
+        assert_eq!(
+            copied["properties"]["probe"]["oneOf"],
+            json!([
+                { "required": ["http"], "x-variant-description": "Send an HTTP request" },
+                { "required": ["tcp"], "x-variant-description": "Open a TCP connection" },
+            ])
+        );
+        assert_eq!(check_structural(&copied), []);
+    }
+
+    #[test]
+    fn map_values_are_rewritten() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        #[serde(tag = "type")]
+        enum Backend {
+            Service { name: String },
+            Url { url: String },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            backends: BTreeMap<String, Backend>,
+        }
+
+        assert_eq!(
+            schema_for::<Spec>()["properties"]["backends"],
+            json!({
+                "type": "object",
+                "additionalProperties": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "type": { "type": "string", "enum": ["Service", "Url"] },
+                        "url": { "type": "string" },
+                    },
+                    "oneOf": [
+                        { "required": ["name", "type"] },
+                        { "required": ["type", "url"] },
+                    ],
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn enum_variants_flattening_a_base_struct_are_hoisted() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Base {
+            name: String,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        #[serde(tag = "type")]
+        enum Backend {
+            Service {
+                #[serde(flatten)]
+                base: Base,
+                port: u16,
+            },
+            Url {
+                #[serde(flatten)]
+                base: Base,
+                url: String,
+            },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            backend: Backend,
+        }
+
+        let hoisted = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "port": { "type": "integer", "format": "uint16", "minimum": 0, "maximum": 65535 },
+                "type": { "type": "string", "enum": ["Service", "Url"] },
+                "url": { "type": "string" },
+            },
+            "oneOf": [
+                { "required": ["name", "port", "type"] },
+                { "required": ["name", "type", "url"] },
+            ],
+        });
+        // schemars merges flattened fields into the variant itself
+        assert_eq!(schema_for::<Spec>()["properties"]["backend"], hoisted);
+
+        // ..but other generators may compose the variants using `allOf`
+        let base = json!({
+            "type": "object",
+            "properties": { "name": { "type": "string" } },
+            "required": ["name"],
+        });
+        let variant = |tag: &str, field: &str, schema: Value| {
+            json!({
+                "allOf": [
+                    base,
+                    {
+                        "type": "object",
+                        "properties": {
+                            "type": { "type": "string", "enum": [tag] },
+                            field: schema,
+                        },
+                        "required": ["type", field],
+                    },
+                ],
+            })
+        };
+        let composed = json!({
+            "oneOf": [
+                variant(
+                    "Service",
+                    "port",
+                    json!({ "type": "integer", "format": "uint16", "minimum": 0, "maximum": 65535 })
+                ),
+                variant("Url", "url", json!({ "type": "string" })),
+            ],
+        });
+        assert_eq!(rewrite_existing_schema(composed).unwrap(), hoisted);
+    }
+
+    #[test]
+    fn tag_colliding_with_variant_field_is_rejected() {
+        // serde rejects such enums at compile time, but other generators may not
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "backend": {
+                    "oneOf": [
+                        {
+                            "type": "object",
+                            "properties": {
+                                "type": { "type": "string", "enum": ["Service"] },
+                                "name": { "type": "string" },
+                            },
+                            "required": ["type", "name"],
+                        },
+                        {
+                            "type": "object",
+                            "properties": {
+                                "type": { "type": "integer" },
+                            },
+                            "required": ["type"],
+                        },
+                    ],
+                },
+            },
+        });
+        match rewrite_existing_schema(schema) {
+            Err(SchemaRewriteError::DiscriminatorCollision { path, property }) => {
+                assert_eq!(path, "/properties/backend");
+                assert_eq!(property, "type");
+            }
+            other => panic!("expected a discriminator collision, got {other:?}"),
+        }
+
+        // serde only detects a collision with the fields of newtype variants at runtime
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Service {
+            r#type: u32,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type")]
+        #[allow(dead_code)]
+        enum Backend {
+            Service(Service),
+        }
+
+        let schema = SchemaSettings::openapi3()
+            .into_generator()
+            .into_root_schema_for::<Backend>()
+            .to_value();
+        match rewrite_existing_schema(schema) {
+            Err(SchemaRewriteError::DiscriminatorCollision { path, property }) => {
+                assert_eq!(path, "/oneOf/0");
+                assert_eq!(property, "type");
+            }
+            other => panic!("expected a discriminator collision, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn variants_that_cannot_be_merged_are_rejected() {
+        let schema = json!({
+            "type": "integer",
+            "oneOf": [
+                { "type": "string", "enum": ["A"] },
+                { "type": "string", "enum": ["B"] },
+            ],
+        });
+        match rewrite_existing_schema(schema) {
+            Err(SchemaRewriteError::Hoist { path, source }) => {
+                assert_eq!(path, "");
+                match *source {
+                    HoistError::MismatchedTypes {
+                        expected,
+                        found,
+                        variant,
+                    } => {
+                        assert_eq!(expected, json!("integer"));
+                        assert_eq!(found, json!("string"));
+                        assert_eq!(variant, json!({ "type": "string", "enum": ["A"] }));
+                    }
+                    other => panic!("expected mismatched types, got {other:?}"),
+                }
+            }
+            other => panic!("expected variants that can't be merged, got {other:?}"),
+        }
+
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "size": {
+                    "oneOf": [
+                        { "type": "object", "properties": { "value": { "type": "string" } } },
+                        { "type": "object", "properties": { "value": { "type": "integer" } } },
+                    ],
+                },
+            },
+        });
+        let err = rewrite_existing_schema(schema).unwrap_err();
+        let variant = json!({ "type": "object", "properties": { "value": { "type": "integer" } } });
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "the variants of the enum at \"/properties/size\" can't be merged: the property \"value\" is \
+                 {{\"type\":\"integer\"}} in the variant {variant}, but {{\"type\":\"string\"}} in another"
+            )
+        );
+    }
+
+    #[test]
+    fn unevaluated_properties_are_replaced() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "closed": {
+                    "type": "object",
+                    "properties": { "a": { "type": "string" } },
+                    "unevaluatedProperties": false,
+                },
+                "empty": {
+                    "type": "object",
+                    "unevaluatedProperties": false,
+                },
+                "open": {
+                    "type": "object",
+                    "properties": { "a": { "type": "string" } },
+                    "unevaluatedProperties": true,
+                },
+            },
+        });
+        assert_eq!(
+            rewrite_existing_schema(schema).unwrap()["properties"],
+            json!({
+                "closed": {
+                    "type": "object",
+                    "properties": { "a": { "type": "string" } },
+                },
+                "empty": {
+                    "type": "object",
+                    "additionalProperties": false,
+                },
+                "open": {
+                    "type": "object",
+                    "properties": { "a": { "type": "string" } },
+                    "x-kubernetes-preserve-unknown-fields": true,
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn unevaluated_properties_of_tagged_enums_are_replaced_after_hoisting() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type")]
+        #[schemars(extend("unevaluatedProperties" = false))]
+        #[allow(dead_code)]
+        enum Source {
+            Image { name: String },
+            Url { url: String },
+        }
+
+        // The OpenAPI 3 settings replace `unevaluatedProperties`, unlike JSON Schema 2020-12
+        let schema = SchemaSettings::draft2020_12()
+            .with(|s| s.inline_subschemas = true)
+            .into_generator()
+            .into_root_schema_for::<Source>()
+            .to_value();
+        assert_eq!(schema["unevaluatedProperties"], false);
+
+        let schema = rewrite_existing_schema(schema).unwrap();
+        assert_eq!(check_structural(&schema), []);
+        assert_eq!(schema.get("additionalProperties"), None);
+        assert_eq!(
+            schema["properties"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .collect::<BTreeSet<_>>(),
+            BTreeSet::from([&"name".to_string(), &"type".to_string(), &"url".to_string()])
+        );
+    }
+
+    #[test]
+    fn hoisted_enum_values_are_appended_to_existing_ones() {
+        let schema = json!({
+            "type": "string",
+            "enum": ["A", "B"],
+            "oneOf": [
+                { "type": "string", "enum": ["B", "C"] },
+                { "type": "string", "enum": ["C", "D"] },
+            ],
+        });
+        assert_eq!(
+            rewrite_existing_schema(schema).unwrap(),
+            json!({ "type": "string", "enum": ["A", "B", "C", "D"] })
+        );
+    }
+
+    #[test]
+    fn documentation_only_enum_variants_are_unwrapped() {
+        let schema = json!({
+            "oneOf": [
+                { "type": "string", "const": "A", "description": "The A variant" },
+                { "type": "string", "const": "B", "description": "The B variant" },
+                {
+                    "description": "Either C or D",
+                    "allOf": [{ "type": "string", "enum": ["C", "D"], "description": "C or D" }],
+                },
+            ],
+        });
+        let rewritten = rewrite_existing_schema(schema.clone()).unwrap();
+        assert_eq!(
+            rewritten,
+            json!({ "type": "string", "enum": ["A", "B", "C", "D"] })
+        );
+        assert_eq!(check_structural(&rewritten), []);
+
+        // Variants that only carry annotations accept any value, so the enum can't be validated
+        let mut open = schema;
+        open["oneOf"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({ "description": "Reserved for future variants" }));
+        let mut rewriter = StructuralSchemaRewriter::default();
+        let mut rewritten = open.clone();
+        rewriter.transform_steps(&mut rewritten).unwrap();
+        assert_eq!(rewritten, json!({ "x-kubernetes-preserve-unknown-fields": true }));
+        assert_eq!(rewriter.warnings(), [
+            "the enum at \"\" has a variant that accepts any value, so its values are not validated (it was \
+             replaced by `x-kubernetes-preserve-unknown-fields`)"
+        ]);
+        match StructuralSchemaRewriter::builder()
+            .lossless(true)
+            .build()
+            .rewrite(&mut open.clone())
+        {
+            Err(SchemaRewriteError::LossyTransform { detail, .. }) => {
+                assert_eq!(
+                    detail,
+                    "enums with a variant that accepts any value are not validated"
+                );
+            }
+            other => panic!("expected a lossy transform error, got {other:?}"),
+        }
+
+        // Wrappers that constrain the wrapped schema any further are left alone
+        let constrained = rewrite_existing_schema(json!({
+            "oneOf": [
+                { "type": "string", "const": "A" },
+                { "maxLength": 1, "allOf": [{ "type": "string", "enum": ["C", "DD"] }] },
+            ],
+        }))
+        .unwrap();
+        assert_eq!(constrained["enum"], json!(["A"]));
+        assert_eq!(constrained["oneOf"][0]["maxLength"], json!(1));
+    }
+
+    #[test]
+    fn tuple_structs_become_fixed_length_arrays() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Point(f64, f64);
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Mixed(String, i32);
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            point: Point,
+            mixed: Mixed,
+        }
+
+        let schema = schema_for::<Spec>();
+        assert_eq!(check_structural(&schema), []);
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "point": {
+                    "type": "array",
+                    "items": { "type": "number", "format": "double" },
+                    "minItems": 2,
+                    "maxItems": 2,
+                },
+                "mixed": { "x-kubernetes-preserve-unknown-fields": true },
+            })
+        );
+    }
+
+    #[test]
+    fn enum_mixing_unit_and_struct_variants_is_structural() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Mixed {
+            Unit,
+            Struct { x: i32 },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            mixed: Mixed,
+        }
+
+        let schema = schema_for::<Spec>();
+        assert_eq!(check_structural(&schema), []);
+        assert_eq!(
+            schema.pointer("/properties/mixed"),
+            Some(&json!({ "x-kubernetes-preserve-unknown-fields": true }))
+        );
+
+        let raw_schema = SchemaSettings::openapi3()
+            .with(|s| s.inline_subschemas = true)
+            .into_generator()
+            .into_root_schema_for::<Mixed>()
+            .to_value();
+        let mut rewriter = StructuralSchemaRewriter::default();
+        rewriter.transform_steps(&mut raw_schema.clone()).unwrap();
+        assert_eq!(rewriter.warnings().len(), 1);
+
+        let untyped = json!({
+            "oneOf": [
+                { "enum": ["Unit"] },
+                { "properties": { "Struct": { "type": "integer" } }, "required": ["Struct"] },
+            ],
+        });
+        let mut rewriter = StructuralSchemaRewriter::default();
+        rewriter.transform_steps(&mut untyped.clone()).unwrap();
+        assert_eq!(rewriter.warnings(), [
+            "the enum at \"\" mixes unit and struct variants, so its values are not validated (it was \
+             replaced by `x-kubernetes-preserve-unknown-fields`)"
+        ]);
+        match StructuralSchemaRewriter::builder()
+            .lossless(true)
+            .build()
+            .rewrite(&mut untyped.clone())
+        {
+            Err(SchemaRewriteError::LossyTransform { detail, .. }) => {
+                assert_eq!(detail, "enums mixing unit and struct variants are not validated");
+            }
+            other => panic!("expected a lossy transform error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn open_enums_with_catch_all_variant_are_not_closed() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[schemars(extend("x-kube-rs-open-enum" = true))]
+        #[allow(dead_code)]
+        enum Plain {
+            A,
+            B,
+            #[serde(other)]
+            Unknown,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type")]
+        #[schemars(extend("x-kube-rs-open-enum" = true))]
+        #[allow(dead_code)]
+        enum Tagged {
+            A {
+                a: i32,
+            },
+            B {
+                b: String,
+            },
+            #[serde(other)]
+            Unknown,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            plain: Plain,
+            tagged: Tagged,
+        }
+
+        assert_eq!(
+            schema_for::<Spec>(),
+            json!({
+                "title": "Spec",
+                "type": "object",
+                "properties": {
+                    "plain": { "type": "string" },
+                    "tagged": {
+                        "type": "object",
+                        "oneOf": [
+                            { "required": ["a", "type"] },
+                            { "required": ["b", "type"] },
+                            { "required": ["type"] },
+                        ],
+                        "properties": {
+                            "a": { "type": "integer", "format": "int32" },
+                            "b": { "type": "string" },
+                            "type": { "type": "string" },
+                        },
+                    },
+                },
+                "required": ["plain", "tagged"],
+            })
+        );
+    }
+
+    #[test]
+    fn open_enum_tags_can_be_validated_by_rules() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type")]
+        #[schemars(extend("x-kube-rs-open-enum" = true))]
+        #[allow(dead_code)]
+        enum Tagged {
+            A {
+                a: i32,
+            },
+            B {
+                b: String,
+            },
+            #[serde(other)]
+            Unknown,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            tagged: Tagged,
+        }
+
+        let rewriter = StructuralSchemaRewriter::builder()
+            .open_enum_tag_validations(true)
+            .build();
+        let schema = schema_with_rewriter_for::<Spec>(rewriter);
+        let tag = &schema["properties"]["tagged"]["properties"]["type"];
+        assert_eq!(
+            tag,
+            &json!({
+                "type": "string",
+                "x-kubernetes-validations": [{
+                    "rule": "self in ['A', 'B', 'Unknown']",
+                    "message": "must be one of the known values: A, B, Unknown",
+                }],
+            })
+        );
+        assert_eq!(tag.get("enum"), None);
+        assert_eq!(check_structural(&schema), []);
+    }
+
+    #[test]
+    fn nullable_policy_makes_optional_properties_nullable() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Mode {
+            Fast,
+            Slow,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            name: String,
+            image: Option<String>,
+            #[serde(default)]
+            replicas: u32,
+            #[serde(default)]
+            mode: Option<Mode>,
+            #[schemars(extend("nullable" = true))]
+            tag: String,
+        }
+
+        let with_policy = |policy| {
+            schema_with_rewriter_for::<Spec>(
+                StructuralSchemaRewriter::builder()
+                    .nullable_policy(Some(policy))
+                    .build(),
+            )
+        };
+        assert_eq!(
+            with_policy(NullablePolicy::OptionalImpliesNullable)["properties"],
+            json!({
+                "name": { "type": "string" },
+                "image": { "type": "string", "nullable": true },
+                "replicas": {
+                    "default": 0,
+                    "type": "integer",
+                    "format": "uint32",
+                    "minimum": 0,
+                },
+                "mode": {
+                    "default": null,
+                    "type": "string",
+                    "enum": ["Fast", "Slow", null],
+                    "nullable": true,
+                },
+                "tag": { "type": "string", "nullable": true },
+            })
+        );
+
+        let strict = with_policy(NullablePolicy::NullableIffOptional);
+        assert_eq!(strict["properties"]["tag"], json!({ "type": "string" }));
+        assert_eq!(strict["properties"]["image"]["nullable"], json!(true));
+
+        let type_arrays = schema_with_rewriter_for::<Spec>(
+            StructuralSchemaRewriter::builder()
+                .nullable_policy(Some(NullablePolicy::OptionalImpliesNullable))
+                .nullable_type_arrays(true)
+                .build(),
+        );
+        assert_eq!(type_arrays["properties"]["replicas"]["type"], json!("integer"));
+        assert_eq!(
+            type_arrays["properties"]["mode"]["type"],
+            json!(["string", "null"])
+        );
+        assert_eq!(type_arrays["properties"]["mode"].get("nullable"), None);
+        assert_eq!(
+            type_arrays["properties"]["image"],
+            json!({ "type": ["string", "null"] })
+        );
+    }
+
+    #[test]
+    fn conflicting_nullability_is_normalized() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": ["string", "null"], "nullable": true },
+                "mode": { "type": ["null", "string"], "nullable": false, "enum": ["A", "B", null] },
+            },
+        });
+        let rewritten = rewrite_existing_schema(schema.clone()).unwrap();
+        assert_eq!(
+            rewritten["properties"],
+            json!({
+                "name": { "type": "string", "nullable": true },
+                "mode": { "type": "string", "nullable": true, "enum": ["A", "B", null] },
+            })
+        );
+        assert_eq!(check_structural(&rewritten), []);
+
+        // ..and as type arrays, if requested
+        let mut type_arrays = schema;
+        StructuralSchemaRewriter::builder()
+            .nullable_type_arrays(true)
+            .build()
+            .rewrite(&mut type_arrays)
+            .unwrap();
+        assert_eq!(
+            type_arrays["properties"]["name"],
+            json!({ "type": ["string", "null"] })
+        );
+    }
+
+    #[test]
+    fn nullable_type_arrays_replace_nullable() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Mode {
+            Fast,
+            Slow,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            name: Option<String>,
+            mode: Option<Mode>,
+            count: u32,
+        }
+
+        let schema = schema_with_rewriter_for::<Spec>(
+            StructuralSchemaRewriter::builder()
+                .nullable_type_arrays(true)
+                .build(),
+        );
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "name": { "type": ["string", "null"] },
+                "mode": { "type": ["string", "null"], "enum": ["Fast", "Slow", null] },
+                "count": { "type": "integer", "format": "uint32", "minimum": 0 },
+            })
+        );
+
+        let default = schema_for::<Spec>();
+        assert_eq!(
+            default["properties"]["name"],
+            json!({ "type": "string", "nullable": true })
+        );
+    }
+
+    #[test]
+    fn map_key_max_length_bounds_unbounded_map_keys() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "labels": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                },
+                "annotations": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                    "propertyNames": { "maxLength": 63 },
+                },
+            },
+        });
+        let mut rewritten = schemars::Schema::try_from(schema).unwrap();
+        let mut rewriter = StructuralSchemaRewriter::builder()
+            .map_key_max_length(Some(253))
+            .build();
+        rewriter.transform(&mut rewritten);
+        assert_eq!(
+            rewritten.get("properties"),
+            Some(&json!({
+                "labels": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                    "x-kubernetes-validations": [{
+                        "rule": "self.all(k, size(k) <= 253)",
+                        "message": "keys must be at most 253 characters long",
+                    }],
+                },
+                "annotations": {
+                    "type": "object",
+                    "additionalProperties": { "type": "string" },
+                    "propertyNames": { "maxLength": 63 },
+                    "x-kubernetes-validations": [{
+                        "rule": "self.all(k, size(k) <= 63)",
+                        "message": "keys must be at most 63 characters long",
+                    }],
+                },
+            }))
+        );
+
+        let labels = rewritten.get("properties").unwrap()["labels"].clone();
+        let mut again = schemars::Schema::try_from(labels.clone()).unwrap();
+        rewriter.transform(&mut again);
+        assert_eq!(again.to_value(), labels);
+    }
+
+    #[test]
+    fn skip_rewrite_marker_leaves_subschema_untouched() {
+        fn hand_written(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+            schemars::json_schema!({
+                "x-kube-rs-skip-rewrite": true,
+                "oneOf": [
+                    { "type": "object", "properties": { "a": { "type": "string" } }, "required": ["a"] },
+                    { "type": "object", "properties": { "b": { "type": "string" } }, "required": ["b"] },
+                ],
+            })
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            #[schemars(schema_with = "hand_written")]
+            untouched: Value,
+        }
+
+        assert_eq!(
+            schema_for::<Spec>()["properties"]["untouched"],
+            json!({
+                "oneOf": [
+                    { "type": "object", "properties": { "a": { "type": "string" } }, "required": ["a"] },
+                    { "type": "object", "properties": { "b": { "type": "string" } }, "required": ["b"] },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn oversized_schemas_are_rejected_until_descriptions_are_stripped() {
+        /// A field with an excessively long description, repeated over and over again to make the CRD large.
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Documented {
+            /// The first field, which is documented in great detail to take up as much space as possible.
+            first: String,
+            /// The second field, which is documented in great detail to take up as much space as possible.
+            second: String,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            a: Documented,
+            b: Documented,
+            c: Documented,
+        }
+
+        let limit = 512;
+        match check_schema_size(&schema_for::<Spec>(), limit) {
+            Err(SchemaRewriteError::CrdTooLarge { size, limit: 512 }) => assert!(size > limit),
+            other => panic!("expected the schema to be too large, got {other:?}"),
+        }
+
+        let stripped = schema_with_rewriter_for::<Spec>(
+            StructuralSchemaRewriter::builder()
+                .strip_descriptions(true)
+                .build(),
+        );
+        assert!(stripped.pointer("/properties/a/description").is_none());
+        assert!(check_schema_size(&stripped, limit).is_ok());
+    }
+
+    #[test]
+    fn dynamic_root_accepts_any_object() {
+        /// A dynamic resource
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            name: String,
+            ports: Vec<u16>,
+        }
+
+        let dynamic = dynamic_root_schema();
+        assert_eq!(check_structural(&dynamic), []);
+        let props = to_json_schema_props(&dynamic).unwrap();
+        assert_eq!(props.type_.as_deref(), Some("object"));
+        assert_eq!(props.x_kubernetes_preserve_unknown_fields, Some(true));
+        assert_eq!(props.properties, None);
+        assert_eq!(props.required, None);
+        assert_eq!(props.additional_properties, None);
+
+        let schema = schema_with_rewriter_for::<Spec>(
+            StructuralSchemaRewriter::builder()
+                .preserve_unknown_root(true)
+                .build(),
+        );
+        assert_eq!(
+            schema,
+            json!({
+                "title": "Spec",
+                "description": "A dynamic resource",
+                "type": "object",
+                "x-kubernetes-preserve-unknown-fields": true,
+            })
+        );
+        assert_eq!(check_structural(&schema), []);
+    }
+
+    #[test]
+    fn root_description_overrides_derived_description() {
+        /// A Foo, derived from the doc comment
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            /// Number of replicas
+            replicas: u32,
+        }
+
+        assert_eq!(
+            schema_for::<Spec>()["description"],
+            json!("A Foo, derived from the doc comment")
+        );
+        let description = "A Foo (deprecated in v1beta1, use v1 instead)";
+        let overridden = schema_with_rewriter_for::<Spec>(
+            StructuralSchemaRewriter::builder()
+                .root_description(Some(description.into()))
+                .build(),
+        );
+        assert_eq!(overridden["description"], json!(description));
+        assert_eq!(
+            overridden["properties"]["replicas"]["description"],
+            json!("Number of replicas")
+        );
+
+        let stripped = schema_with_rewriter_for::<Spec>(
+            StructuralSchemaRewriter::builder()
+                .strip_descriptions(true)
+                .root_description(Some(description.into()))
+                .build(),
+        );
+        assert_eq!(stripped["description"], json!(description));
+        assert_eq!(stripped["properties"]["replicas"].get("description"), None);
+    }
+
+    #[test]
+    fn compact_reduces_size_and_keeps_descriptions() {
+        let property = |i: usize| {
+            json!({
+                "title": format!("Property{i}"),
+                "$comment": "Generated from a Rust type, see the documentation for details",
+                "externalDocs": { "url": "https://example.com/docs" },
+                "description": format!("Property number {i}"),
+                "allOf": [{
+                    "type": "string",
+                    "enum": ["a", "b", "a", "c", "b"],
+                }],
+            })
+        };
+        let schema = json!({
+            "title": "Spec",
+            "description": "A large spec",
+            "type": "object",
+            "properties": (0..20).map(|i| (format!("property{i}"), property(i))).collect::<serde_json::Map<_, _>>(),
+        });
+
+        let mut compacted = schemars::Schema::try_from(schema.clone()).unwrap();
+        StructuralSchemaRewriter::builder()
+            .compact(true)
+            .build()
+            .transform(&mut compacted);
+        let compacted = compacted.to_value();
+
+        assert_eq!(
+            compacted.pointer("/properties/property3"),
+            Some(&json!({
+                "description": "Property number 3",
+                "type": "string",
+                "enum": ["a", "b", "c"],
+            }))
+        );
+        assert_eq!(compacted.get("description"), Some(&json!("A large spec")));
+        assert_eq!(compacted.get("title"), None);
+
+        let before = serde_json::to_vec(&schema).unwrap().len();
+        let after = serde_json::to_vec(&compacted).unwrap().len();
+        assert!(
+            after * 2 < before,
+            "expected {after} bytes to be less than half of {before} bytes"
+        );
+    }
+
+    #[test]
+    fn compact_merges_or_keeps_conflicting_junctors() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "merged": {
+                    "description": "Outer",
+                    "properties": { "a": { "type": "string" } },
+                    "required": ["a"],
+                    "allOf": [{
+                        "description": "Inner",
+                        "type": "object",
+                        "properties": { "b": { "type": "integer" } },
+                        "required": ["b"],
+                    }],
+                },
+                "conflicting": {
+                    "properties": { "a": { "type": "string" } },
+                    "allOf": [{ "properties": { "a": { "type": "integer" } } }],
+                },
+            },
+        });
+
+        let mut compacted = schemars::Schema::try_from(schema).unwrap();
+        StructuralSchemaRewriter::builder()
+            .compact(true)
+            .build()
+            .transform(&mut compacted);
+        let compacted = compacted.to_value();
+
+        assert_eq!(
+            compacted.pointer("/properties/merged"),
+            Some(&json!({
+                "description": "Outer",
+                "type": "object",
+                "properties": {
+                    "a": { "type": "string" },
+                    "b": { "type": "integer" },
+                },
+                "required": ["a", "b"],
+            }))
+        );
+        assert_eq!(
+            compacted.pointer("/properties/conflicting/allOf"),
+            Some(&json!([{ "properties": { "a": { "type": "integer" } } }]))
+        );
+    }
+
+    #[test]
+    fn uninhabited_fields_are_rejected() {
+        fn never(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+            false.into()
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Inner {
+            #[schemars(schema_with = "never")]
+            never: Value,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            inner: Inner,
+        }
+
+        let schema = SchemaSettings::openapi3()
+            .with(|s| s.inline_subschemas = true)
+            .into_generator()
+            .into_root_schema_for::<Spec>();
+        match rewrite_existing_schema(schema.to_value()) {
+            Err(SchemaRewriteError::UninhabitedField { path }) => {
+                assert_eq!(path, "/properties/inner/properties/never")
+            }
+            other => panic!("expected an uninhabited field error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn inlined_references_keep_field_description_and_default() {
+        /// Inner documentation
+        #[derive(Serialize, Deserialize, JsonSchema, Default)]
+        #[allow(dead_code)]
+        struct Inner {
+            a: i32,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            /// Field documentation
+            inner: Inner,
+            #[serde(default)]
+            defaulted: Inner,
+        }
+
+        let schema = SchemaSettings::openapi3()
+            .with(|s| s.meta_schema = None)
+            .into_generator()
+            .into_root_schema_for::<Spec>();
+        assert!(schema.get("components").is_some());
+
+        let inner = |description: &str, default: Option<Value>| {
+            let mut inner = json!({
+                "description": description,
+                "type": "object",
+                "properties": { "a": { "type": "integer", "format": "int32" } },
+                "required": ["a"],
+            });
+            if let Some(default) = default {
+                inner["default"] = default;
+            }
+            inner
+        };
+        assert_eq!(
+            rewrite_existing_schema(schema.clone().to_value()).unwrap(),
+            json!({
+                "title": "Spec",
+                "type": "object",
+                "properties": {
+                    "inner": inner("Field documentation", None),
+                    "defaulted": inner("Inner documentation", Some(json!({ "a": 0 }))),
+                },
+                "required": ["inner"],
+            })
+        );
+
+        // References and their definitions are kept if inlining is disabled
+        let mut kept = schema.to_value();
+        StructuralSchemaRewriter::builder()
+            .inline_references(false)
+            .build()
+            .rewrite(&mut kept)
+            .unwrap();
+        assert_eq!(
+            kept["properties"]["inner"]["allOf"],
+            json!([{ "$ref": "#/components/schemas/Inner" }])
+        );
+        assert_eq!(
+            kept["components"]["schemas"]["Inner"]["properties"],
+            json!({ "a": { "type": "integer", "format": "int32" } })
+        );
+    }
+
+    #[test]
+    fn integer_and_string_types_become_int_or_string() {
+        fn int_or_string(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+            schemars::json_schema!({ "type": ["string", "integer"] })
+        }
+
+        fn nullable_int_or_string(_: &mut schemars::SchemaGenerator) -> schemars::Schema {
+            schemars::json_schema!({ "type": ["integer", "null", "string"] })
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            #[schemars(schema_with = "int_or_string")]
+            port: Value,
+            #[schemars(schema_with = "nullable_int_or_string")]
+            target_port: Value,
+        }
+
+        assert_eq!(
+            schema_for::<Spec>()["properties"],
+            json!({
+                "port": { "x-kubernetes-int-or-string": true },
+                "target_port": { "x-kubernetes-int-or-string": true, "nullable": true },
+            })
+        );
+
+        // Existing values of the extension are replaced rather than shadowing the new one
+        let schema = json!({ "type": ["string", "integer"], "x-kubernetes-int-or-string": false });
+        assert_eq!(
+            rewrite_existing_schema(schema).unwrap(),
+            json!({ "x-kubernetes-int-or-string": true })
+        );
+    }
+
+    #[test]
+    fn other_type_unions_are_rejected() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "flag": { "type": ["string", "boolean"] },
+            },
+        });
+        match rewrite_existing_schema(schema) {
+            Err(SchemaRewriteError::MultipleTypes { path, types }) => {
+                assert_eq!(path, "/properties/flag");
+                assert_eq!(types, ["boolean", "string"]);
+            }
+            other => panic!("expected a multiple types error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn preserve_unknown_fields_at_strips_status_properties() {
+        /// Observed state
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Status {
+            ready: bool,
+            conditions: Vec<String>,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Resource {
+            spec: BTreeMap<String, String>,
+            status: Option<Status>,
+        }
+
+        let mut schema = schema_for::<Resource>();
+        preserve_unknown_fields_at(&mut schema, "/properties/status").unwrap();
+        assert_eq!(
+            schema["properties"]["status"],
+            json!({
+                "description": "Observed state",
+                "type": "object",
+                "nullable": true,
+                "x-kubernetes-preserve-unknown-fields": true,
+            })
+        );
+        assert!(matches!(
+            preserve_unknown_fields_at(&mut schema, "/properties/missing"),
+            Err(SchemaRewriteError::PointerNotFound { .. })
+        ));
+
+        let conditions = json!({ "type": "array", "items": { "type": "string" } });
+        let mut schema = json!({ "type": "object", "properties": { "conditions": conditions } });
+        match preserve_unknown_fields_at(&mut schema, "/properties/conditions") {
+            Err(SchemaRewriteError::NotAnObject { path, types }) => {
+                assert_eq!(path, "/properties/conditions");
+                assert_eq!(types, ["array"]);
+            }
+            other => panic!("expected a type error, got {other:?}"),
+        }
+        assert_eq!(schema["properties"]["conditions"], conditions);
+    }
+
+    #[test]
+    fn strip_defaults_at_only_strips_status_defaults() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Condition {
+            #[serde(default = "unknown")]
+            status: String,
+        }
+
+        fn unknown() -> String {
+            "Unknown".into()
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Status {
+            #[serde(default)]
+            ready_replicas: i32,
+            #[serde(default)]
+            conditions: Vec<Condition>,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            #[serde(default = "one")]
+            replicas: i32,
+        }
+
+        fn one() -> i32 {
+            1
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Resource {
+            spec: Spec,
+            status: Option<Status>,
+        }
+
+        let mut schema = schema_for::<Resource>();
+        let status = &schema["properties"]["status"]["properties"];
+        assert_eq!(status["ready_replicas"]["default"], json!(0));
+        assert_eq!(
+            status["conditions"]["items"]["properties"]["status"]["default"],
+            json!("Unknown")
+        );
+
+        strip_defaults_at(&mut schema, "/properties/status").unwrap();
+        let mut defaults = Vec::new();
+        for_each_subschema(&schema, "", &mut |pointer, subschema| {
+            if subschema.get("default").is_some() {
+                defaults.push(pointer.to_string());
+            }
+        });
+        assert_eq!(defaults, ["/properties/spec/properties/replicas"]);
+        assert!(matches!(
+            strip_defaults_at(&mut schema, "/properties/missing"),
+            Err(SchemaRewriteError::PointerNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn rename_properties_updates_properties_and_required() {
+        let mut schema = json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "type": "object",
+                    "properties": {
+                        "replicaCount": { "type": "integer" },
+                        "image": { "type": "string" },
+                        "app/name": { "type": "string" },
+                    },
+                    "required": ["image", "replicaCount"],
+                },
+            },
+            "required": ["spec"],
+        });
+        let renames = BTreeMap::from([
+            ("/properties/spec".to_string(), "specification".to_string()),
+            (
+                "/properties/spec/properties/replicaCount".to_string(),
+                "replicas".to_string(),
+            ),
+            (
+                "/properties/spec/properties/app~1name".to_string(),
+                "appName".to_string(),
+            ),
+        ]);
+        rename_properties(&mut schema, &renames).unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": {
+                    "specification": {
+                        "type": "object",
+                        "properties": {
+                            "replicas": { "type": "integer" },
+                            "image": { "type": "string" },
+                            "appName": { "type": "string" },
+                        },
+                        "required": ["image", "replicas"],
+                    },
+                },
+                "required": ["specification"],
+            })
+        );
+    }
+
+    #[test]
+    fn deprecated_fields_keep_their_marker() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code, deprecated)]
+        struct Spec {
+            replicas: i32,
+            #[deprecated]
+            replica_count: Option<i32>,
+            legacy: Option<Legacy>,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Legacy {
+            #[deprecated]
+            Image {
+                image: String,
+            },
+            Images {
+                images: Vec<String>,
+            },
+        }
+
+        let mut schema = schema_for::<Spec>();
+        assert_eq!(
+            schema["properties"]["replica_count"],
+            json!({ "type": "integer", "format": "int32", "nullable": true, "deprecated": true })
+        );
+        assert_eq!(
+            schema["properties"]["legacy"]["properties"]["Image"]["deprecated"],
+            json!(true)
+        );
+        assert_eq!(
+            schema["properties"]["legacy"]["properties"]["Images"].get("deprecated"),
+            None
+        );
+        assert_eq!(check_structural(&schema), []);
+
+        mark_deprecated(&mut schema, "/properties/legacy").unwrap();
+        assert_eq!(schema["properties"]["legacy"]["description"], "Deprecated.");
+        assert_eq!(schema["properties"]["legacy"].get("deprecated"), None);
+        schema["properties"]["replicas"]["description"] = "Number of replicas".into();
+        mark_deprecated(&mut schema, "/properties/replicas").unwrap();
+        mark_deprecated(&mut schema, "/properties/replicas").unwrap();
+        assert_eq!(
+            schema["properties"]["replicas"]["description"],
+            "Deprecated: Number of replicas"
+        );
+        assert!(matches!(
+            mark_deprecated(&mut schema, "/properties/missing"),
+            Err(SchemaRewriteError::PointerNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn remove_property_removes_properties_and_required() {
+        let mut schema = json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "type": "object",
+                    "properties": {
+                        "replicas": { "type": "integer" },
+                        "internal/debug": { "type": "boolean" },
+                    },
+                    "required": ["internal/debug"],
+                },
+                "internal": { "type": "string" },
+            },
+            "required": ["internal", "spec"],
+        });
+        remove_property(&mut schema, "/properties/internal").unwrap();
+        remove_property(&mut schema, "/properties/spec/properties/internal~1debug").unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": {
+                    "spec": {
+                        "type": "object",
+                        "properties": {
+                            "replicas": { "type": "integer" },
+                        },
+                    },
+                },
+                "required": ["spec"],
+            })
+        );
+
+        for pointer in [
+            "/properties/internal",
+            "/properties/spec/type",
+            "",
+            "/properties/spec/properties",
+        ] {
+            let err = remove_property(&mut schema, pointer).unwrap_err();
+            assert!(
+                matches!(&err, SchemaRewriteError::PointerNotFound { pointer: p } if p == pointer),
+                "{err:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn remove_property_updates_enum_variants() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Source {
+            Image { name: String },
+            Url { url: String },
+            Internal { path: String },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            source: Source,
+        }
+
+        let mut schema = schema_for::<Spec>();
+        remove_property(&mut schema, "/properties/source/properties/Internal").unwrap();
+        assert_eq!(
+            schema["properties"]["source"]["oneOf"],
+            json!([{ "required": ["Image"] }, { "required": ["Url"] }])
+        );
+        assert_eq!(check_structural(&schema), []);
+
+        let mut schema = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "replicas": { "type": "integer" },
+            },
+            "anyOf": [{ "required": ["name", "replicas"] }, { "required": ["name"] }],
+        });
+        remove_property(&mut schema, "/properties/replicas").unwrap();
+        assert_eq!(
+            schema["anyOf"],
+            json!([{ "required": ["name"] }, { "required": ["name"] }])
+        );
+        remove_property(&mut schema, "/properties/name").unwrap();
+        assert_eq!(schema, json!({ "type": "object", "properties": {} }));
+    }
+
+    #[test]
+    fn rename_properties_rejects_collisions() {
+        let original = json!({
+            "type": "object",
+            "properties": {
+                "name": { "type": "string" },
+                "displayName": { "type": "string" },
+                "replicas": { "type": "integer" },
+            },
+        });
+        let mut schema = original.clone();
+        let renames = BTreeMap::from([
+            ("/properties/displayName".to_string(), "name".to_string()),
+            ("/properties/replicas".to_string(), "replicaCount".to_string()),
+        ]);
+        let err = rename_properties(&mut schema, &renames).unwrap_err();
+        assert!(
+            matches!(&err, SchemaRewriteError::PropertyExists { path } if path == "/properties/name"),
+            "{err:?}"
+        );
+        // Nothing is renamed if any of the renames fails
+        assert_eq!(schema, original);
+
+        // Two properties can't get the same name
+        let renames = BTreeMap::from([
+            ("/properties/displayName".to_string(), "title".to_string()),
+            ("/properties/name".to_string(), "title".to_string()),
+        ]);
+        assert!(matches!(
+            rename_properties(&mut schema, &renames),
+            Err(SchemaRewriteError::PropertyExists { path }) if path == "/properties/title"
+        ));
+
+        let renames = BTreeMap::from([("/properties/missing".to_string(), "other".to_string())]);
+        assert!(matches!(
+            rename_properties(&mut schema, &renames),
+            Err(SchemaRewriteError::PointerNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn rename_properties_updates_references() {
+        let mut schema = json!({
+            "type": "object",
+            "properties": {
+                "ports": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "name": { "type": "string" },
+                            "port": { "type": "integer" },
+                        },
+                        "required": ["name"],
+                    },
+                    "x-kubernetes-list-type": "map",
+                    "x-kubernetes-list-map-keys": ["name"],
+                },
+                "min": { "type": "integer" },
+                "max": { "type": "integer" },
+                "image": { "type": "string" },
+                "url": { "type": "string" },
+            },
+            "oneOf": [{ "required": ["image"] }, { "required": ["url"] }],
+            "x-kubernetes-validations": [{
+                "rule": "self.min <= self.max && (!has(oldSelf.url) || self.url == oldSelf.url)",
+                "messageExpression": "'self.min must not exceed max, was ' + string(self.min)",
+            }],
+        });
+        let renames = BTreeMap::from([
+            ("/properties/min".to_string(), "max".to_string()),
+            ("/properties/max".to_string(), "min".to_string()),
+            ("/properties/url".to_string(), "source-url".to_string()),
+            (
+                "/properties/ports/items/properties/name".to_string(),
+                "portName".to_string(),
+            ),
+        ]);
+        rename_properties(&mut schema, &renames).unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": {
+                    "ports": {
+                        "type": "array",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "portName": { "type": "string" },
+                                "port": { "type": "integer" },
+                            },
+                            "required": ["portName"],
+                        },
+                        "x-kubernetes-list-type": "map",
+                        "x-kubernetes-list-map-keys": ["portName"],
+                    },
+                    "max": { "type": "integer" },
+                    "min": { "type": "integer" },
+                    "image": { "type": "string" },
+                    "source-url": { "type": "string" },
+                },
+                "oneOf": [{ "required": ["image"] }, { "required": ["source-url"] }],
+                "x-kubernetes-validations": [{
+                    "rule": "self.max <= self.min && (!has(oldSelf.source__dash__url) \
+                             || self.source__dash__url == oldSelf.source__dash__url)",
+                    "messageExpression": "'self.min must not exceed max, was ' + string(self.max)",
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn schema_objects_round_trip_real_crds() {
+        let crds = [
+            include_str!("./test_data/certificates.yaml"),
+            include_str!("./test_data/servicemonitors.yaml"),
+            include_str!("./test_data/kitchen_sink.yaml"),
+        ];
+        for crd in crds {
+            let crd: Value = serde_yaml::from_str(crd).unwrap();
+            let name = crd["metadata"]["name"].as_str().unwrap();
+            for version in crd["spec"]["versions"].as_array().unwrap() {
+                let schema = &version["schema"]["openAPIV3Schema"];
+                assert!(schema.is_object(), "{name} has no schema");
+                super::for_each_subschema(schema, "", &mut |pointer, subschema| {
+                    let parsed = serde_json::from_value::<super::Schema>(subschema.clone())
+                        .unwrap_or_else(|err| panic!("{name}{pointer} could not be parsed: {err}"));
+                    assert_eq!(
+                        &serde_json::to_value(parsed).unwrap(),
+                        subschema,
+                        "{name}{pointer} did not round-trip"
+                    );
+                });
+            }
+        }
+    }
+
+    /// Assert that the schema generated for `T` matches a committed expected schema (as YAML)
+    ///
+    /// The expected schemas document how the rewriter transforms the raw schemars output.
+    fn assert_schema_matches<T: JsonSchema>(expected: &str) {
+        let expected: Value = serde_yaml::from_str(expected).unwrap();
+        crate::assert_schema_eq!(schema_for::<T>(), expected);
+    }
+
+    #[test]
+    fn enums_match_expected_schemas() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Protocol {
+            Tcp,
+            Udp,
+        }
+
+        /// A listener
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type", rename_all = "camelCase")]
+        #[allow(dead_code)]
+        enum Listener {
+            /// Listen on a port
+            Port { port: u16, protocol: Option<Protocol> },
+            /// Listen on a unix socket
+            Socket { path: String },
+        }
+
+        /// Some content
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(untagged)]
+        #[allow(dead_code)]
+        enum Content {
+            Number { number: i64 },
+            Text { text: String, language: Option<String> },
+        }
+
+        assert_schema_matches::<Listener>(include_str!("./test_data/expected/tagged_enum.yaml"));
+        assert_schema_matches::<Content>(include_str!("./test_data/expected/untagged_enum.yaml"));
+    }
+
+    #[test]
+    fn default_transforms_can_be_disabled() {
+        /// A listener
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type", rename_all = "camelCase")]
+        #[allow(dead_code)]
+        enum Listener {
+            /// Listen on a port
+            Port { port: u16, protocol: Option<Protocol> },
+            /// Listen on a unix socket
+            Socket { path: String },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Protocol {
+            Tcp,
+            Udp,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            listener: Listener,
+            ports: BTreeSet<u16>,
+            #[serde(flatten)]
+            labels: BTreeMap<String, String>,
+        }
+
+        let expected: Value =
+            serde_yaml::from_str(include_str!("./test_data/expected/tagged_enum.yaml")).unwrap();
+        let all_enabled = StructuralSchemaRewriter::builder()
+            .hoist_enums(true)
+            .strip_unique_items(true)
+            .preserve_unknown_flattened_maps(true)
+            .remove_null_enum_variant(true)
+            .build();
+        crate::assert_schema_eq!(
+            schema_with_rewriter_for::<Listener>(all_enabled.clone()),
+            expected
+        );
+        crate::assert_schema_eq!(
+            schema_with_rewriter_for::<Listener>(StructuralSchemaRewriter::builder().build()),
+            expected
+        );
+        assert_eq!(
+            schema_with_rewriter_for::<Spec>(all_enabled),
+            schema_for::<Spec>()
+        );
+
+        let schema = schema_with_rewriter_for::<Listener>(
+            StructuralSchemaRewriter::builder().hoist_enums(false).build(),
+        );
+        assert_eq!(schema["oneOf"].as_array().map(Vec::len), Some(2));
+        assert_eq!(schema.get("properties"), None);
+
+        let rewrite = |mut rewriter: StructuralSchemaRewriter| {
+            let mut schema = schemars::Schema::try_from(json!({
+                "anyOf": [{ "type": "string", "enum": ["Tcp", "Udp"] }, { "type": "null" }],
+            }))
+            .unwrap();
+            rewriter.transform(&mut schema);
+            schema.to_value()
+        };
+        // Optional enums are still made nullable without hoisting..
+        assert_eq!(
+            rewrite(StructuralSchemaRewriter::builder().hoist_enums(false).build()),
+            json!({ "type": "string", "enum": ["Tcp", "Udp", null], "nullable": true })
+        );
+        // ..but otherwise, `null` is hoisted like any other variant, which has a different type
+        assert_eq!(
+            rewrite(
+                StructuralSchemaRewriter::builder()
+                    .remove_null_enum_variant(false)
+                    .build()
+            ),
+            json!({ "x-kubernetes-preserve-unknown-fields": true })
+        );
+
+        let schema = schema_with_rewriter_for::<Spec>(
+            StructuralSchemaRewriter::builder()
+                .strip_unique_items(false)
+                .preserve_unknown_flattened_maps(false)
+                .build(),
+        );
+        assert_eq!(schema["properties"]["ports"]["uniqueItems"], json!(true));
+        assert_eq!(schema["additionalProperties"], json!({ "type": "string" }));
+        assert_eq!(schema.get("x-kubernetes-preserve-unknown-fields"), None);
+    }
+
+    #[test]
+    fn optional_structs_in_tagged_enum_variants_stay_nullable() {
+        /// Inner settings
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Inner {
+            value: String,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type")]
+        #[allow(dead_code)]
+        enum Outer {
+            A { inner: Option<Inner> },
+            B { other: Option<Option<Inner>> },
+        }
+
+        let schema = schema_for::<Outer>();
+        assert_eq!(check_structural(&schema), []);
+        let inner = json!({
+            "description": "Inner settings",
+            "type": "object",
+            "nullable": true,
+            "properties": {
+                "value": { "type": "string" },
+            },
+            "required": ["value"],
+        });
+        assert_eq!(schema["properties"]["inner"], inner);
+        assert_eq!(schema["properties"]["other"], inner);
+    }
+
+    #[test]
+    fn preserved_flattened_maps_keep_min_properties() {
+        let schema = rewrite_existing_schema(json!({
+            "type": "object",
+            "properties": {
+                "labels": {
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                    },
+                    "additionalProperties": true,
+                    "minProperties": 1,
+                },
+            },
+        }))
+        .unwrap();
+        assert_eq!(
+            schema["properties"]["labels"],
+            json!({
+                "type": "object",
+                "properties": {
+                    "name": { "type": "string" },
+                },
+                "minProperties": 1,
+                "x-kubernetes-preserve-unknown-fields": true,
+            })
+        );
+    }
+
+    #[test]
+    fn flattened_typed_maps_keep_value_schema() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Mode {
+            Read { path: String },
+            Write { path: String, append: bool },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Extra {
+            #[serde(flatten)]
+            extra: BTreeMap<String, Mode>,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Mixed {
+            name: String,
+            #[serde(flatten)]
+            extra: BTreeMap<String, Mode>,
+        }
+
+        let mode = json!({
+            "type": "object",
+            "properties": {
+                "Read": {
+                    "type": "object",
+                    "properties": { "path": { "type": "string" } },
+                    "required": ["path"],
+                },
+                "Write": {
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string" },
+                        "append": { "type": "boolean" },
+                    },
+                    "required": ["append", "path"],
+                },
+            },
+            "oneOf": [{ "required": ["Read"] }, { "required": ["Write"] }],
+        });
+        assert_eq!(
+            schema_for::<Extra>(),
+            json!({
+                "title": "Extra",
+                "type": "object",
+                "additionalProperties": mode,
+            })
+        );
+
+        // Kubernetes doesn't allow `additionalProperties` alongside other `properties`
+        let mixed = schema_for::<Mixed>();
+        assert_eq!(
+            mixed,
+            json!({
+                "title": "Mixed",
+                "type": "object",
+                "properties": { "name": { "type": "string" } },
+                "required": ["name"],
+                "x-kubernetes-preserve-unknown-fields": true,
+            })
+        );
+        assert_eq!(check_structural(&mixed), []);
+    }
+
+    #[test]
+    fn empty_keywords_are_removed() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(untagged)]
+        #[allow(dead_code)]
+        enum Content {
+            Text { text: Option<String> },
+            Number { number: Option<i32> },
+        }
+
+        assert_eq!(
+            schema_for::<Content>(),
+            json!({
+                "title": "Content",
+                "type": "object",
+                "properties": {
+                    "text": { "type": "string", "nullable": true },
+                    "number": { "type": "integer", "format": "int32", "nullable": true },
+                },
+            })
+        );
+
+        let schema = rewrite_existing_schema(json!({
+            "type": "object",
+            "properties": {
+                "empty": { "type": "object", "properties": {}, "required": [] },
+            },
+            "required": [],
+        }))
+        .unwrap();
+        assert_eq!(
+            schema,
+            json!({
+                "type": "object",
+                "properties": {
+                    "empty": { "type": "object" },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn content_keywords_are_removed_or_mapped_to_formats() {
+        let schema = json!({
+            "type": "object",
+            "properties": {
+                "data": { "type": "string", "contentEncoding": "base64" },
+                "config": { "type": "string", "contentMediaType": "application/json" },
+            },
+        });
+
+        assert_eq!(
+            rewrite_existing_schema(schema.clone()).unwrap()["properties"],
+            json!({
+                "data": { "type": "string" },
+                "config": { "type": "string" },
+            })
+        );
+
+        let mut rewritten = schemars::Schema::try_from(schema).unwrap();
+        StructuralSchemaRewriter::builder()
+            .content_encoding_as_format(true)
+            .build()
+            .transform(&mut rewritten);
+        assert_eq!(
+            rewritten.to_value()["properties"],
+            json!({
+                "data": { "type": "string", "format": "byte" },
+                "config": { "type": "string" },
+            })
+        );
+    }
+
+    #[test]
+    fn lossless_rewriter_rejects_dropping_variant_descriptions() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(untagged)]
+        #[allow(dead_code)]
+        enum Content {
+            /// Plain text
+            Text { text: String },
+            /// A number
+            Number { number: i32 },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(untagged)]
+        #[allow(dead_code)]
+        enum Range {
+            /// A single port
+            Single { port: u16 },
+            /// A range of ports
+            Range { port: u16, end: u16 },
+        }
+
+        let generator = SchemaSettings::openapi3()
+            .with(|s| s.inline_subschemas = true)
+            .into_generator();
+        let lossless = StructuralSchemaRewriter::builder().lossless(true).build();
+
+        // The descriptions move to the only property of each variant
+        let content = generator.clone().into_root_schema_for::<Content>().to_value();
+        assert!(lossless.rewrite(&mut content.clone()).is_ok());
+
+        let schema = generator.into_root_schema_for::<Range>().to_value();
+        assert!(StructuralSchemaRewriter::default()
+            .rewrite(&mut schema.clone())
+            .is_ok());
+        match lossless.rewrite(&mut schema.clone()) {
+            Err(SchemaRewriteError::LossyTransform { path, detail }) => {
+                assert_eq!(path, "");
+                assert_eq!(detail, "the descriptions of enum variants are removed");
+            }
+            other => panic!("expected a lossy transform error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn transparent_newtypes_use_the_inner_schema() {
+        /// A port number
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(transparent)]
+        #[allow(dead_code)]
+        struct Port(u16);
+
+        /// Connection settings
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Settings {
+            host: String,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(transparent)]
+        #[allow(dead_code)]
+        struct Wrapped(Settings);
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            /// The port to listen on
+            port: Port,
+            /// The default port
+            default_port: Option<Port>,
+            /// How to connect
+            settings: Wrapped,
+            undocumented: Port,
+        }
+
+        let port = json!({
+            "type": "integer",
+            "format": "uint16",
+            "minimum": 0,
+            "maximum": 65535,
+        });
+        let with_description = |schema: &Value, description: &str| {
+            let mut schema = schema.clone();
+            schema["description"] = description.into();
+            schema
+        };
+
+        let schema = schema_for::<Spec>();
+        // Field documentation takes precedence over the documentation of the newtype
+        assert_eq!(
+            schema["properties"]["port"],
+            with_description(&port, "The port to listen on")
+        );
+        let mut default_port = with_description(&port, "The default port");
+        default_port["nullable"] = true.into();
+        assert_eq!(schema["properties"]["default_port"], default_port);
+        assert_eq!(
+            schema["properties"]["undocumented"],
+            with_description(&port, "A port number")
+        );
+        assert_eq!(
+            schema["properties"]["settings"],
+            json!({
+                "description": "How to connect",
+                "type": "object",
+                "properties": {
+                    "host": { "type": "string" },
+                },
+                "required": ["host"],
+            })
+        );
+    }
+
+    #[test]
+    fn source_types_annotate_named_types() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Endpoint {
+            host: String,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            primary: Endpoint,
+            replicas: Vec<Endpoint>,
+        }
+
+        let mut schema = SchemaSettings::openapi3()
+            .with(|s| s.meta_schema = None)
+            .with_transform(schemars::transform::AddNullable::default())
+            .with_transform(StructuralSchemaRewriter::builder().source_types(true).build())
+            .into_generator()
+            .into_root_schema_for::<Spec>()
+            .to_value();
+        assert_eq!(schema["x-kube-rs-source-type"], "Spec");
+        assert_eq!(
+            schema["properties"]["primary"]["x-kube-rs-source-type"],
+            "Endpoint"
+        );
+        assert_eq!(
+            schema["properties"]["replicas"]["items"]["x-kube-rs-source-type"],
+            "Endpoint"
+        );
+        assert_eq!(
+            schema["properties"]["replicas"].get("x-kube-rs-source-type"),
+            None
+        );
+
+        strip_source_types(&mut schema);
+        let mut annotations = 0;
+        for_each_subschema(&schema, "", &mut |_, subschema| {
+            annotations += usize::from(subschema.get("x-kube-rs-source-type").is_some());
+        });
+        assert_eq!(annotations, 0);
+        assert_eq!(
+            schema["properties"]["primary"]["properties"]["host"],
+            json!({ "type": "string" })
+        );
+    }
+
+    #[test]
+    fn long_enum_values_are_rejected() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Policy {
+            Always,
+            #[serde(rename = "IfNotPresentInTheLocalImageCache")]
+            IfNotPresent,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            policy: Policy,
+        }
+
+        let schema = SchemaSettings::openapi3()
+            .with(|s| s.inline_subschemas = true)
+            .into_generator()
+            .into_root_schema_for::<Spec>()
+            .to_value();
+        let rewrite = |max_length| {
+            StructuralSchemaRewriter::builder()
+                .enum_value_max_length(max_length)
+                .build()
+                .rewrite(&mut schema.clone())
+        };
+        assert!(rewrite(Some(64)).is_ok());
+        assert!(rewrite(None).is_ok());
+        match rewrite(Some(16)) {
+            Err(SchemaRewriteError::EnumValueTooLong {
+                path,
+                value,
+                max_length,
+            }) => {
+                assert_eq!(path, "/properties/policy");
+                assert_eq!(value, "IfNotPresentInTheLocalImageCache");
+                assert_eq!(max_length, 16);
+            }
+            other => panic!("expected a too long enum value, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn empty_additional_properties_are_handled_like_true() {
+        let schema = |additional_properties: Value| {
+            json!({
+                "type": "object",
+                "properties": {
+                    "map": { "type": "object", "additionalProperties": additional_properties },
+                    "flattened": {
+                        "type": "object",
+                        "properties": { "name": { "type": "string" } },
+                        "additionalProperties": additional_properties,
+                    },
+                },
+            })
+        };
+        let rewritten = rewrite_existing_schema(schema(json!({}))).unwrap();
+        assert_eq!(rewritten, rewrite_existing_schema(schema(json!(true))).unwrap());
+        assert_eq!(
+            rewritten["properties"],
+            json!({
+                "map": { "type": "object", "additionalProperties": true },
+                "flattened": {
+                    "type": "object",
+                    "properties": { "name": { "type": "string" } },
+                    "x-kubernetes-preserve-unknown-fields": true,
+                },
+            })
+        );
+
+        // Nothing is lost, since `{}` doesn't validate the values in the first place
+        let mut lossless = schema(json!({}));
+        StructuralSchemaRewriter::builder()
+            .lossless(true)
+            .build()
+            .rewrite(&mut lossless)
+            .unwrap();
+        assert_eq!(lossless, rewritten);
+    }
+
+    #[test]
+    fn single_property_wrappers_are_unwrapped() {
+        /// How a third-party adapter serializes durations
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Seconds {
+            /// Whole seconds
+            seconds: u64,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            /// How long to wait
+            #[schemars(with = "Seconds")]
+            timeout: u64,
+            #[schemars(with = "Option<Seconds>")]
+            grace_period: Option<u64>,
+            name: String,
+        }
+
+        let schema = schema_with_rewriter_for::<Spec>(
+            StructuralSchemaRewriter::builder()
+                .unwrap_single_property("/properties/timeout")
+                .unwrap_single_property("/properties/grace_period")
+                .build(),
+        );
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "timeout": {
+                    "type": "integer",
+                    "format": "uint64",
+                    "minimum": 0,
+                    "description": "How long to wait",
+                },
+                "grace_period": {
+                    "type": "integer",
+                    "format": "uint64",
+                    "minimum": 0,
+                    "description": "How a third-party adapter serializes durations",
+                    "nullable": true,
+                },
+                "name": { "type": "string" },
+            })
+        );
+
+        let mut schema = json!({
+            "type": "object",
+            "properties": { "a": { "type": "string" }, "b": { "type": "string" } },
+        });
+        let err = StructuralSchemaRewriter::builder()
+            .unwrap_single_property("")
+            .build()
+            .rewrite(&mut schema)
+            .unwrap_err();
+        assert!(
+            matches!(&err, SchemaRewriteError::NotSingleProperty { path } if path.is_empty()),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn opaque_types_preserve_unknown_fields() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            timeout: std::time::Duration,
+            timeouts: BTreeMap<String, std::time::Duration>,
+        }
+
+        let schema = SchemaSettings::openapi3()
+            .with(|s| s.meta_schema = None)
+            .with_transform(schemars::transform::AddNullable::default())
+            .with_transform(
+                StructuralSchemaRewriter::builder()
+                    .opaque_type("Duration")
+                    .build(),
+            )
+            .into_generator()
+            .into_root_schema_for::<Spec>()
+            .to_value();
+        let opaque = json!({ "x-kubernetes-preserve-unknown-fields": true });
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "timeout": opaque,
+                "timeouts": { "type": "object", "additionalProperties": opaque },
+            })
+        );
+
+        /// How long to wait
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[schemars(title = "Timeout")]
+        #[allow(dead_code)]
+        struct Timeout {
+            secs: u64,
+            nanos: u32,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct InlinedSpec {
+            timeout: Option<Timeout>,
+            #[schemars(title = "Duration")]
+            interval: std::time::Duration,
+        }
+
+        // Like `#[derive(CustomResource)]`, which inlines all subschemas
+        let schema = schema_with_rewriter_for::<InlinedSpec>(
+            StructuralSchemaRewriter::builder()
+                .opaque_type("Timeout")
+                .opaque_type("Duration")
+                .build(),
+        );
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "timeout": {
+                    "description": "How long to wait",
+                    "nullable": true,
+                    "x-kubernetes-preserve-unknown-fields": true,
+                },
+                "interval": opaque,
+            })
+        );
+    }
+
+    #[test]
+    fn untagged_enum_mixing_primitive_and_object_variants_preserves_unknown_fields() {
+        /// Connection settings
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Structured {
+            host: String,
+            port: u16,
+        }
+
+        /// A raw or structured connection
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(untagged)]
+        #[allow(dead_code)]
+        enum Connection {
+            Raw(String),
+            Structured(Structured),
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            connection: Connection,
+            fallback: Option<Connection>,
+        }
+
+        let connection = json!({
+            "description": "A raw or structured connection",
+            "x-kubernetes-preserve-unknown-fields": true,
+        });
+        let mut fallback = connection.clone();
+        fallback["nullable"] = true.into();
+
+        let schema = schema_for::<Spec>();
+        assert_eq!(check_structural(&schema), []);
+        assert_eq!(schema["properties"]["connection"], connection);
+        assert_eq!(schema["properties"]["fallback"], fallback);
+
+        let raw_schema = SchemaSettings::openapi3()
+            .with(|s| s.inline_subschemas = true)
+            .into_generator()
+            .into_root_schema_for::<Connection>()
+            .to_value();
+        match StructuralSchemaRewriter::builder()
+            .lossless(true)
+            .build()
+            .rewrite(&mut raw_schema.clone())
+        {
+            Err(SchemaRewriteError::LossyTransform { detail, .. }) => {
+                assert_eq!(detail, "enum variants of different types are not validated");
+            }
+            other => panic!("expected a lossy transform error, got {other:?}"),
+        }
+
+        let mut rewriter = StructuralSchemaRewriter::default();
+        rewriter.transform_steps(&mut raw_schema.clone()).unwrap();
+        assert_eq!(rewriter.warnings(), [
+            "the enum at \"\" has variants of different types, so its values are not validated (it was \
+             replaced by `x-kubernetes-preserve-unknown-fields`)"
+        ]);
+    }
+
+    /// Whether `serde_json` maps keep the order of their keys, which `preserve-order` depends on
+    #[cfg(feature = "preserve-order")]
+    fn json_preserves_order() -> bool {
+        let map = serde_json::from_str::<serde_json::Map<String, Value>>(r#"{ "b": 0, "a": 0 }"#).unwrap();
+        map.keys().next().is_some_and(|key| key == "b")
+    }
+
+    #[test]
+    #[cfg(feature = "preserve-order")]
+    fn keywords_can_be_emitted_in_canonical_order() {
+        if !json_preserves_order() {
+            return;
+        }
+        let schema = json!({
+            "x-kubernetes-validations": [{ "rule": "self.replicas > 0" }],
+            "title": "Spec",
+            "required": ["replicas"],
+            "properties": {
+                "replicas": { "maximum": 10, "type": "integer", "description": "Number of replicas" },
+            },
+            "type": "object",
+            "description": "The spec",
+            "maxProperties": 3,
+            "x-kubernetes-map-type": "atomic",
+        });
+        let keywords = |schema: &Value| schema.as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+
+        let mut rewritten = schema.clone();
+        StructuralSchemaRewriter::default()
+            .rewrite(&mut rewritten)
+            .unwrap();
+        assert_eq!(keywords(&rewritten), [
+            "description",
+            "type",
+            "maxProperties",
+            "required",
+            "properties",
+            "title",
+            "x-kubernetes-map-type",
+            "x-kubernetes-validations",
+        ]);
+
+        let mut canonical = schema;
+        StructuralSchemaRewriter::builder()
+            .canonical_keyword_order(true)
+            .build()
+            .rewrite(&mut canonical)
+            .unwrap();
+        assert_eq!(canonical, rewritten);
+        assert_eq!(keywords(&canonical), [
+            "type",
+            "description",
+            "maxProperties",
+            "properties",
+            "required",
+            "x-kubernetes-map-type",
+            "x-kubernetes-validations",
+            "title",
+        ]);
+        assert_eq!(keywords(&canonical["properties"]["replicas"]), [
+            "type",
+            "description",
+            "maximum"
+        ]);
+    }
+
+    #[test]
+    #[cfg(feature = "preserve-order")]
+    fn properties_keep_declaration_order() {
+        if !json_preserves_order() {
+            return;
+        }
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type")]
+        #[allow(dead_code)]
+        enum Variant {
+            Zebra { zoo: String, aardvark: String },
+            Apple { monkey: String },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            name: String,
+            replicas: i32,
+            image: String,
+            variant: Variant,
+        }
+
+        let schema = schema_for::<Spec>();
+        let names = |schema: &Value| {
+            schema["properties"]
+                .as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(&schema), ["name", "replicas", "image", "variant"]);
+        assert_eq!(names(&schema["properties"]["variant"]), [
+            "type", "zoo", "aardvark", "monkey"
+        ]);
+    }
+
+    #[test]
+    fn examples_are_replaced_by_the_first_example() {
+        let schema = json!({
+            "type": "string",
+            "examples": ["nginx", "redis", "postgres"],
+        });
+
+        assert_eq!(
+            rewrite_existing_schema(schema.clone()).unwrap(),
+            json!({ "type": "string", "example": "nginx" })
+        );
+
+        let mut preserved = schemars::Schema::try_from(schema).unwrap();
+        StructuralSchemaRewriter::builder()
+            .preserve_examples(true)
+            .build()
+            .transform(&mut preserved);
+        assert_eq!(
+            preserved.to_value(),
+            json!({
+                "type": "string",
+                "example": "nginx",
+                "x-examples": ["nginx", "redis", "postgres"],
+            })
+        );
+    }
+
+    #[test]
+    fn one_of_and_any_of_enums_are_hoisted_alike() {
+        let shapes = [
+            // Documented unit variants
+            json!([
+                { "description": "A", "type": "string", "enum": ["A"] },
+                { "description": "B", "type": "string", "enum": ["B"] },
+            ]),
+            // Integer values
+            json!([
+                { "type": "integer", "enum": [1] },
+                { "type": "integer", "enum": [2, 3] },
+            ]),
+            // Struct variants with distinct fields
+            json!([
+                { "type": "object", "properties": { "a": { "type": "string" } }, "required": ["a"] },
+                { "type": "object", "properties": { "b": { "type": "integer" } }, "required": ["b"] },
+            ]),
+            // Struct variants sharing a field
+            json!([
+                {
+                    "type": "object",
+                    "properties": { "name": { "type": "string" }, "a": { "type": "string" } },
+                    "required": ["name"],
+                },
+                {
+                    "type": "object",
+                    "properties": { "name": { "type": "string" }, "b": { "type": "string" } },
+                    "required": ["name", "b"],
+                },
+            ]),
+            // Internally tagged variants
+            json!([
+                {
+                    "type": "object",
+                    "properties": { "kind": { "type": "string", "enum": ["A"] }, "a": { "type": "string" } },
+                    "required": ["kind"],
+                },
+                {
+                    "type": "object",
+                    "properties": { "kind": { "type": "string", "enum": ["B"] } },
+                    "required": ["kind"],
+                },
+            ]),
+            // Externally tagged variants with nested objects
+            json!([
+                {
+                    "type": "object",
+                    "properties": {
+                        "A": { "type": "object", "properties": { "x": { "type": "integer" } } },
+                    },
+                    "required": ["A"],
+                },
+                {
+                    "type": "object",
+                    "properties": { "B": { "type": "array", "items": { "type": "string" } } },
+                    "required": ["B"],
+                },
+            ]),
+            // Variants flattening a base struct
+            json!([
+                {
+                    "allOf": [
+                        { "type": "object", "properties": { "base": { "type": "string" } } },
+                        { "type": "object", "properties": { "a": { "type": "string" } }, "required": ["a"] },
+                    ],
+                },
+                { "type": "object", "properties": { "b": { "type": "string" } }, "required": ["b"] },
+            ]),
+            // A single variant
+            json!([
+                { "type": "object", "properties": { "a": { "type": "string" } }, "required": ["a"] },
+            ]),
+            // Primitive and struct variants
+            json!([
+                { "type": "string" },
+                { "type": "object", "properties": { "a": { "type": "string" } }, "required": ["a"] },
+            ]),
+            // Unit and struct variants of the same type
+            json!([
+                { "type": "object", "enum": [{}] },
+                { "type": "object", "properties": { "a": { "type": "string" } }, "required": ["a"] },
+            ]),
+        ];
+
+        for variants in shapes {
+            let rewrite = |junctor: &str| {
+                let mut schema = json!({ junctor: variants.clone() });
+                StructuralSchemaRewriter::default()
+                    .rewrite(&mut schema)
+                    .map_err(|err| err.to_string())?;
+                // Only the name of the junctor holding the remaining variants may differ
+                if let Some(remaining) = schema.as_object_mut().and_then(|s| s.remove(junctor)) {
+                    schema["variants"] = remaining;
+                }
+                Ok::<_, String>(schema)
+            };
+            assert_eq!(rewrite("oneOf"), rewrite("anyOf"), "variants: {variants:#}");
+        }
+    }
+
+    #[test]
+    fn root_validations_survive_hoisting() {
+        /// A tagged enum at the root
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type")]
+        #[schemars(extend("x-kubernetes-validations" = [{ "rule": "self.type != 'B' || has(self.b)" }]))]
+        #[allow(dead_code)]
+        enum Root {
+            A { a: String },
+            B { b: Option<String> },
+        }
+
+        /// An untagged enum mixing types at the root
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(untagged)]
+        #[schemars(extend("x-kubernetes-validations" = [{ "rule": "self != ''" }]))]
+        #[allow(dead_code)]
+        enum Mixed {
+            Raw(String),
+            Structured { a: String },
+        }
+
+        let mut root = schema_for::<Root>();
+        assert!(root.pointer("/properties/a").is_some());
+        assert_eq!(
+            root["x-kubernetes-validations"],
+            json!([{ "rule": "self.type != 'B' || has(self.b)" }])
+        );
+        // Rules can also be attached to the root after the schema was generated
+        FieldValidations::new()
+            .rule("", Rule::new("self.type == 'A' || !has(self.a)"))
+            .attach(&mut root)
+            .unwrap();
+        assert_eq!(
+            root["x-kubernetes-validations"],
+            json!([
+                { "rule": "self.type != 'B' || has(self.b)" },
+                { "rule": "self.type == 'A' || !has(self.a)" },
+            ])
+        );
+
+        let mixed = schema_for::<Mixed>();
+        assert_eq!(mixed["x-kubernetes-preserve-unknown-fields"], true);
+        assert_eq!(
+            mixed["x-kubernetes-validations"],
+            json!([{ "rule": "self != ''" }])
+        );
+
+        // Optional enums are merged into the schema that references them, along with their rules
+        let optional = rewrite_existing_schema(json!({
+            "x-kubernetes-validations": [{ "rule": "has(self.a)" }],
+            "anyOf": [
+                {
+                    "type": "object",
+                    "properties": { "a": { "type": "string" } },
+                    "x-kubernetes-validations": [{ "rule": "self.a != ''" }],
+                },
+                { "enum": [null], "nullable": true },
+            ],
+        }))
+        .unwrap();
+        assert_eq!(
+            optional,
+            json!({
+                "type": "object",
+                "nullable": true,
+                "properties": { "a": { "type": "string" } },
+                "x-kubernetes-validations": [{ "rule": "self.a != ''" }, { "rule": "has(self.a)" }],
+            })
+        );
+    }
+
+    #[test]
+    fn nullable_enums_list_null() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Mode {
+            A,
+            B,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            mode: Option<Mode>,
+        }
+
+        // Referenced optional enums become nullable when their `None` variant is removed
+        let referenced = SchemaSettings::openapi3()
+            .with(|s| s.meta_schema = None)
+            .with_transform(schemars::transform::AddNullable::default())
+            .with_transform(StructuralSchemaRewriter::default())
+            .into_generator()
+            .into_root_schema_for::<Spec>()
+            .to_value();
+        let mode = json!({ "type": "string", "enum": ["A", "B", null], "nullable": true });
+        assert_eq!(referenced["properties"]["mode"], mode);
+        assert_eq!(schema_for::<Spec>()["properties"]["mode"], mode);
+
+        let rewritten = rewrite_existing_schema(json!({
+            "anyOf": [{ "type": "string", "enum": ["A", "B"] }, { "type": "null" }],
+        }))
+        .unwrap();
+        assert_eq!(rewritten, mode);
+    }
+
+    #[test]
+    fn null_in_enums_that_are_not_nullable_is_normalized() {
+        // `null` is rejected by the `type` anyway
+        let typed = rewrite_existing_schema(json!({ "type": "string", "enum": ["A", null] })).unwrap();
+        assert_eq!(typed, json!({ "type": "string", "enum": ["A"] }));
+
+        // ..but accepted by untyped enums
+        let untyped = rewrite_existing_schema(json!({ "enum": ["A", null] })).unwrap();
+        assert_eq!(untyped, json!({ "enum": ["A", null], "nullable": true }));
+
+        // ..and typed enums that only allow `null` can never be valid
+        let null_only = json!({
+            "type": "object",
+            "properties": { "never": { "type": "string", "enum": [null] } },
+        });
+        assert!(matches!(
+            rewrite_existing_schema(null_only),
+            Err(SchemaRewriteError::UninhabitedField { path }) if path == "/properties/never"
+        ));
+    }
+
+    #[test]
+    fn optional_documented_enums_are_nullable() {
+        /// Mode of operation
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Mode {
+            /// Go fast
+            Fast,
+            /// Go slow
+            Slow,
+        }
+
+        /// How to probe the service
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(rename_all = "camelCase")]
+        #[allow(dead_code)]
+        enum Probe {
+            /// Send an HTTP request
+            Http { path: String },
+            /// Open a TCP connection
+            Tcp { port: u16 },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            mode: Option<Mode>,
+            probe: Option<Probe>,
+        }
+
+        // Documented variants are generated as a `oneOf` within the `anyOf` of the `Option`
+        let referenced = SchemaSettings::openapi3()
+            .with(|s| s.meta_schema = None)
+            .with_transform(schemars::transform::AddNullable::default())
+            .with_transform(StructuralSchemaRewriter::default())
+            .into_generator()
+            .into_root_schema_for::<Spec>()
+            .to_value();
+        for schema in [schema_for::<Spec>(), referenced] {
+            let properties = &schema["properties"];
+            assert_eq!(
+                properties["mode"],
+                json!({
+                    "description": "Mode of operation",
+                    "type": "string",
+                    "enum": ["Fast", "Slow", null],
+                    "nullable": true,
+                })
+            );
+            assert_eq!(
+                properties["probe"],
+                json!({
+                    "description": "How to probe the service",
+                    "type": "object",
+                    "nullable": true,
+                    "properties": {
+                        "http": {
+                            "description": "Send an HTTP request",
+                            "type": "object",
+                            "properties": { "path": { "type": "string" } },
+                            "required": ["path"],
+                        },
+                        "tcp": {
+                            "description": "Open a TCP connection",
+                            "type": "object",
+                            "properties": {
+                                "port": { "type": "integer", "format": "uint16", "minimum": 0, "maximum": 65535 },
+                            },
+                            "required": ["port"],
+                        },
+                    },
+                    "oneOf": [{ "required": ["http"] }, { "required": ["tcp"] }],
+                })
+            );
+            assert_eq!(check_structural(&schema), []);
+        }
+    }
+
+    #[test]
+    fn nested_optional_enums_are_clean_at_every_level() {
+        /// Mode of operation
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        enum Mode {
+            Fast,
+            Slow,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "kind")]
+        #[allow(dead_code)]
+        enum Source {
+            Image { image: String },
+            Url { url: String },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            modes: Option<Vec<Option<Mode>>>,
+            sources: Option<Vec<Option<Source>>>,
+            matrix: Vec<Option<Vec<Option<Mode>>>>,
+        }
+
+        let referenced = SchemaSettings::openapi3()
+            .with(|s| s.meta_schema = None)
+            .with_transform(schemars::transform::AddNullable::default())
+            .with_transform(StructuralSchemaRewriter::default())
+            .into_generator()
+            .into_root_schema_for::<Spec>()
+            .to_value();
+        let mode = json!({
+            "description": "Mode of operation",
+            "type": "string",
+            "enum": ["Fast", "Slow", null],
+            "nullable": true,
+        });
+        for schema in [schema_for::<Spec>(), referenced] {
+            let properties = &schema["properties"];
+            assert_eq!(
+                properties["modes"],
+                json!({ "type": "array", "nullable": true, "items": mode })
+            );
+            assert_eq!(properties["matrix"]["items"]["items"], mode);
+            assert_eq!(properties["matrix"]["items"]["nullable"], true);
+            assert_eq!(properties["sources"]["items"]["nullable"], true);
+            assert_eq!(
+                properties["sources"]["items"]["properties"]["kind"],
+                json!({ "type": "string", "enum": ["Image", "Url"] })
+            );
+            assert_eq!(check_structural(&schema), []);
+
+            for_each_subschema(&schema, "", &mut |pointer, subschema| {
+                assert_eq!(subschema.get("anyOf"), None, "leftover anyOf at {pointer}");
+                assert_ne!(
+                    subschema.get("type"),
+                    Some(&json!("null")),
+                    "null type at {pointer}"
+                );
+                assert_ne!(
+                    subschema.get("enum"),
+                    Some(&json!([null])),
+                    "null variant at {pointer}"
+                );
+            });
+        }
+    }
+
+    #[test]
+    fn null_only_fields_are_nullable() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            /// Nothing at all
+            unit: (),
+            optional_unit: Option<()>,
+        }
+
+        let schema = schema_for::<Spec>();
+        assert_eq!(check_structural(&schema), []);
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "unit": {
+                    "description": "Nothing at all",
+                    "nullable": true,
+                    "x-kubernetes-preserve-unknown-fields": true,
+                },
+                "optional_unit": {
+                    "nullable": true,
+                    "x-kubernetes-preserve-unknown-fields": true,
+                },
+            })
+        );
+        assert_eq!(
+            rewrite_existing_schema(json!({ "type": "null" })).unwrap(),
+            json!({ "nullable": true, "x-kubernetes-preserve-unknown-fields": true })
+        );
+    }
+
+    #[test]
+    fn make_all_optional_removes_required_outside_of_preserved_subtrees() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "type")]
+        #[allow(dead_code)]
+        enum Variant {
+            A { a: String },
+            B { b: String },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Port {
+            port: u16,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            name: String,
+            ports: Vec<Port>,
+            variant: Variant,
+        }
+
+        let mut schema = schema_for::<Spec>();
+        schema["properties"]["opaque"] = json!({
+            "type": "object",
+            "x-kubernetes-preserve-unknown-fields": true,
+            "properties": { "kept": { "type": "string" } },
+            "required": ["kept"],
+        });
+        make_all_optional(&mut schema);
+
+        let mut required = Vec::new();
+        super::for_each_subschema(&schema, "", &mut |pointer, subschema| {
+            if subschema.get("required").is_some() {
+                required.push(pointer.to_string());
+            }
+        });
+        assert_eq!(required, ["/properties/opaque"]);
+        assert_eq!(schema["properties"]["variant"].get("oneOf"), None);
+        assert_eq!(check_structural(&schema), []);
+    }
+
+    #[test]
+    fn untagged_enum_with_overlapping_variants_is_hoisted_regardless_of_order() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Tls {
+            secret: String,
+            ca: Option<String>,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Basic {
+            name: String,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Replicated {
+            name: String,
+            replicas: Option<i32>,
+            tls: Option<Tls>,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Secured {
+            name: String,
+            replicas: i32,
+            tls: Tls,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(untagged)]
+        #[allow(dead_code)]
+        enum SmallestFirst {
+            /// Only a name
+            Basic(Basic),
+            Replicated(Replicated),
+            Secured(Secured),
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(untagged)]
+        #[allow(dead_code)]
+        enum LargestFirst {
+            Secured(Secured),
+            Replicated(Replicated),
+            /// Only a name
+            Basic(Basic),
+        }
+
+        let properties = json!({
+            "name": { "type": "string" },
+            "replicas": { "type": "integer", "format": "int32", "nullable": true },
+            "tls": {
+                "type": "object",
+                "properties": {
+                    "secret": { "type": "string" },
+                    "ca": { "type": "string", "nullable": true },
+                },
+                "required": ["secret"],
+                "nullable": true,
+            },
+        });
+        let smallest_first = schema_for::<SmallestFirst>();
+        assert_eq!(smallest_first["properties"], properties);
+        assert_eq!(
+            smallest_first["anyOf"],
+            json!([
+                { "required": ["name"] },
+                { "required": ["name"] },
+                { "required": ["name", "replicas", "tls"] },
+            ])
+        );
+        let largest_first = schema_for::<LargestFirst>();
+        assert_eq!(largest_first["properties"], properties);
+        assert_eq!(
+            largest_first["anyOf"],
+            json!([
+                { "required": ["name", "replicas", "tls"] },
+                { "required": ["name"] },
+                { "required": ["name"] },
+            ])
+        );
+        assert_eq!(check_structural(&smallest_first), []);
+        assert_eq!(check_structural(&largest_first), []);
+
+        // The variants of tagged enums are selected by the tag, so a field that is only optional in some of
+        // them can't be made nullable for all of them
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "mode")]
+        #[allow(dead_code)]
+        enum Tagged {
+            Replicated(Replicated),
+            Secured(Secured),
+        }
+
+        let tagged = SchemaSettings::openapi3()
+            .with(|s| s.inline_subschemas = true)
+            .with_transform(schemars::transform::AddNullable::default())
+            .into_generator()
+            .into_root_schema_for::<Tagged>()
+            .to_value();
+        match rewrite_existing_schema(tagged) {
+            Err(SchemaRewriteError::Hoist { source, .. }) => match *source {
+                HoistError::MismatchedProperty {
+                    property, variant, ..
+                } => {
+                    assert_eq!(property, "replicas");
+                    assert_eq!(variant["properties"]["mode"]["enum"], json!(["Secured"]));
+                }
+                other => panic!("expected a mismatched property, got {other:?}"),
+            },
+            other => panic!("expected the variants to be rejected, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn enums_with_renamed_fields_hoist_the_serialized_names() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "kind", rename_all_fields = "camelCase")]
+        #[allow(dead_code)]
+        enum Source {
+            Http {
+                base_url: String,
+                retry_count: Option<u32>,
+            },
+            File {
+                file_path: String,
+                retry_count: Option<u32>,
+            },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(untagged, rename_all_fields = "camelCase")]
+        #[allow(dead_code)]
+        enum UntaggedSource {
+            Http {
+                base_url: String,
+                retry_count: Option<u32>,
+            },
+            File {
+                file_path: String,
+                retry_count: Option<u32>,
+            },
+        }
+
+        let retry_count = json!({ "type": "integer", "format": "uint32", "minimum": 0, "nullable": true });
+        let schema = schema_for::<Source>();
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "kind": { "type": "string", "enum": ["Http", "File"] },
+                "baseUrl": { "type": "string" },
+                "retryCount": retry_count,
+                "filePath": { "type": "string" },
+            })
+        );
+        assert_eq!(
+            schema["oneOf"],
+            json!([{ "required": ["baseUrl", "kind"] }, { "required": ["filePath", "kind"] }])
+        );
+        let instance = serde_json::to_value(Source::Http {
+            base_url: "https://example.com".into(),
+            retry_count: Some(3),
+        })
+        .unwrap();
+        for field in instance.as_object().unwrap().keys() {
+            assert!(
+                schema["properties"].get(field).is_some(),
+                "{field} is not hoisted"
+            );
+        }
+
+        let schema = schema_for::<UntaggedSource>();
+        assert_eq!(
+            schema["properties"],
+            json!({
+                "baseUrl": { "type": "string" },
+                "retryCount": retry_count,
+                "filePath": { "type": "string" },
+            })
+        );
+        assert_eq!(
+            schema["anyOf"],
+            json!([{ "required": ["baseUrl"] }, { "required": ["filePath"] }])
+        );
+    }
+
+    #[test]
+    fn map_values_keep_the_description_of_their_type() {
+        /// An endpoint to connect to
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Endpoint {
+            port: u16,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(untagged)]
+        #[allow(dead_code)]
+        enum Backend {
+            Static { endpoints: BTreeMap<String, Endpoint> },
+            Dynamic { selector: String },
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            /// Endpoints by name
+            endpoints: BTreeMap<String, Endpoint>,
+            fallbacks: Option<BTreeMap<String, Endpoint>>,
+            backend: Backend,
+        }
+
+        let endpoint = json!({
+            "description": "An endpoint to connect to",
+            "type": "object",
+            "properties": {
+                "port": { "type": "integer", "format": "uint16", "minimum": 0, "maximum": 65535 },
+            },
+            "required": ["port"],
+        });
+        let referenced = SchemaSettings::openapi3()
+            .with(|s| s.meta_schema = None)
+            .with_transform(schemars::transform::AddNullable::default())
+            .with_transform(StructuralSchemaRewriter::default())
+            .into_generator()
+            .into_root_schema_for::<Spec>()
+            .to_value();
+        for schema in [schema_for::<Spec>(), referenced] {
+            let properties = &schema["properties"];
+            assert_eq!(properties["endpoints"]["description"], "Endpoints by name");
+            assert_eq!(properties["endpoints"]["additionalProperties"], endpoint);
+            assert_eq!(properties["fallbacks"]["additionalProperties"], endpoint);
+            assert_eq!(
+                properties["backend"]["properties"]["endpoints"]["additionalProperties"],
+                endpoint
+            );
+        }
+    }
+
+    #[test]
+    fn tagged_enum_with_referenced_struct_variants_is_hoisted_after_inlining() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct S3 {
+            bucket: String,
+            region: Option<String>,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Pvc {
+            claim: String,
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[serde(tag = "kind")]
+        #[allow(dead_code)]
+        enum Storage {
+            S3(S3),
+            Pvc(Pvc),
+        }
+
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            storage: Storage,
+        }
+
+        // Without `inline_subschemas`, the variants are references to the structs, next to their tags
+        let generator = || {
+            SchemaSettings::openapi3()
+                .with(|s| s.meta_schema = None)
+                .with_transform(StructuralSchemaRewriter::default())
+                .into_generator()
+        };
+        let storage = json!({
+            "type": "object",
+            "properties": {
+                "bucket": { "type": "string" },
+                "region": { "type": "string", "nullable": true },
+                "kind": { "type": "string", "enum": ["S3", "Pvc"] },
+                "claim": { "type": "string" },
+            },
+            "oneOf": [
+                { "required": ["bucket", "kind"] },
+                { "required": ["claim", "kind"] },
+            ],
+        });
+        let mut root = generator().into_root_schema_for::<Storage>().to_value();
+        root.as_object_mut().unwrap().remove("title");
+        assert_eq!(root, storage);
+        let spec = generator().into_root_schema_for::<Spec>().to_value();
+        assert_eq!(spec["properties"]["storage"], storage);
+        assert_eq!(spec.get("components"), None);
+        assert_eq!(check_structural(&spec), []);
+    }
+
+    #[test]
+    fn properties_named_like_keywords_survive_rewrite() {
+        #[derive(Serialize, Deserialize, JsonSchema)]
+        #[allow(dead_code)]
+        struct Spec {
+            #[serde(rename = "type")]
+            kind: String,
+            #[serde(rename = "enum")]
+            values: Vec<String>,
+            #[serde(rename = "additionalProperties")]
+            extra: Option<BTreeMap<String, i32>>,
+        }
+
+        let properties = json!({
+            "type": { "type": "string" },
+            "enum": { "type": "array", "items": { "type": "string" } },
+            "additionalProperties": {
+                "type": "object",
+                "additionalProperties": { "type": "integer", "format": "int32" },
+                "nullable": true,
+            },
+        });
+        let schema = schema_for::<Spec>();
+        assert_eq!(schema["type"], "object");
+        assert_eq!(schema["properties"], properties);
+        assert_eq!(schema.get("enum"), None);
+        assert_eq!(schema.get("additionalProperties"), None);
+        assert_eq!(schema["required"], json!(["enum", "type"]));
+
+        // Rewriting the generated schema again must not confuse the names with the keywords
+        let rewritten = rewrite_existing_schema(schema.clone()).unwrap();
+        assert_eq!(rewritten, schema);
+        assert_eq!(check_structural(&rewritten), []);
+    }
+}
@@ -0,0 +1,265 @@
+use super::{
+    check, check_structural, invalid_defaults, invalid_list_map_keys, invalid_patterns, SchemaIssue,
+};
+use serde_json::Value;
+
+/// Validate `instance` against `schema`, the way the apiserver would when it is created
+///
+/// This covers the `type`, `nullable`, `enum`, range, `required` and junctor (`allOf`, `anyOf` and `oneOf`)
+/// keywords, as well as fields that would be pruned because the schema doesn't define them. Formats, patterns
+/// and CEL rules aren't evaluated. The error describes the first violation that was found, as a
+/// [`SchemaIssueKind::InvalidValue`](super::SchemaIssueKind::InvalidValue) of the subschema that rejected it.
+///
+/// ```
+/// use kube_core::schema::validate_instance;
+/// use serde_json::json;
+///
+/// let schema = json!({
+///     "type": "object",
+///     "properties": { "replicas": { "type": "integer", "minimum": 0 } },
+///     "required": ["replicas"],
+/// });
+/// assert_eq!(validate_instance(&schema, &json!({ "replicas": 3 })), Ok(()));
+/// let issue = validate_instance(&schema, &json!({ "replicas": -1 })).unwrap_err();
+/// assert_eq!(issue.pointer, "/properties/replicas");
+/// ```
+pub fn validate_instance(schema: &Value, instance: &Value) -> Result<(), SchemaIssue> {
+    check::check_value(schema, instance, "", "")
+}
+
+/// Assert that the apiserver would accept `schema` as the schema of a CRD, and each of `instances` as an object
+///
+/// The schema is checked with [`check_structural`], [`invalid_patterns`], [`invalid_defaults`] and
+/// [`invalid_list_map_keys`], and the instances with [`validate_instance`]. This is meant for testing derived
+/// schemas in CI without a cluster.
+///
+/// # Panics
+///
+/// Panics with a list of all problems, if there are any.
+///
+/// ```
+/// use kube_core::schema::{assert_schema_accepted, StructuralSchemaRewriter};
+/// use schemars::{generate::SchemaSettings, JsonSchema};
+/// use serde::Serialize;
+///
+/// #[derive(Serialize, JsonSchema)]
+/// struct Spec {
+///     replicas: u32,
+///     image: Option<String>,
+/// }
+///
+/// let schema = SchemaSettings::openapi3()
+///     .with(|s| s.inline_subschemas = true)
+///     .with_transform(StructuralSchemaRewriter::default())
+///     .into_generator()
+///     .into_root_schema_for::<Spec>()
+///     .to_value();
+/// let instance = Spec { replicas: 1, image: None };
+/// assert_schema_accepted(&schema, &[serde_json::to_value(instance).unwrap()]);
+/// ```
+pub fn assert_schema_accepted(schema: &Value, instances: &[Value]) {
+    let mut problems = check_structural(schema)
+        .into_iter()
+        .chain(invalid_patterns(schema))
+        .chain(invalid_defaults(schema))
+        .chain(invalid_list_map_keys(schema))
+        .map(|issue| issue.to_string())
+        .collect::<Vec<_>>();
+    for (index, instance) in instances.iter().enumerate() {
+        if let Err(error) = validate_instance(schema, instance) {
+            problems.push(format!("instance {index}: {error}"));
+        }
+    }
+    assert!(
+        problems.is_empty(),
+        "the schema would be rejected:\n{}",
+        problems.join("\n")
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{assert_schema_accepted, validate_instance};
+    use crate::schema::{tests::schema_for, SchemaIssue, SchemaIssueKind};
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+
+    fn instances<T: Serialize>(values: &[T]) -> Vec<Value> {
+        values
+            .iter()
+            .map(|value| serde_json::to_value(value).unwrap())
+            .collect()
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    enum Protocol {
+        Tcp,
+        Udp,
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[serde(rename_all = "camelCase")]
+    struct Port {
+        container_port: u16,
+        protocol: Option<Protocol>,
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[serde(tag = "type")]
+    enum Storage {
+        Ephemeral { medium: String },
+        Persistent { size: String, class: Option<String> },
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[serde(untagged)]
+    enum Source {
+        Image {
+            image: String,
+        },
+        Build {
+            repository: String,
+            revision: Option<String>,
+        },
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    struct Spec {
+        replicas: i32,
+        ports: Vec<Port>,
+        labels: BTreeMap<String, String>,
+        storage: Storage,
+        source: Source,
+        #[schemars(default = "default_paused")]
+        paused: Option<bool>,
+    }
+
+    fn default_paused() -> Option<bool> {
+        Some(false)
+    }
+
+    #[test]
+    fn accepts_representative_derived_types() {
+        assert_schema_accepted(
+            &schema_for::<Spec>(),
+            &instances(&[
+                Spec {
+                    replicas: 1,
+                    ports: vec![Port {
+                        container_port: 80,
+                        protocol: Some(Protocol::Tcp),
+                    }],
+                    labels: BTreeMap::from([("app".into(), "web".into())]),
+                    storage: Storage::Ephemeral {
+                        medium: "Memory".into(),
+                    },
+                    source: Source::Image {
+                        image: "nginx".into(),
+                    },
+                    paused: None,
+                },
+                Spec {
+                    replicas: 3,
+                    ports: vec![],
+                    labels: BTreeMap::new(),
+                    storage: Storage::Persistent {
+                        size: "1Gi".into(),
+                        class: None,
+                    },
+                    source: Source::Build {
+                        repository: "https://example.com/app.git".into(),
+                        revision: Some("main".into()),
+                    },
+                    paused: Some(true),
+                },
+            ]),
+        );
+        assert_schema_accepted(
+            &schema_for::<Port>(),
+            &instances(&[Port {
+                container_port: 443,
+                protocol: None,
+            }]),
+        );
+        assert_schema_accepted(
+            &schema_for::<Storage>(),
+            &instances(&[Storage::Ephemeral { medium: "".into() }]),
+        );
+        assert_schema_accepted(
+            &schema_for::<Source>(),
+            &instances(&[Source::Image {
+                image: "nginx".into(),
+            }]),
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_instances() {
+        let schema = schema_for::<Spec>();
+        let valid = serde_json::to_value(Spec {
+            replicas: 1,
+            ports: vec![],
+            labels: BTreeMap::new(),
+            storage: Storage::Ephemeral {
+                medium: "Memory".into(),
+            },
+            source: Source::Image {
+                image: "nginx".into(),
+            },
+            paused: None,
+        })
+        .unwrap();
+        assert_eq!(validate_instance(&schema, &valid), Ok(()));
+
+        let with = |pointer: &str, value: Value| {
+            let mut instance = valid.clone();
+            *instance.pointer_mut(pointer).unwrap() = value;
+            validate_instance(&schema, &instance).unwrap_err().to_string()
+        };
+        assert_eq!(
+            with("/replicas", json!("1")),
+            "/properties/replicas: \"1\" at `.replicas` isn't of type integer"
+        );
+        assert_eq!(
+            with("/ports", json!([{ "containerPort": 80, "protocol": "Sctp" }])),
+            "/properties/ports/items/properties/protocol: \"Sctp\" at `.ports[0].protocol` isn't one of the \
+             `enum` values"
+        );
+        assert_eq!(
+            with("/source", json!({})),
+            "/properties/source: the value at `.source` doesn't match any of the `anyOf` variants"
+        );
+        assert_eq!(
+            with("/storage", json!({ "type": "Persistent" })),
+            "/properties/storage: the value at `.storage` matches 0 of the `oneOf` variants, rather than \
+             exactly one"
+        );
+        assert_eq!(
+            with("/labels", json!({ "app": 1 })),
+            "/properties/labels/additionalProperties: 1 at `.labels.app` isn't of type string"
+        );
+        assert_eq!(
+            with("/paused", json!({ "enabled": true })),
+            "/properties/paused: {\"enabled\":true} at `.paused` isn't of type boolean"
+        );
+        let mut instance = valid.clone();
+        instance["extra"] = json!(true);
+        assert_eq!(
+            validate_instance(&schema, &instance),
+            Err(SchemaIssue {
+                pointer: "".into(),
+                kind: SchemaIssueKind::InvalidValue {
+                    reason: "the unknown field `extra` would be pruned".into()
+                },
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "instance 0: /: the object is missing the required field")]
+    fn panics_on_rejected_instances() {
+        assert_schema_accepted(&schema_for::<Spec>(), &[json!({})]);
+    }
+}
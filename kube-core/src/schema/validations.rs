@@ -0,0 +1,196 @@
+use super::SchemaRewriteError;
+use crate::Rule;
+use serde_json::Value;
+
+/// CEL validation rules for individual fields of a schema
+///
+/// This collects rules for fields by their path, so that they can be declared next to the fields they
+/// constrain and be attached to a generated schema afterwards with [`FieldValidations::attach`].
+///
+/// ```
+/// use kube_core::{schema::FieldValidations, Rule};
+/// use serde_json::json;
+///
+/// let mut schema = json!({
+///     "type": "object",
+///     "properties": {
+///         "spec": {
+///             "type": "object",
+///             "properties": { "replicas": { "type": "integer" } },
+///         },
+///     },
+/// });
+/// FieldValidations::new()
+///     .rule("spec.replicas", Rule::new("self >= 1").message("must have at least one replica"))
+///     .attach(&mut schema)?;
+/// assert_eq!(
+///     schema["properties"]["spec"]["properties"]["replicas"]["x-kubernetes-validations"],
+///     json!([{ "rule": "self >= 1", "message": "must have at least one replica" }])
+/// );
+/// # Ok::<(), kube_core::schema::SchemaRewriteError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FieldValidations {
+    fields: Vec<(String, Vec<Rule>)>,
+}
+
+impl FieldValidations {
+    /// Create an empty set of validations
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a rule for the field at `path`
+    ///
+    /// See [`attach_field_validations`] for the syntax of `path`.
+    #[must_use]
+    pub fn rule(mut self, path: impl Into<String>, rule: impl Into<Rule>) -> Self {
+        let path = path.into();
+        match self.fields.iter_mut().find(|(field, _)| *field == path) {
+            Some((_, rules)) => rules.push(rule.into()),
+            None => self.fields.push((path, vec![rule.into()])),
+        }
+        self
+    }
+
+    /// Attach all rules to their fields in `schema`
+    pub fn attach(&self, schema: &mut Value) -> Result<(), SchemaRewriteError> {
+        attach_field_validations(schema, &self.fields)
+    }
+}
+
+/// Add CEL validation rules (`x-kubernetes-validations`) to fields of a generated schema
+///
+/// Fields are addressed by the dot-separated names of their properties, such as `spec.replicas`, or by
+/// the empty string for the root of the schema. Items of arrays and values of maps are part of the field
+/// that contains them, so `spec.ports.port` refers to the `port` of each entry of the `spec.ports` array.
+///
+/// The schema must not use references (`$ref`), which is the case for schemas that were generated for
+/// CRDs. Rules are appended to any rules that are already present on the field.
+pub fn attach_field_validations(
+    schema: &mut Value,
+    validations: &[(String, Vec<Rule>)],
+) -> Result<(), SchemaRewriteError> {
+    for (path, rules) in validations {
+        let Some(Value::Object(field)) = find_field(schema, path) else {
+            return Err(SchemaRewriteError::FieldNotFound { path: path.clone() });
+        };
+        let existing = field
+            .entry("x-kubernetes-validations")
+            .or_insert_with(|| Value::Array(Vec::new()));
+        let Value::Array(existing) = existing else {
+            return Err(SchemaRewriteError::FieldNotFound { path: path.clone() });
+        };
+        for rule in rules {
+            let rule = serde_json::to_value(rule).map_err(|source| SchemaRewriteError::InvalidSchema {
+                pointer: path.clone(),
+                source,
+            })?;
+            existing.push(rule);
+        }
+    }
+    Ok(())
+}
+
+fn find_field<'a>(schema: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    let mut field = schema;
+    for name in path.split('.').filter(|name| !name.is_empty()) {
+        // Look through arrays and maps to the schema of their elements
+        while field.get("properties").is_none() {
+            let elements = match field.get("items") {
+                Some(Value::Object(_)) => "items",
+                _ => "additionalProperties",
+            };
+            field = field.get_mut(elements).filter(|elements| elements.is_object())?;
+        }
+        field = field.get_mut("properties")?.get_mut(name)?;
+    }
+    Some(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{attach_field_validations, FieldValidations};
+    use crate::{schema::SchemaRewriteError, Rule};
+    use serde_json::json;
+
+    #[test]
+    fn attaches_rules_to_nested_fields() {
+        let mut schema = json!({
+            "type": "object",
+            "properties": {
+                "spec": {
+                    "type": "object",
+                    "properties": {
+                        "ports": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": { "port": { "type": "integer" } },
+                            },
+                        },
+                        "backends": {
+                            "type": "object",
+                            "additionalProperties": {
+                                "type": "object",
+                                "properties": { "url": { "type": "string" } },
+                            },
+                        },
+                        "name": {
+                            "type": "string",
+                            "x-kubernetes-validations": [{ "rule": "self != ''" }],
+                        },
+                    },
+                },
+            },
+        });
+        FieldValidations::new()
+            .rule("", "has(self.spec)")
+            .rule(
+                "spec.ports.port",
+                Rule::new("self > 0").message("must be positive"),
+            )
+            .rule("spec.ports.port", Rule::new("self < 65536"))
+            .rule(
+                "spec.backends.url",
+                ("self.startsWith('https://')", "must use https"),
+            )
+            .rule("spec.name", Rule::new("self == oldSelf").field_path(".name"))
+            .attach(&mut schema)
+            .unwrap();
+
+        assert_eq!(
+            schema["x-kubernetes-validations"],
+            json!([{ "rule": "has(self.spec)" }])
+        );
+        let spec = &schema["properties"]["spec"]["properties"];
+        assert_eq!(
+            spec["ports"]["items"]["properties"]["port"]["x-kubernetes-validations"],
+            json!([
+                { "rule": "self > 0", "message": "must be positive" },
+                { "rule": "self < 65536" },
+            ])
+        );
+        assert_eq!(
+            spec["backends"]["additionalProperties"]["properties"]["url"]["x-kubernetes-validations"],
+            json!([{ "rule": "self.startsWith('https://')", "message": "must use https" }])
+        );
+        assert_eq!(
+            spec["name"]["x-kubernetes-validations"],
+            json!([
+                { "rule": "self != ''" },
+                { "rule": "self == oldSelf", "fieldPath": ".name" },
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        let mut schema = json!({ "type": "object", "properties": { "spec": { "type": "object" } } });
+        let validations = [("spec.missing".to_string(), vec![Rule::new("true")])];
+        match attach_field_validations(&mut schema, &validations) {
+            Err(SchemaRewriteError::FieldNotFound { path }) => assert_eq!(path, "spec.missing"),
+            other => panic!("expected a missing field error, got {other:?}"),
+        }
+    }
+}
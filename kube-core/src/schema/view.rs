@@ -0,0 +1,210 @@
+use serde_json::Value;
+
+/// A read-only view of a (structural) schema, with typed accessors for its keywords
+///
+/// This is meant for tooling that inspects generated schemas, without having to match on the raw JSON.
+///
+/// ```
+/// use kube_core::schema::StructuralSchemaView;
+///
+/// let schema = serde_json::json!({
+///     "type": "object",
+///     "properties": {
+///         "replicas": { "type": "integer" },
+///         "labels": { "type": "object", "additionalProperties": { "type": "string" } },
+///     },
+///     "required": ["replicas"],
+/// });
+/// let view = StructuralSchemaView::new(&schema);
+/// assert_eq!(view.instance_type(), Some("object"));
+/// assert_eq!(view.required().collect::<Vec<_>>(), ["replicas"]);
+/// let labels = view.property("labels").unwrap();
+/// assert_eq!(labels.additional_properties().unwrap().instance_type(), Some("string"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StructuralSchemaView<'a> {
+    schema: &'a Value,
+}
+
+impl<'a> StructuralSchemaView<'a> {
+    /// View `schema`
+    pub fn new(schema: &'a Value) -> Self {
+        Self { schema }
+    }
+
+    /// The underlying JSON of the schema
+    pub fn as_value(&self) -> &'a Value {
+        self.schema
+    }
+
+    /// The `type` of the schema, if it has a single one
+    ///
+    /// ```
+    /// # use kube_core::schema::StructuralSchemaView;
+    /// let schema = serde_json::json!({ "type": "string" });
+    /// assert_eq!(StructuralSchemaView::new(&schema).instance_type(), Some("string"));
+    /// ```
+    pub fn instance_type(&self) -> Option<&'a str> {
+        self.schema.get("type").and_then(Value::as_str)
+    }
+
+    /// The `properties` of the schema, in the order that they are defined in
+    ///
+    /// ```
+    /// # use kube_core::schema::StructuralSchemaView;
+    /// let schema = serde_json::json!({ "type": "object", "properties": { "name": { "type": "string" } } });
+    /// let properties = StructuralSchemaView::new(&schema).properties().collect::<Vec<_>>();
+    /// assert_eq!(properties.len(), 1);
+    /// assert_eq!(properties[0].0, "name");
+    /// assert_eq!(properties[0].1.instance_type(), Some("string"));
+    /// ```
+    pub fn properties(&self) -> impl Iterator<Item = (&'a str, StructuralSchemaView<'a>)> {
+        self.schema
+            .get("properties")
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+            .map(|(name, property)| (name.as_str(), StructuralSchemaView::new(property)))
+    }
+
+    /// The property named `name`, if the schema defines it
+    pub fn property(&self, name: &str) -> Option<StructuralSchemaView<'a>> {
+        self.schema
+            .get("properties")
+            .and_then(|properties| properties.get(name))
+            .map(StructuralSchemaView::new)
+    }
+
+    /// The names of the `required` properties
+    ///
+    /// ```
+    /// # use kube_core::schema::StructuralSchemaView;
+    /// let schema = serde_json::json!({ "type": "object", "required": ["a", "b"] });
+    /// assert_eq!(StructuralSchemaView::new(&schema).required().collect::<Vec<_>>(), ["a", "b"]);
+    /// ```
+    pub fn required(&self) -> impl Iterator<Item = &'a str> {
+        self.schema
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+    }
+
+    /// The values allowed by `enum`
+    ///
+    /// ```
+    /// # use kube_core::schema::StructuralSchemaView;
+    /// let schema = serde_json::json!({ "type": "string", "enum": ["A", "B"] });
+    /// assert_eq!(StructuralSchemaView::new(&schema).enum_values(), Some(&["A".into(), "B".into()][..]));
+    /// ```
+    pub fn enum_values(&self) -> Option<&'a [Value]> {
+        self.schema
+            .get("enum")
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+    }
+
+    /// The schema of the values of a map (`additionalProperties`)
+    ///
+    /// ```
+    /// # use kube_core::schema::StructuralSchemaView;
+    /// let schema = serde_json::json!({ "type": "object", "additionalProperties": { "type": "integer" } });
+    /// let values = StructuralSchemaView::new(&schema).additional_properties().unwrap();
+    /// assert_eq!(values.instance_type(), Some("integer"));
+    /// ```
+    pub fn additional_properties(&self) -> Option<StructuralSchemaView<'a>> {
+        self.schema
+            .get("additionalProperties")
+            .map(StructuralSchemaView::new)
+    }
+
+    /// The schema of the items of an array
+    pub fn items(&self) -> Option<StructuralSchemaView<'a>> {
+        self.schema
+            .get("items")
+            .filter(|items| items.is_object())
+            .map(StructuralSchemaView::new)
+    }
+
+    /// The value of the extension (or any other keyword) named `key`
+    ///
+    /// ```
+    /// # use kube_core::schema::StructuralSchemaView;
+    /// let schema = serde_json::json!({ "x-kubernetes-int-or-string": true });
+    /// let view = StructuralSchemaView::new(&schema);
+    /// assert_eq!(view.extension("x-kubernetes-int-or-string"), Some(&true.into()));
+    /// ```
+    pub fn extension(&self, key: &str) -> Option<&'a Value> {
+        self.schema.get(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StructuralSchemaView;
+    use crate::schema::tests::schema_for;
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    enum Protocol {
+        Tcp,
+        Udp,
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Port {
+        port: u16,
+        protocol: Option<Protocol>,
+    }
+
+    #[derive(Serialize, Deserialize, JsonSchema)]
+    #[allow(dead_code)]
+    struct Spec {
+        name: String,
+        ports: Vec<Port>,
+        labels: BTreeMap<String, String>,
+        #[schemars(extend("x-kubernetes-list-type" = "set"))]
+        hosts: Vec<String>,
+    }
+
+    #[test]
+    fn views_generated_schema() {
+        let schema = schema_for::<Spec>();
+        let view = StructuralSchemaView::new(&schema);
+        assert_eq!(view.instance_type(), Some("object"));
+        let mut names = view.properties().map(|(name, _)| name).collect::<Vec<_>>();
+        names.sort_unstable();
+        assert_eq!(names, ["hosts", "labels", "name", "ports"]);
+        assert_eq!(view.required().collect::<Vec<_>>(), [
+            "hosts", "labels", "name", "ports"
+        ]);
+
+        let labels = view.property("labels").unwrap();
+        assert_eq!(
+            labels.additional_properties().unwrap().instance_type(),
+            Some("string")
+        );
+        assert_eq!(view.additional_properties(), None);
+
+        let hosts = view.property("hosts").unwrap();
+        assert_eq!(hosts.extension("x-kubernetes-list-type"), Some(&json!("set")));
+        assert_eq!(hosts.extension("x-kubernetes-map-type"), None);
+
+        let port = view.property("ports").unwrap().items().unwrap();
+        assert_eq!(port.required().collect::<Vec<_>>(), ["port"]);
+        let protocol = port.property("protocol").unwrap();
+        assert_eq!(protocol.instance_type(), Some("string"));
+        assert_eq!(
+            protocol.enum_values(),
+            Some(&[json!("Tcp"), json!("Udp"), Value::Null][..])
+        );
+        assert_eq!(protocol.extension("nullable"), Some(&json!(true)));
+        assert_eq!(port.property("missing"), None);
+    }
+}
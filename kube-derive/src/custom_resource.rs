@@ -634,7 +634,7 @@ pub(crate) fn derive(input: proc_macro2::TokenStream) -> proc_macro2::TokenStrea
                     s.meta_schema = None;
                 })
                 .with_transform(#schemars::transform::AddNullable::default())
-                .with_transform(#kube_core::schema::StructuralSchemaRewriter)
+                .with_transform(#kube_core::schema::StructuralSchemaRewriter::default())
                 .into_generator();
             let schema = generate.into_root_schema_for::<Self>();
         }
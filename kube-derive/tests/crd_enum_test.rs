@@ -155,3 +155,40 @@ fn test_crd_schema_matches_expected() {
         .unwrap()
     );
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, JsonSchema)]
+enum Mode {
+    Fast,
+    Slow,
+}
+
+#[derive(CustomResource, Serialize, Deserialize, Debug, Clone, JsonSchema)]
+#[kube(group = "clux.dev", version = "v1", kind = "OptionalEnum")]
+struct OptionalEnumSpec {
+    /// How fast to go
+    mode: Option<Mode>,
+}
+
+#[test]
+fn test_optional_enum_schema_lists_null() {
+    use kube::core::CustomResourceExt;
+
+    let crd = OptionalEnum::crd();
+    let schema = crd.spec.versions[0].schema.as_ref().unwrap();
+    let schema = schema.open_api_v3_schema.as_ref().unwrap();
+    // The apiserver validates `null` against the `enum` too, so it has to be listed
+    assert_eq!(
+        serde_json::to_value(&schema.properties.as_ref().unwrap()["spec"]).unwrap(),
+        serde_json::json!({
+            "properties": {
+                "mode": {
+                    "description": "How fast to go",
+                    "enum": ["Fast", "Slow", null],
+                    "nullable": true,
+                    "type": "string"
+                }
+            },
+            "type": "object"
+        })
+    );
+}